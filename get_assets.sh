@@ -1,5 +1,5 @@
 VERSION="1.16.3"
-DATA_FILES=("blocks.min.json" "entities.min.json" "block_entities.min.json" "models.min.json")
+DATA_FILES=("blocks.min.json" "entities.min.json" "block_entities.min.json" "models.min.json" "biomes.min.json" "items.min.json")
 
 mkdir assets
 for file in ${DATA_FILES[@]}; do
@@ -11,5 +11,6 @@ cd temp
 curl https://launcher.mojang.com/v1/objects/1321521b2caf934f7fc9665aab7e059a7b2bfcdf/client.jar >> ${VERSION}.jar
 unzip ${VERSION}.jar
 mv assets/minecraft/textures ../assets/textures
+mv assets/minecraft/models ../assets/models
 cd ..
 rm -rf temp
@@ -0,0 +1,12 @@
+#version 430
+
+in vec2 uv;
+
+uniform sampler2D tex;
+uniform vec4 tint;
+
+out vec4 color;
+
+void main() {
+    color = texture(tex, uv) * tint;
+}
@@ -0,0 +1,16 @@
+#version 430
+
+in vec3 position;
+in vec2 tex_coords;
+
+out vec2 uv;
+
+uniform mat4 pvmat;
+uniform mat4 tmat;
+uniform vec2 uvOffset;
+uniform vec2 uvScale;
+
+void main() {
+    uv = tex_coords * uvScale + uvOffset;
+    gl_Position = pvmat * tmat * vec4(position, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 430
+
+in vec3 pos;
+in float light;
+
+uniform vec4 cloudCol;
+uniform vec4 fogCol;
+uniform float fogNear;
+uniform float fogFar;
+
+out vec4 color;
+
+void main() {
+    // Clouds fade out towards the fog distance instead of ending in a hard line
+    float fogDistance = length(pos.xz);
+    float fogAmount = smoothstep(fogNear, fogFar, fogDistance);
+    float fade = 1.0 - smoothstep(fogFar * 0.8, fogFar, fogDistance);
+
+    vec3 col = mix(cloudCol.rgb * light, fogCol.rgb, fogAmount);
+    color = vec4(col, cloudCol.a * fade);
+}
@@ -0,0 +1,19 @@
+#version 430
+
+in vec3 position;
+in float shade;
+
+out vec3 pos;
+out float light;
+
+uniform mat4 pvmat;
+uniform mat4 tmat;
+uniform vec3 camPos;
+
+void main() {
+    light = shade;
+
+    vec4 world_pos = tmat * vec4(position, 1.0);
+    pos = world_pos.xyz - camPos;
+    gl_Position = pvmat * world_pos;
+}
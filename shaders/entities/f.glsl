@@ -0,0 +1,26 @@
+#version 430
+
+in vec2 uv;
+in vec3 pos;
+in float light;
+
+uniform sampler2D tex;
+uniform vec4 fogCol;
+uniform float fogNear;
+uniform float fogFar;
+uniform float daylight;
+
+out vec4 color;
+
+void main() {
+    vec4 texCol = texture(tex, uv);
+    if (texCol.a < 0.1) {
+        discard;
+    }
+    texCol.rgb *= light * daylight;
+
+    float fogDistance = length(pos);
+    float fogAmount = smoothstep(fogNear, fogFar, fogDistance);
+
+    color = vec4(mix(texCol.rgb, fogCol.rgb, fogAmount), 1.0);
+}
@@ -0,0 +1,25 @@
+#version 430
+
+in vec3 position;
+in vec2 tex_coords;
+in vec3 normal;
+
+out vec2 uv;
+out vec3 pos;
+out float light;
+
+uniform mat4 pvmat;
+uniform mat4 tmat;
+
+void main() {
+    uv = tex_coords;
+
+    // Faces are lit by the way they face, like block faces are
+    vec3 n = normalize(mat3(tmat) * normal);
+    light = 0.8 + 0.2 * n.y - 0.2 * abs(n.x);
+
+    vec4 world_pos = tmat * vec4(position, 1.0);
+    vec4 position = pvmat * world_pos;
+    pos = position.xyz;
+    gl_Position = position;
+}
@@ -0,0 +1,28 @@
+#version 430
+
+in vec3 uv;
+in vec4 tint;
+in float glow;
+in vec3 pos;
+
+uniform sampler2DArray textures;
+uniform vec4 fogCol;
+uniform float fogNear;
+uniform float fogFar;
+uniform float daylight;
+
+out vec4 color;
+
+void main() {
+    vec4 texCol = texture(textures, uv) * tint;
+    if (texCol.a < 0.1) {
+        discard;
+    }
+    // Glowing particles like flames aren't darkened at night
+    texCol.rgb *= mix(daylight, 1.0, glow);
+
+    float fogDistance = length(pos);
+    float fogAmount = smoothstep(fogNear, fogFar, fogDistance);
+
+    color = vec4(mix(texCol.rgb, fogCol.rgb, fogAmount), 1.0);
+}
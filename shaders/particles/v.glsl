@@ -0,0 +1,30 @@
+#version 430
+
+in vec2 corner;
+
+in vec3 centre;
+in float size;
+in vec4 colour;
+in float layer;
+in vec4 uv_rect;
+in float emissive;
+
+out vec3 uv;
+out vec4 tint;
+out float glow;
+out vec3 pos;
+
+uniform mat4 pvmat;
+uniform vec3 camRight;
+uniform vec3 camUp;
+uniform vec3 camPos;
+
+void main() {
+    uv = vec3(uv_rect.xy + (corner * 0.5 + 0.5) * uv_rect.zw, layer);
+    tint = colour;
+    glow = emissive;
+
+    vec3 world_pos = centre + (camRight * corner.x + camUp * corner.y) * size;
+    pos = world_pos - camPos;
+    gl_Position = pvmat * vec4(world_pos, 1.0);
+}
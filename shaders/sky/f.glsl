@@ -0,0 +1,24 @@
+#version 430
+
+in vec3 dir;
+
+uniform vec3 skyCol;
+uniform vec3 fogCol;
+uniform vec4 sunriseCol;
+uniform vec3 sunriseDir;
+
+out vec4 color;
+
+void main() {
+    vec3 d = normalize(dir);
+
+    // Fades from the fog colour at the horizon to the sky colour overhead, and darker below
+    vec3 col = mix(fogCol, skyCol, smoothstep(0.0, 0.4, d.y));
+    col = mix(col, col * 0.2, smoothstep(0.0, 0.4, -d.y));
+
+    // Sunrise and sunset glow around the horizon on the side the sun is on
+    float glow = pow(max(dot(d, sunriseDir), 0.0), 2.0) * (1.0 - smoothstep(0.0, 0.5, abs(d.y)));
+    col = mix(col, sunriseCol.rgb, glow * sunriseCol.a);
+
+    color = vec4(col, 1.0);
+}
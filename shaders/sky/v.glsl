@@ -0,0 +1,13 @@
+#version 430
+
+in vec3 position;
+
+out vec3 dir;
+
+uniform mat4 pvmat;
+uniform mat4 tmat;
+
+void main() {
+    dir = position;
+    gl_Position = pvmat * tmat * vec4(position, 1.0);
+}
@@ -2,23 +2,35 @@
 
 in vec3 tex;
 in vec3 pos;
+in vec3 tintCol;
+in float light;
 
 uniform sampler2DArray textures;
+uniform sampler2D animations;
 uniform vec4 fogCol;
 uniform float fogNear;
 uniform float fogFar;
+uniform float alphaCutoff;
+uniform float daylight;
 
 out vec4 color;
 
 void main() {
-    vec4 texCol = texture(textures, tex);
+    // Animated textures are drawn with whichever frame they're currently on
+    vec4 anim = texelFetch(animations, ivec2(int(tex.z + 0.5), 0), 0);
+    vec4 texCol = mix(
+        texture(textures, vec3(tex.xy, anim.x)),
+        texture(textures, vec3(tex.xy, anim.y)),
+        anim.z
+    );
     
-    if (texCol.a < 0.5) {
+    if (texCol.a < alphaCutoff) {
         discard;
     }
+    texCol.rgb *= tintCol * light * daylight;
 
     float fogDistance = length(pos);
     float fogAmount = smoothstep(fogNear, fogFar, fogDistance);
 
-    color = mix(texCol, fogCol, fogAmount);
+    color = vec4(mix(texCol.rgb, fogCol.rgb, fogAmount), texCol.a);
 }
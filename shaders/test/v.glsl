@@ -1,17 +1,26 @@
 #version 430
 
+// Positions and texture coordinates are fixed point, with this many steps per block
+const float VERTEX_SCALE = 1024.0;
+
 in vec3 position;
-in vec3 tex_coords;
+in float tex_layer;
+in vec2 tex_coords;
+in vec4 colour;
 
 out vec3 tex;
 out vec3 pos;
+out vec3 tintCol;
+out float light;
 
 uniform mat4 pvmat;
 uniform mat4 tmat;
 
 void main() {
-    tex = tex_coords;
-    vec4 world_pos = tmat * vec4(position, 1.0);
+    tex = vec3(tex_coords / VERTEX_SCALE, tex_layer);
+    tintCol = colour.rgb;
+    light = colour.a;
+    vec4 world_pos = tmat * vec4(position / VERTEX_SCALE, 1.0);
     vec4 position = pvmat * world_pos;
     pos = position.xyz;
     gl_Position = position;
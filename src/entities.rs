@@ -4,6 +4,7 @@ use crate::{
 };
 
 pub mod components;
+pub mod models;
 use components::*;
 use glam::Vec3;
 use mcproto_rs::uuid::UUID4;
@@ -12,38 +13,56 @@ pub struct Entity {
     pub id: i32,
     pub uuid: UUID4,
 
-    pub entity_type: &'static resources::Entity,
+    pub entity_type: resources::Entity,
 
     pub data: i32,
 
     pub pos: Vec3,
-    pub last_pos: Vec3,
     pub vel: Vec3,
     pub ori: Orientation,
     pub ori_head: Orientation,
 
     pub on_ground: bool,
+
+    movement: Movement,
+    /// Where the server last said the entity is, which relative moves are added to
+    server_pos: Interpolation<Vec3>,
+    /// Yaw then pitch the server last sent
+    server_rot: Interpolation<(f32, f32)>,
+    server_head_yaw: Interpolation<f32>,
 }
 
 impl Entity {
-    pub fn new(entity_type: u32) -> Entity {
-        Entity {
+    /// Creates an entity of the given type, or nothing if there's no entity with that id
+    pub fn new(entity_type: u32) -> Option<Entity> {
+        let entity_type = match ENTITIES.load().get(&entity_type) {
+            Some(entity_type) => entity_type.clone(),
+            None => {
+                log::error!("No entity with id {}", entity_type);
+                return None;
+            }
+        };
+
+        let movement = Movement::for_entity(&entity_type.name);
+        Some(Entity {
             id: 0,
             uuid: UUID4::random(),
 
-            entity_type: ENTITIES
-                .get(&entity_type)
-                .expect(&format!("No entity with id {}", &entity_type)),
+            entity_type,
             data: 0,
 
             pos: Vec3::new(0.0, 0.0, 0.0),
-            last_pos: Vec3::new(0.0, 0.0, 0.0),
             vel: Vec3::new(0.0, 0.0, 0.0),
             ori: Orientation::new(),
             ori_head: Orientation::new(),
 
             on_ground: true,
-        }
+
+            movement,
+            server_pos: Interpolation::new(Vec3::ZERO),
+            server_rot: Interpolation::new((0.0, 0.0)),
+            server_head_yaw: Interpolation::new(0.0),
+        })
     }
 
     pub fn new_with_values(
@@ -60,21 +79,32 @@ impl Entity {
         vx: f32,
         vy: f32,
         vz: f32,
-    ) -> Entity {
-        Entity {
+    ) -> Option<Entity> {
+        let entity_type = match ENTITIES.load().get(&entity_type) {
+            Some(entity_type) => entity_type.clone(),
+            None => {
+                log::error!("Failed to get entity from ID: {}", entity_type);
+                return None;
+            }
+        };
+
+        let movement = Movement::for_entity(&entity_type.name);
+        Some(Entity {
             id,
             uuid,
-            entity_type: ENTITIES
-                .get(&entity_type)
-                .expect(&format!("Failed to get entity from ID: {}", entity_type)),
+            entity_type,
             data,
             pos: Vec3::new(px, py, pz),
-            last_pos: Vec3::new(px, py, pz),
             vel: Vec3::new(vx, vy, vz),
             ori: Orientation::new_with_values(yaw, pitch, 0.0, 0.0),
-            ori_head: Orientation::new_with_values(0.0, head_pitch, -90.0, 90.0),
+            ori_head: Orientation::new_with_values(yaw, head_pitch, -90.0, 90.0),
             on_ground: true,
-        }
+
+            movement,
+            server_pos: Interpolation::new(Vec3::new(px, py, pz)),
+            server_rot: Interpolation::new((yaw, pitch)),
+            server_head_yaw: Interpolation::new(yaw),
+        })
     }
 
     pub fn get_id(&self) -> i32 {
@@ -85,19 +115,78 @@ impl Entity {
         self.uuid
     }
 
-    pub fn get_type(&self) -> &'static resources::Entity {
-        self.entity_type
+    pub fn get_type(&self) -> &resources::Entity {
+        &self.entity_type
+    }
+
+    pub fn get_movement(&self) -> Movement {
+        self.movement
+    }
+
+    /// Where the server last said the entity is
+    pub fn get_server_pos(&self) -> Vec3 {
+        self.server_pos.get_target()
+    }
+
+    /// Moves the entity to a position sent by the server. Projectiles jump straight there since
+    /// they're simulated in between, anything else is eased there over a few ticks.
+    pub fn move_to(&mut self, pos: Vec3) {
+        match self.movement {
+            Movement::Interpolated => self.server_pos.set_target(pos),
+            Movement::DeadReckoning { .. } => {
+                self.server_pos = Interpolation::new(pos);
+                self.pos = pos;
+            }
+        }
+    }
+
+    /// Turns the entity to a yaw and pitch in degrees sent by the server
+    pub fn rotate_to(&mut self, yaw: f32, pitch: f32) {
+        self.server_rot.set_target((yaw, pitch));
+    }
+
+    /// Turns the entity's head to a yaw in degrees sent by the server
+    pub fn turn_head_to(&mut self, yaw: f32) {
+        self.server_head_yaw.set_target(yaw);
     }
 
     pub fn update(&mut self, delta: f32) {
-        let mut vel = self.vel;
-        if self.on_ground {
-            vel.y = 0.0;
-        } else {
-            vel.y -= 13.0 * delta;
+        match self.movement {
+            Movement::Interpolated => {
+                if let Some(amount) = self.server_pos.step(delta) {
+                    self.pos = self.pos.lerp(self.server_pos.get_target(), amount);
+                }
+            }
+            Movement::DeadReckoning { gravity, drag } => {
+                // Projectiles that have stopped, like arrows stuck in a block, are left where the
+                // server put them
+                if self.vel != Vec3::ZERO {
+                    let ticks = delta * 20.0;
+                    self.pos += self.vel * delta;
+                    self.vel *= drag.powf(ticks);
+                    // Gravity is per tick per tick, velocity is in blocks per second
+                    self.vel.y -= gravity * 20.0 * ticks;
+                }
+            }
         }
 
-        self.pos += vel * delta;
+        if let Some(amount) = self.server_rot.step(delta) {
+            let (yaw, pitch) = self.server_rot.get_target();
+            self.ori.set(
+                lerp_angle(self.ori.get_yaw(), yaw, amount),
+                lerp_angle(self.ori.get_pitch(), pitch, amount),
+            );
+            // The pitch is where the entity's head is looking
+            self.ori_head.set_pitch(self.ori.get_pitch());
+        }
+        if let Some(amount) = self.server_head_yaw.step(delta) {
+            let yaw = lerp_angle(
+                self.ori_head.get_yaw(),
+                self.server_head_yaw.get_target(),
+                amount,
+            );
+            self.ori_head.set_yaw(yaw);
+        }
     }
 }
 
@@ -110,3 +110,98 @@ impl Orientation {
         Vec3::new(self.yaw, self.pitch, 0.0)
     }
 }
+
+/// How long the game takes to move an entity to a new position or rotation sent by the server,
+/// which is 3 ticks
+pub const INTERPOLATION_TIME: f32 = 3.0 / 20.0;
+
+/// Eases a value towards the last one the server sent over `INTERPOLATION_TIME`, instead of
+/// snapping to it
+#[derive(Debug, Clone, Copy)]
+pub struct Interpolation<T: Copy> {
+    target: T,
+    remaining: f32,
+}
+
+impl<T: Copy> Interpolation<T> {
+    pub fn new(value: T) -> Interpolation<T> {
+        Interpolation {
+            target: value,
+            remaining: 0.0,
+        }
+    }
+
+    /// Starts moving towards a new value
+    pub fn set_target(&mut self, target: T) {
+        self.target = target;
+        self.remaining = INTERPOLATION_TIME;
+    }
+
+    pub fn get_target(&self) -> T {
+        self.target
+    }
+
+    /// Moves on by `delta` seconds, returning how much of the rest of the way to the target to
+    /// move this frame, or `None` if the target has already been reached
+    pub fn step(&mut self, delta: f32) -> Option<f32> {
+        if self.remaining <= 0.0 {
+            return None;
+        }
+
+        let amount = (delta / self.remaining).min(1.0);
+        self.remaining -= delta;
+        Some(amount)
+    }
+}
+
+/// Moves part of the way from one angle in degrees to another, going the shortest way round
+pub fn lerp_angle(from: f32, to: f32, amount: f32) -> f32 {
+    let diff = (to - from + 180.0).rem_euclid(360.0) - 180.0;
+    from + diff * amount
+}
+
+/// How an entity moves between the updates the server sends about it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Movement {
+    /// Eased towards each position the server sends
+    Interpolated,
+    /// Simulated from its velocity like the game does for projectiles, since they move too fast
+    /// to wait for updates. Gravity is in blocks per tick per tick, and the velocity is multiplied
+    /// by `drag` every tick.
+    DeadReckoning { gravity: f32, drag: f32 },
+}
+
+impl Movement {
+    /// How a type of entity moves, by its name
+    pub fn for_entity(name: &str) -> Movement {
+        match name {
+            "Arrow" | "Spectral Arrow" | "Trident" => Movement::DeadReckoning {
+                gravity: 0.05,
+                drag: 0.99,
+            },
+            "Snowball" | "Egg" | "Ender Pearl" => Movement::DeadReckoning {
+                gravity: 0.03,
+                drag: 0.99,
+            },
+            "Potion" => Movement::DeadReckoning {
+                gravity: 0.05,
+                drag: 0.99,
+            },
+            "Experience Bottle" => Movement::DeadReckoning {
+                gravity: 0.07,
+                drag: 0.99,
+            },
+            "Llama Spit" => Movement::DeadReckoning {
+                gravity: 0.06,
+                drag: 0.99,
+            },
+            "Fireball" | "Small Fireball" | "Dragon Fireball" | "Wither Skull" => {
+                Movement::DeadReckoning {
+                    gravity: 0.0,
+                    drag: 1.0,
+                }
+            }
+            _ => Movement::Interpolated,
+        }
+    }
+}
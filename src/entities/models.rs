@@ -0,0 +1,466 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{renderer::EntityVertex, resources};
+
+/// Index of the right arm in the parts of humanoid models like the player's
+pub const RIGHT_ARM: usize = 2;
+
+/// A box in an entity model. Models are laid out the same way the game's are: in pixels, with y
+/// pointing down and the front of the entity facing -z.
+#[derive(Debug, Clone, Copy)]
+pub struct Cuboid {
+    /// Corner of the box relative to the pivot of the part it's in
+    pub from: Vec3,
+    pub size: Vec3,
+    /// Top left of the box's faces in the texture, in pixels
+    pub uv: Vec2,
+    /// How far to grow the box in every direction without changing its texture, used for layers
+    /// drawn over the top of another box like hats
+    pub inflate: f32,
+    /// Flips the texture left to right, so a limb can share the texture of the one opposite it
+    pub mirror: bool,
+}
+
+impl Cuboid {
+    pub fn new(uv: [f32; 2], from: [f32; 3], size: [f32; 3]) -> Cuboid {
+        Cuboid {
+            from: Vec3::from(from),
+            size: Vec3::from(size),
+            uv: Vec2::from(uv),
+            inflate: 0.0,
+            mirror: false,
+        }
+    }
+
+    pub fn inflated(mut self, inflate: f32) -> Cuboid {
+        self.inflate = inflate;
+        self
+    }
+
+    pub fn mirrored(mut self) -> Cuboid {
+        self.mirror = true;
+        self
+    }
+
+    /// Adds the six faces of this box to `verts`, with texture coordinates scaled to fit a texture
+    /// `texture_size` pixels big
+    fn generate_vertices(&self, texture_size: Vec2, verts: &mut Vec<EntityVertex>) {
+        let min = self.from - self.inflate;
+        let max = self.from + self.size + self.inflate;
+        let corner = |x: usize, y: usize, z: usize| {
+            Vec3::new([min.x, max.x][x], [min.y, max.y][y], [min.z, max.z][z])
+        };
+
+        let (w, h, d) = (self.size.x, self.size.y, self.size.z);
+        let (u, v) = (self.uv.x, self.uv.y);
+        let (mut right, mut left) = (Vec2::new(u, v + d), Vec2::new(u + d + w, v + d));
+        if self.mirror {
+            std::mem::swap(&mut right, &mut left);
+        }
+
+        // Corners of each face are top left, bottom left, bottom right then top right as seen from
+        // outside the box, along with where the face is in the texture and how big it is there
+        let faces = [
+            // Top, which has -y as its normal since y points down
+            (
+                [
+                    corner(0, 0, 1),
+                    corner(0, 0, 0),
+                    corner(1, 0, 0),
+                    corner(1, 0, 1),
+                ],
+                Vec2::new(u + d, v),
+                Vec2::new(w, d),
+                -Vec3::Y,
+            ),
+            // Bottom
+            (
+                [
+                    corner(0, 1, 0),
+                    corner(0, 1, 1),
+                    corner(1, 1, 1),
+                    corner(1, 1, 0),
+                ],
+                Vec2::new(u + d + w, v),
+                Vec2::new(w, d),
+                Vec3::Y,
+            ),
+            // Right, from the entity's point of view
+            (
+                [
+                    corner(0, 0, 1),
+                    corner(0, 1, 1),
+                    corner(0, 1, 0),
+                    corner(0, 0, 0),
+                ],
+                right,
+                Vec2::new(d, h),
+                -Vec3::X,
+            ),
+            // Front
+            (
+                [
+                    corner(0, 0, 0),
+                    corner(0, 1, 0),
+                    corner(1, 1, 0),
+                    corner(1, 0, 0),
+                ],
+                Vec2::new(u + d, v + d),
+                Vec2::new(w, h),
+                -Vec3::Z,
+            ),
+            // Left
+            (
+                [
+                    corner(1, 0, 0),
+                    corner(1, 1, 0),
+                    corner(1, 1, 1),
+                    corner(1, 0, 1),
+                ],
+                left,
+                Vec2::new(d, h),
+                Vec3::X,
+            ),
+            // Back
+            (
+                [
+                    corner(1, 0, 1),
+                    corner(1, 1, 1),
+                    corner(0, 1, 1),
+                    corner(0, 0, 1),
+                ],
+                Vec2::new(u + d + w + d, v + d),
+                Vec2::new(w, h),
+                Vec3::Z,
+            ),
+        ];
+
+        for (corners, uv_from, uv_size, normal) in faces {
+            let (mut u0, mut u1) = (uv_from.x, uv_from.x + uv_size.x);
+            if self.mirror {
+                std::mem::swap(&mut u0, &mut u1);
+            }
+            let (v0, v1) = (uv_from.y, uv_from.y + uv_size.y);
+            let uvs = [
+                Vec2::new(u0, v0),
+                Vec2::new(u0, v1),
+                Vec2::new(u1, v1),
+                Vec2::new(u1, v0),
+            ];
+
+            for i in [0, 1, 2, 0, 2, 3] {
+                let uv = uvs[i] / texture_size;
+                verts.push(EntityVertex {
+                    position: corners[i].to_array(),
+                    // Textures are stored upside down, so flip v
+                    tex_coords: [uv.x, 1.0 - uv.y],
+                    normal: normal.to_array(),
+                });
+            }
+        }
+    }
+}
+
+/// A group of boxes in a model that move together around a pivot
+#[derive(Debug, Clone)]
+pub struct ModelPart {
+    pub pivot: Vec3,
+    /// Rotation around the pivot in radians, applied around z then y then x
+    pub rotation: Vec3,
+    /// Whether this part turns with the entity's head rather than its body
+    pub head: bool,
+    pub cubes: Vec<Cuboid>,
+}
+
+impl ModelPart {
+    pub fn new(pivot: [f32; 3], cubes: Vec<Cuboid>) -> ModelPart {
+        ModelPart {
+            pivot: Vec3::from(pivot),
+            rotation: Vec3::ZERO,
+            head: false,
+            cubes,
+        }
+    }
+
+    /// A part that turns with the entity's head
+    pub fn new_head(pivot: [f32; 3], cubes: Vec<Cuboid>) -> ModelPart {
+        ModelPart {
+            head: true,
+            ..ModelPart::new(pivot, cubes)
+        }
+    }
+
+    pub fn rotated(mut self, x: f32, y: f32, z: f32) -> ModelPart {
+        self.rotation = Vec3::new(x, y, z);
+        self
+    }
+
+    pub fn generate_vertices(&self, texture_size: Vec2) -> Vec<EntityVertex> {
+        let mut verts = Vec::with_capacity(self.cubes.len() * 36);
+        for cube in &self.cubes {
+            cube.generate_vertices(texture_size, &mut verts);
+        }
+        verts
+    }
+
+    /// Moves this part from its own space into model space. `head_yaw` is how far the head is
+    /// turned from the body and `head_pitch` how far it's looking down, both in radians.
+    pub fn get_transform(&self, head_yaw: f32, head_pitch: f32) -> Mat4 {
+        let mut mat = Mat4::from_translation(self.pivot)
+            * Mat4::from_rotation_z(self.rotation.z)
+            * Mat4::from_rotation_y(self.rotation.y)
+            * Mat4::from_rotation_x(self.rotation.x);
+        if self.head {
+            mat *= Mat4::from_rotation_y(head_yaw) * Mat4::from_rotation_x(head_pitch);
+        }
+        mat
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EntityModel {
+    /// Path of the texture in the assets folder
+    pub texture: &'static str,
+    /// Size the texture coordinates in the model are measured against, so higher resolution
+    /// textures still line up
+    pub texture_size: Vec2,
+    pub parts: Vec<ModelPart>,
+}
+
+impl EntityModel {
+    /// Gets the model for a type of entity, if there is one
+    pub fn for_entity(entity: &resources::Entity) -> Option<EntityModel> {
+        let model = match entity.name.as_str() {
+            "Player" => EntityModel {
+                texture: "textures/entity/steve.png",
+                texture_size: Vec2::new(64.0, 64.0),
+                parts: player_parts(),
+            },
+            "Zombie" => EntityModel {
+                texture: "textures/entity/zombie/zombie.png",
+                texture_size: Vec2::new(64.0, 64.0),
+                parts: humanoid_parts(true, 4.0, false)
+                    .into_iter()
+                    .enumerate()
+                    // Zombies hold their arms out in front of them
+                    .map(|(i, part)| match i {
+                        2 | 3 => part.rotated(-std::f32::consts::FRAC_PI_2, 0.0, 0.0),
+                        _ => part,
+                    })
+                    .collect(),
+            },
+            "Skeleton" => EntityModel {
+                texture: "textures/entity/skeleton/skeleton.png",
+                texture_size: Vec2::new(64.0, 32.0),
+                parts: humanoid_parts(false, 1.0, true),
+            },
+            "Creeper" => EntityModel {
+                texture: "textures/entity/creeper/creeper.png",
+                texture_size: Vec2::new(64.0, 32.0),
+                parts: creeper_parts(),
+            },
+            "Pig" => EntityModel {
+                texture: "textures/entity/pig/pig.png",
+                texture_size: Vec2::new(64.0, 32.0),
+                parts: quadruped_parts(
+                    ModelPart::new_head(
+                        [0.0, 12.0, -6.0],
+                        vec![
+                            Cuboid::new([0.0, 0.0], [-4.0, -4.0, -8.0], [8.0, 8.0, 8.0]),
+                            Cuboid::new([16.0, 16.0], [-2.0, 0.0, -9.0], [4.0, 3.0, 1.0]),
+                        ],
+                    ),
+                    Cuboid::new([28.0, 8.0], [-5.0, -10.0, -7.0], [10.0, 16.0, 8.0]),
+                    [0.0, 11.0, 2.0],
+                    6.0,
+                    [3.0, 7.0, -5.0],
+                ),
+            },
+            "Cow" => EntityModel {
+                texture: "textures/entity/cow/cow.png",
+                texture_size: Vec2::new(64.0, 32.0),
+                parts: quadruped_parts(
+                    ModelPart::new_head(
+                        [0.0, 4.0, -8.0],
+                        vec![
+                            Cuboid::new([0.0, 0.0], [-4.0, -4.0, -6.0], [8.0, 8.0, 6.0]),
+                            Cuboid::new([22.0, 0.0], [-5.0, -5.0, -4.0], [1.0, 3.0, 1.0]),
+                            Cuboid::new([22.0, 0.0], [4.0, -5.0, -4.0], [1.0, 3.0, 1.0]),
+                        ],
+                    ),
+                    Cuboid::new([18.0, 4.0], [-6.0, -10.0, -7.0], [12.0, 18.0, 10.0]),
+                    [0.0, 5.0, 2.0],
+                    12.0,
+                    [4.0, 7.0, -6.0],
+                ),
+            },
+            "Sheep" => EntityModel {
+                texture: "textures/entity/sheep/sheep.png",
+                texture_size: Vec2::new(64.0, 32.0),
+                parts: quadruped_parts(
+                    ModelPart::new_head(
+                        [0.0, 6.0, -8.0],
+                        vec![Cuboid::new([0.0, 0.0], [-3.0, -4.0, -6.0], [6.0, 6.0, 8.0])],
+                    ),
+                    Cuboid::new([28.0, 8.0], [-4.0, -10.0, -7.0], [8.0, 16.0, 6.0]),
+                    [0.0, 5.0, 2.0],
+                    12.0,
+                    [3.0, 7.0, -5.0],
+                ),
+            },
+            "Chicken" => EntityModel {
+                texture: "textures/entity/chicken.png",
+                texture_size: Vec2::new(64.0, 32.0),
+                parts: chicken_parts(),
+            },
+            _ => return None,
+        };
+
+        Some(model)
+    }
+}
+
+/// Matrix moving an entity's model into the world, from its position and the way its body faces
+pub fn get_model_matrix(pos: Vec3, yaw: f32) -> Mat4 {
+    Mat4::from_translation(pos)
+        * Mat4::from_rotation_y(-yaw.to_radians())
+        // Models are in pixels with y pointing down, the front facing -z and their feet 24
+        // pixels below the origin
+        * Mat4::from_scale(Vec3::splat(1.0 / 16.0))
+        * Mat4::from_translation(Vec3::new(0.0, 24.0, 0.0))
+        * Mat4::from_scale(Vec3::new(1.0, -1.0, -1.0))
+}
+
+/// Head, body, arms then legs of a humanoid. `limb` is how thick the arms and legs are, and
+/// textures from before skins had separate left limbs are `legacy` and mirror the right ones.
+fn humanoid_parts(hat: bool, limb: f32, legacy: bool) -> Vec<ModelPart> {
+    let mut head = vec![Cuboid::new([0.0, 0.0], [-4.0, -8.0, -4.0], [8.0, 8.0, 8.0])];
+    if hat {
+        head.push(Cuboid::new([32.0, 0.0], [-4.0, -8.0, -4.0], [8.0, 8.0, 8.0]).inflated(0.5));
+    }
+
+    let arm = |uv: [f32; 2], x: f32| Cuboid::new(uv, [x, -2.0, -limb / 2.0], [limb, 12.0, limb]);
+    let leg = |uv: [f32; 2]| Cuboid::new(uv, [-limb / 2.0, 0.0, -limb / 2.0], [limb, 12.0, limb]);
+    let (left_arm, left_leg) = if legacy {
+        (
+            arm([40.0, 16.0], -1.0).mirrored(),
+            leg([0.0, 16.0]).mirrored(),
+        )
+    } else {
+        (arm([32.0, 48.0], -1.0), leg([16.0, 48.0]))
+    };
+
+    vec![
+        ModelPart::new_head([0.0, 0.0, 0.0], head),
+        ModelPart::new(
+            [0.0, 0.0, 0.0],
+            vec![Cuboid::new(
+                [16.0, 16.0],
+                [-4.0, 0.0, -2.0],
+                [8.0, 12.0, 4.0],
+            )],
+        ),
+        ModelPart::new([-5.0, 2.0, 0.0], vec![arm([40.0, 16.0], 1.0 - limb)]),
+        ModelPart::new([5.0, 2.0, 0.0], vec![left_arm]),
+        ModelPart::new([-1.9, 12.0, 0.0], vec![leg([0.0, 16.0])]),
+        ModelPart::new([1.9, 12.0, 0.0], vec![left_leg]),
+    ]
+}
+
+/// A humanoid with the extra layers players' skins have over their body and limbs
+fn player_parts() -> Vec<ModelPart> {
+    let mut parts = humanoid_parts(true, 4.0, false);
+    let overlays = [
+        Cuboid::new([16.0, 32.0], [-4.0, 0.0, -2.0], [8.0, 12.0, 4.0]),
+        Cuboid::new([40.0, 32.0], [-3.0, -2.0, -2.0], [4.0, 12.0, 4.0]),
+        Cuboid::new([48.0, 48.0], [-1.0, -2.0, -2.0], [4.0, 12.0, 4.0]),
+        Cuboid::new([0.0, 32.0], [-2.0, 0.0, -2.0], [4.0, 12.0, 4.0]),
+        Cuboid::new([0.0, 48.0], [-2.0, 0.0, -2.0], [4.0, 12.0, 4.0]),
+    ];
+    for (part, overlay) in parts[1..].iter_mut().zip(overlays) {
+        part.cubes.push(overlay.inflated(0.25));
+    }
+    parts
+}
+
+/// A four legged animal with its body on its side. `body_pivot` is where the body turns around
+/// and `leg` is how long the legs are, with `legs` giving how far out the legs are to the side,
+/// to the back and to the front.
+fn quadruped_parts(
+    head: ModelPart,
+    body: Cuboid,
+    body_pivot: [f32; 3],
+    leg: f32,
+    legs: [f32; 3],
+) -> Vec<ModelPart> {
+    let [side, back, front] = legs;
+    let y = 24.0 - leg;
+    let leg = || Cuboid::new([0.0, 16.0], [-2.0, 0.0, -2.0], [4.0, leg, 4.0]);
+
+    vec![
+        head,
+        ModelPart::new(body_pivot, vec![body]).rotated(std::f32::consts::FRAC_PI_2, 0.0, 0.0),
+        ModelPart::new([-side, y, back], vec![leg()]),
+        ModelPart::new([side, y, back], vec![leg()]),
+        ModelPart::new([-side, y, front], vec![leg()]),
+        ModelPart::new([side, y, front], vec![leg()]),
+    ]
+}
+
+fn creeper_parts() -> Vec<ModelPart> {
+    let leg = || Cuboid::new([0.0, 16.0], [-2.0, 0.0, -2.0], [4.0, 6.0, 4.0]);
+
+    vec![
+        ModelPart::new_head(
+            [0.0, 6.0, 0.0],
+            vec![Cuboid::new([0.0, 0.0], [-4.0, -8.0, -4.0], [8.0, 8.0, 8.0])],
+        ),
+        ModelPart::new(
+            [0.0, 6.0, 0.0],
+            vec![Cuboid::new(
+                [16.0, 16.0],
+                [-4.0, 0.0, -2.0],
+                [8.0, 12.0, 4.0],
+            )],
+        ),
+        ModelPart::new([-2.0, 18.0, 4.0], vec![leg()]),
+        ModelPart::new([2.0, 18.0, 4.0], vec![leg()]),
+        ModelPart::new([-2.0, 18.0, -4.0], vec![leg()]),
+        ModelPart::new([2.0, 18.0, -4.0], vec![leg()]),
+    ]
+}
+
+fn chicken_parts() -> Vec<ModelPart> {
+    let leg = || Cuboid::new([26.0, 0.0], [-1.0, 0.0, -3.0], [3.0, 5.0, 3.0]);
+
+    vec![
+        ModelPart::new_head(
+            [0.0, 15.0, -4.0],
+            vec![
+                Cuboid::new([0.0, 0.0], [-2.0, -6.0, -2.0], [4.0, 6.0, 3.0]),
+                // Beak and wattle
+                Cuboid::new([14.0, 0.0], [-2.0, -4.0, -4.0], [4.0, 2.0, 2.0]),
+                Cuboid::new([14.0, 4.0], [-1.0, -2.0, -3.0], [2.0, 2.0, 2.0]),
+            ],
+        ),
+        ModelPart::new(
+            [0.0, 16.0, 0.0],
+            vec![Cuboid::new([0.0, 9.0], [-3.0, -4.0, -3.0], [6.0, 8.0, 6.0])],
+        )
+        .rotated(std::f32::consts::FRAC_PI_2, 0.0, 0.0),
+        ModelPart::new([-2.0, 19.0, 1.0], vec![leg()]),
+        ModelPart::new([1.0, 19.0, 1.0], vec![leg()]),
+        ModelPart::new(
+            [-4.0, 13.0, 0.0],
+            vec![Cuboid::new([24.0, 13.0], [0.0, 0.0, -3.0], [1.0, 4.0, 6.0])],
+        ),
+        ModelPart::new(
+            [4.0, 13.0, 0.0],
+            vec![Cuboid::new(
+                [24.0, 13.0],
+                [-1.0, 0.0, -3.0],
+                [1.0, 4.0, 6.0],
+            )],
+        ),
+    ]
+}
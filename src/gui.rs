@@ -8,9 +8,13 @@ use crate::{server::InputState, state::State, Client};
 use self::other_windows::fps_counter;
 
 pub mod chat_windows;
+pub mod hotbar;
 pub mod info_windows;
+pub mod item_icons;
 pub mod other_windows;
 pub mod pause_windows;
+pub mod resource_pack_prompt;
+pub mod world_labels;
 
 pub mod main_menu;
 
@@ -21,6 +25,10 @@ pub fn render(gui_ctx: &Context, cli: &mut Client, t: &Timer) {
                 fps_counter::render(gui_ctx, t.fps(), t.delta());
             }
 
+            world_labels::render_signs(gui_ctx, s, &cli.state.rend.cam);
+            world_labels::render_name_tags(gui_ctx, s, &cli.state.rend.cam);
+            hotbar::render(gui_ctx, s, &mut cli.state.item_icons);
+
             s.render(gui_ctx, &mut cli.window_manager);
         }
         None => match main_menu::render(gui_ctx, cli) {
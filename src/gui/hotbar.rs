@@ -0,0 +1,48 @@
+use egui::{Align2, Color32, Context, FontId, Sense, Stroke, Vec2};
+
+use crate::{inventory::HOTBAR_SLOTS, server::Server};
+
+use super::item_icons::ItemIcons;
+
+const SLOT_SIZE: f32 = 44.0;
+
+/// Draws the hotbar along the bottom of the screen, with the selected slot outlined
+pub fn render(gui_ctx: &Context, server: &Server, icons: &mut ItemIcons) {
+    let inventory = server.get_inventory();
+
+    egui::Area::new("Hotbar")
+        .anchor(Align2::CENTER_BOTTOM, Vec2::new(0.0, -8.0))
+        .interactable(false)
+        .show(gui_ctx, |ui| {
+            ui.spacing_mut().item_spacing = Vec2::ZERO;
+            ui.horizontal(|ui| {
+                for slot in 0..HOTBAR_SLOTS {
+                    let (rect, _) = ui.allocate_exact_size(Vec2::splat(SLOT_SIZE), Sense::hover());
+                    let stroke = if slot == inventory.get_selected() {
+                        Stroke::new(3.0, Color32::WHITE)
+                    } else {
+                        Stroke::new(1.0, Color32::GRAY)
+                    };
+                    ui.painter()
+                        .rect(rect, 0.0, Color32::from_black_alpha(150), stroke);
+
+                    let stack = match inventory.get_hotbar(slot) {
+                        Some(stack) => stack,
+                        None => continue,
+                    };
+                    if let Some(icon) = icons.get(gui_ctx, stack.item) {
+                        egui::Image::new(icon, rect.size()).paint_at(ui, rect.shrink(6.0));
+                    }
+                    if stack.count > 1 {
+                        ui.painter().text(
+                            rect.right_bottom() - Vec2::new(4.0, 2.0),
+                            Align2::RIGHT_BOTTOM,
+                            stack.count,
+                            FontId::proportional(16.0),
+                            Color32::WHITE,
+                        );
+                    }
+                }
+            });
+        });
+}
@@ -1,6 +1,7 @@
 use crate::server::Server;
 use egui::Context;
 
+pub mod block_entity_window;
 pub mod entities_window;
 pub mod players_window;
 pub mod server_info_window;
@@ -9,4 +10,5 @@ pub fn render(gui_ctx: &Context, server: &Server) {
     server_info_window::render(gui_ctx, server);
     entities_window::render(gui_ctx, server);
     players_window::render(gui_ctx, server);
+    block_entity_window::render(gui_ctx, server);
 }
@@ -0,0 +1,99 @@
+use egui::{Color32, Context, Id, RichText, Ui};
+use quartz_nbt::{NbtCompound, NbtTag};
+
+use crate::server::Server;
+
+pub fn render(gui_ctx: &Context, server: &Server) {
+    egui::Window::new("Targeted Block")
+        .id(Id::new("Targeted Block"))
+        .show(gui_ctx, |ui| {
+            let (pos, _) = match server.get_targeted_block() {
+                Some(target) => target,
+                None => {
+                    ui.label(RichText::new("Not looking at a block").color(Color32::LIGHT_GRAY));
+                    return;
+                }
+            };
+
+            ui.horizontal(|ui| {
+                ui.label("Pos: ");
+                ui.label(
+                    RichText::new(format!("{} / {} / {}", pos.x, pos.y, pos.z))
+                        .color(Color32::LIGHT_GRAY),
+                );
+            });
+
+            if let Some(block) = server.get_world().block_at(&pos) {
+                ui.horizontal(|ui| {
+                    ui.label("Block: ");
+                    ui.label(
+                        RichText::new(format!("{} ({})", block.name, block.id))
+                            .color(Color32::LIGHT_GRAY),
+                    );
+                });
+            }
+
+            match server.get_world().get_block_entity(&pos) {
+                Some(be) => {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Block entity: ");
+                        ui.label(RichText::new(&be.id).color(Color32::LIGHT_GRAY));
+                    });
+
+                    if let Some(lines) = be.get_sign_text() {
+                        for line in lines.iter() {
+                            ui.label(RichText::new(line).color(Color32::LIGHT_GRAY));
+                        }
+                    }
+
+                    egui::CollapsingHeader::new("NBT")
+                        .id_source(Id::new("Targeted Block NBT"))
+                        .default_open(true)
+                        .show(ui, |ui| render_compound(ui, &be.nbt, "nbt"));
+                }
+                None => {
+                    ui.label(RichText::new("No block entity").color(Color32::LIGHT_GRAY));
+                }
+            }
+        });
+}
+
+fn render_compound(ui: &mut Ui, compound: &NbtCompound, path: &str) {
+    let mut keys: Vec<_> = compound.inner().keys().collect();
+    keys.sort();
+
+    for key in keys {
+        render_tag(
+            ui,
+            key,
+            &compound.inner()[key],
+            &format!("{}.{}", path, key),
+        );
+    }
+}
+
+fn render_tag(ui: &mut Ui, name: &str, tag: &NbtTag, path: &str) {
+    match tag {
+        NbtTag::Compound(compound) => {
+            egui::CollapsingHeader::new(format!("{} ({} entries)", name, compound.len()))
+                .id_source(Id::new(path))
+                .show(ui, |ui| render_compound(ui, compound, path));
+        }
+        NbtTag::List(list) => {
+            egui::CollapsingHeader::new(format!("{} ({} entries)", name, list.len()))
+                .id_source(Id::new(path))
+                .show(ui, |ui| {
+                    for (i, tag) in list.iter().enumerate() {
+                        render_tag(ui, &i.to_string(), tag, &format!("{}[{}]", path, i));
+                    }
+                });
+        }
+        _ => {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}: ", name));
+                ui.label(RichText::new(tag.to_snbt()).color(Color32::LIGHT_GRAY));
+            });
+        }
+    }
+}
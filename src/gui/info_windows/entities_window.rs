@@ -29,8 +29,12 @@ pub fn render(gui_ctx: &Context, server: &Server) {
             ents_vec.sort_by(|(id1, _), (id2, _)| id1.cmp(id2));
 
             // List each present type of entity under dropdown menus
+            let entities = ENTITIES.load();
             for (type_id, ent) in ents_vec {
-                let name = &ENTITIES.get(type_id).unwrap().name;
+                let name = entities
+                    .get(type_id)
+                    .map(|e| e.name.as_str())
+                    .unwrap_or("Unknown");
 
                 egui::CollapsingHeader::new(format!("{} ({})", name, ent.len()))
                     .id_source(Id::new(name))
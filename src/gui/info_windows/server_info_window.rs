@@ -144,6 +144,52 @@ pub fn render(gui_ctx: &Context, server: &Server) {
             );
         });
 
+        ui.horizontal(|ui| {
+            ui.label("Biome: ");
+            ui.label(
+                RichText::new(
+                    server
+                        .get_world()
+                        .biome_at(&pos)
+                        .map(|b| b.name)
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                )
+                .color(Color32::LIGHT_GRAY),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            let queue = server.get_world().get_mesh_queue();
+            ui.label("Meshes: ");
+            ui.label(
+                RichText::new(format!(
+                    "{} queued / {} building",
+                    queue.len(),
+                    queue.in_flight()
+                ))
+                .color(Color32::LIGHT_GRAY),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Mesh memory: ");
+            ui.label(
+                RichText::new(format!(
+                    "{:.2} MB",
+                    server.get_world().get_mesh_memory() as f32 / (1024.0 * 1024.0)
+                ))
+                .color(Color32::LIGHT_GRAY),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Particles: ");
+            ui.label(
+                RichText::new(format!("{}", server.get_particles().get_count()))
+                    .color(Color32::LIGHT_GRAY),
+            );
+        });
+
         ui.horizontal(|ui| {
             ui.label("Block beneath: ");
 
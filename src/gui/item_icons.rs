@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use egui::{ColorImage, Context, TextureHandle};
+
+use crate::{renderer::item_icons::render_icon, resources::ITEMS};
+
+/// Icons of items uploaded for the GUI, which are drawn the first time they're needed
+pub struct ItemIcons {
+    /// None for items that don't have an icon
+    icons: HashMap<u32, Option<TextureHandle>>,
+}
+
+impl ItemIcons {
+    pub fn new() -> ItemIcons {
+        ItemIcons {
+            icons: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, gui_ctx: &Context, item: u32) -> Option<&TextureHandle> {
+        self.icons
+            .entry(item)
+            .or_insert_with(|| {
+                let items = ITEMS.load();
+                let item = items.get(&item)?;
+                let icon = render_icon(item)?;
+                let size = [icon.width() as usize, icon.height() as usize];
+                Some(gui_ctx.load_texture(
+                    &item.name,
+                    ColorImage::from_rgba_unmultiplied(size, icon.as_raw()),
+                ))
+            })
+            .as_ref()
+    }
+
+    /// Forgets every icon so they're drawn again, for when the resource packs change
+    pub fn clear(&mut self) {
+        self.icons.clear();
+    }
+}
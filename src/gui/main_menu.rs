@@ -1,4 +1,4 @@
-use egui::{Align2, Context, Id, ScrollArea, Vec2};
+use egui::{Align2, Color32, Context, Id, ScrollArea, Vec2};
 use egui_extras::RetainedImage;
 use glium_app::utils::persistent_window::PersistentWindow;
 use log::{debug, error};
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     network::{NetworkCommand, NetworkManager, PROTOCOL},
+    resources,
     server::Server,
     state::State,
     Client,
@@ -38,6 +39,16 @@ pub fn render(gui_ctx: &Context, cli: &mut Client) -> Option<Server> {
                     ui.text_edit_singleline(&mut cli.state.settings.name);
                 });
             }
+
+            // The game can't be played properly without its assets, so tell the player why
+            let errors = resources::get_load_errors();
+            if !errors.is_empty() {
+                ui.separator();
+                ui.heading("Assets");
+                for error in errors {
+                    ui.colored_label(Color32::RED, error);
+                }
+            }
         });
 
     egui::CentralPanel::default().show(gui_ctx, |ui| {
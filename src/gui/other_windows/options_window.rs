@@ -1,9 +1,13 @@
 use std::ops::RangeInclusive;
 
-use egui::{Id, ScrollArea};
+use egui::{Color32, Id, ScrollArea};
 use glium_app::utils::persistent_window::PersistentWindow;
 
-use crate::WindowManagerType;
+use crate::{
+    renderer::clouds::CloudMode,
+    resources::{self, packs},
+    WindowManagerType,
+};
 
 pub fn new_options_window() -> PersistentWindow<WindowManagerType> {
     PersistentWindow::new(Box::new(move |id, _, gui_ctx, state| {
@@ -42,6 +46,102 @@ pub fn new_options_window() -> PersistentWindow<WindowManagerType> {
                         });
                     });
 
+                    ui.collapsing("Graphics", |ui| {
+                        egui::ComboBox::from_label("Clouds")
+                            .selected_text(format!("{:?}", state.settings.clouds))
+                            .show_ui(ui, |ui| {
+                                let clouds = &mut state.settings.clouds;
+                                ui.selectable_value(clouds, CloudMode::Off, "Off");
+                                ui.selectable_value(clouds, CloudMode::Flat, "Flat");
+                                ui.selectable_value(clouds, CloudMode::Fancy, "Fancy");
+                            });
+                        ui.horizontal(|ui| {
+                            ui.label("Max particles");
+                            ui.add(egui::Slider::new(
+                                &mut state.settings.max_particles,
+                                RangeInclusive::new(0, 16384),
+                            ));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Mipmap levels");
+                            ui.add(egui::Slider::new(
+                                &mut state.settings.mipmap_levels,
+                                RangeInclusive::new(0, 4),
+                            ));
+                        });
+                        egui::ComboBox::from_label("Anisotropic filtering")
+                            .selected_text(match state.settings.anisotropy {
+                                0 | 1 => String::from("Off"),
+                                n => format!("{}x", n),
+                            })
+                            .show_ui(ui, |ui| {
+                                let anisotropy = &mut state.settings.anisotropy;
+                                ui.selectable_value(anisotropy, 1, "Off");
+                                for n in [2, 4, 8, 16] {
+                                    ui.selectable_value(anisotropy, n, format!("{}x", n));
+                                }
+                            });
+                    });
+
+                    ui.collapsing("Resource packs", |ui| {
+                        ui.label("Packs lower down are used over the ones above them");
+
+                        let enabled = &mut state.settings.resource_packs;
+                        let mut swap = None;
+                        let mut remove = None;
+                        for (i, name) in enabled.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Up").clicked() && i > 0 {
+                                    swap = Some(i - 1);
+                                }
+                                if ui.small_button("Down").clicked() && i + 1 < enabled.len() {
+                                    swap = Some(i);
+                                }
+                                if ui.small_button("Remove").clicked() {
+                                    remove = Some(i);
+                                }
+                                ui.label(name);
+                            });
+                        }
+                        if let Some(i) = swap {
+                            enabled.swap(i, i + 1);
+                        }
+                        if let Some(i) = remove {
+                            enabled.remove(i);
+                        }
+
+                        ui.separator();
+                        for name in packs::find_packs() {
+                            if enabled.contains(&name) {
+                                continue;
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Add").clicked() {
+                                    enabled.push(name.clone());
+                                }
+                                ui.label(&name);
+                            });
+                        }
+                        ui.label(format!("Put packs in the {} folder", packs::PACKS_DIR));
+
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            ui.label("Assets folder");
+                            ui.text_edit_singleline(&mut state.settings.assets_dir);
+                        });
+                        for error in resources::get_load_errors() {
+                            ui.colored_label(Color32::RED, error);
+                        }
+
+                        if ui.button("Apply").clicked() {
+                            state.resources_changed = true;
+                        }
+                    });
+
+                    ui.collapsing("Debug", |ui| {
+                        ui.checkbox(&mut state.settings.show_hitboxes, "Show hitboxes (F3 + B)");
+                    });
+
                     ui.collapsing("Input", |ui| {
                         ui.horizontal(|ui| {
                             ui.label("Mouse sensitivity");
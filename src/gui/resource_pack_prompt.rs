@@ -0,0 +1,25 @@
+use egui::{Align2, Context, Vec2};
+
+/// Asks the player if they want to use the resource pack the server sent. Returns their answer
+/// once they've picked one.
+pub fn render(gui_ctx: &Context) -> Option<bool> {
+    let mut answer = None;
+
+    egui::Window::new("Server Resource Pack")
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .resizable(false)
+        .collapsible(false)
+        .show(gui_ctx, |ui| {
+            ui.label("This server recommends using a custom resource pack.");
+            ui.horizontal(|ui| {
+                if ui.button("Yes").clicked() {
+                    answer = Some(true);
+                }
+                if ui.button("No").clicked() {
+                    answer = Some(false);
+                }
+            });
+        });
+
+    answer
+}
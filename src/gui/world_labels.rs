@@ -0,0 +1,136 @@
+use egui::{Color32, Context, FontId, LayerId, Painter, Pos2, Rect};
+use glam::Vec3;
+
+use crate::{renderer::camera::Camera, server::Server};
+
+/// How far away sign text is still drawn from
+const SIGN_TEXT_DISTANCE: f32 = 16.0;
+/// Height of a line of sign text in blocks
+const SIGN_LINE_HEIGHT: f32 = 0.12;
+/// How far away player name tags are still drawn from
+const NAME_TAG_DISTANCE: f32 = 64.0;
+/// Height of a name tag in blocks
+const NAME_TAG_HEIGHT: f32 = 0.25;
+
+/// Draws the text of all nearby signs which aren't hidden behind other blocks
+pub fn render_signs(gui_ctx: &Context, server: &Server, cam: &Camera) {
+    let painter = gui_ctx.layer_painter(LayerId::background());
+    let screen = gui_ctx.input().screen_rect();
+    let world = server.get_world();
+
+    for (pos, be) in world.get_block_entities() {
+        let lines = match be.get_sign_text() {
+            Some(lines) => lines,
+            None => continue,
+        };
+        if lines.iter().all(|l| l.is_empty()) {
+            continue;
+        }
+
+        let centre = pos.as_vec3() + Vec3::splat(0.5);
+        let to_sign = centre - *cam.get_pos();
+        let distance = to_sign.length();
+        if distance > SIGN_TEXT_DISTANCE {
+            continue;
+        }
+
+        // Don't draw text for signs behind walls
+        if let Some((hit, _)) = world.raycast(*cam.get_pos(), to_sign, distance) {
+            if hit != *pos {
+                continue;
+            }
+        }
+
+        draw_world_text(
+            &painter,
+            cam,
+            screen,
+            centre,
+            &lines,
+            SIGN_LINE_HEIGHT,
+            Color32::BLACK,
+            None,
+        );
+    }
+}
+
+/// Draws name tags above the heads of other players
+pub fn render_name_tags(gui_ctx: &Context, server: &Server, cam: &Camera) {
+    let painter = gui_ctx.layer_painter(LayerId::background());
+    let screen = gui_ctx.input().screen_rect();
+
+    for ent in server.get_entities().values() {
+        let player = match server.get_players().get(&ent.get_uuid()) {
+            Some(player) => player,
+            None => continue,
+        };
+
+        let pos = ent.pos + Vec3::new(0.0, ent.get_type().height + 0.5, 0.0);
+        if (pos - *cam.get_pos()).length() > NAME_TAG_DISTANCE {
+            continue;
+        }
+
+        draw_world_text(
+            &painter,
+            cam,
+            screen,
+            pos,
+            &[player.get_shown_name()],
+            NAME_TAG_HEIGHT,
+            Color32::WHITE,
+            Some(Color32::from_black_alpha(64)),
+        );
+    }
+}
+
+/// Draws lines of text centred on a position in the world, scaled so each line is `line_height`
+/// blocks tall. Returns the screen rect the text was drawn in, if it was on screen at all
+pub fn draw_world_text(
+    painter: &Painter,
+    cam: &Camera,
+    screen: Rect,
+    pos: Vec3,
+    lines: &[String],
+    line_height: f32,
+    colour: Color32,
+    background: Option<Color32>,
+) -> Option<Rect> {
+    let ndc = cam.project(pos)?;
+    if ndc.x.abs() > 1.2 || ndc.y.abs() > 1.2 || ndc.z > 1.0 {
+        return None;
+    }
+
+    let distance = (pos - *cam.get_pos()).length().max(0.1);
+    let pixels_per_block =
+        screen.height() / (2.0 * (cam.get_fov().to_radians() / 2.0).tan() * distance);
+    let font_size = line_height * pixels_per_block;
+    if font_size < 2.0 {
+        return None;
+    }
+
+    let centre = Pos2::new(
+        screen.left() + (ndc.x + 1.0) / 2.0 * screen.width(),
+        screen.top() + (1.0 - ndc.y) / 2.0 * screen.height(),
+    );
+
+    let galleys: Vec<_> = lines
+        .iter()
+        .map(|l| painter.layout_no_wrap(l.clone(), FontId::proportional(font_size), colour))
+        .collect();
+    let width = galleys.iter().map(|g| g.size().x).fold(0.0, f32::max);
+    let height: f32 = galleys.iter().map(|g| g.size().y).sum();
+    let rect = Rect::from_center_size(centre, egui::vec2(width, height));
+
+    if let Some(bg) = background {
+        painter.rect_filled(rect.expand(font_size * 0.1), 0.0, bg);
+    }
+
+    let mut y = rect.top();
+    for galley in galleys {
+        let size = galley.size();
+        painter.galley(Pos2::new(centre.x - size.x / 2.0, y), galley);
+        y += size.y;
+    }
+
+    Some(rect)
+}
@@ -0,0 +1,120 @@
+use mcproto_rs::types::Slot;
+
+/// How many slots the player's inventory window has, including the crafting grid and armour
+pub const INVENTORY_SLOTS: usize = 46;
+/// The slot in the player's inventory window that the hotbar starts at
+pub const HOTBAR_START: usize = 36;
+pub const HOTBAR_SLOTS: usize = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: u32,
+    pub count: u8,
+}
+
+impl ItemStack {
+    /// Gets the stack in a slot the server sent, if there is one
+    pub fn from_slot(slot: &Slot) -> Option<ItemStack> {
+        slot.item.as_ref().map(|stack| ItemStack {
+            item: stack.item_id.0 as u32,
+            count: stack.item_count as u8,
+        })
+    }
+}
+
+/// The items the player is carrying, laid out the same way as the slots of the player's
+/// inventory window
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+    /// Which hotbar slot is being held, from 0 to 8
+    selected: usize,
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory {
+            slots: vec![None; INVENTORY_SLOTS],
+            selected: 0,
+        }
+    }
+
+    pub fn get_slot(&self, slot: usize) -> Option<&ItemStack> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    pub fn set_slot(&mut self, slot: usize, stack: Option<ItemStack>) {
+        match self.slots.get_mut(slot) {
+            Some(s) => *s = stack,
+            None => log::error!("Tried to set inventory slot {} which doesn't exist", slot),
+        }
+    }
+
+    /// Replaces every slot, like when the server sends the whole inventory
+    pub fn set_slots(&mut self, stacks: Vec<Option<ItemStack>>) {
+        self.slots = stacks;
+        self.slots.resize(INVENTORY_SLOTS, None);
+    }
+
+    /// The stack in a slot of the hotbar, from 0 to 8
+    pub fn get_hotbar(&self, slot: usize) -> Option<&ItemStack> {
+        if slot < HOTBAR_SLOTS {
+            self.get_slot(HOTBAR_START + slot)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, slot: usize) {
+        if slot < HOTBAR_SLOTS {
+            self.selected = slot;
+        } else {
+            log::error!("Tried to select hotbar slot {} which doesn't exist", slot);
+        }
+    }
+
+    /// The stack in the selected hotbar slot
+    pub fn get_held(&self) -> Option<&ItemStack> {
+        self.get_hotbar(self.selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(item: u32) -> Option<ItemStack> {
+        Some(ItemStack { item, count: 1 })
+    }
+
+    #[test]
+    fn hotbar_is_end_of_inventory() {
+        let mut inventory = Inventory::new();
+        let mut slots = vec![None; INVENTORY_SLOTS];
+        slots[HOTBAR_START] = stack(1);
+        slots[HOTBAR_START + 8] = stack(2);
+        inventory.set_slots(slots);
+
+        assert_eq!(inventory.get_held(), stack(1).as_ref());
+        inventory.set_selected(8);
+        assert_eq!(inventory.get_held(), stack(2).as_ref());
+        assert_eq!(inventory.get_hotbar(9), None);
+    }
+
+    #[test]
+    fn ignores_slots_that_dont_exist() {
+        let mut inventory = Inventory::new();
+        inventory.set_slot(INVENTORY_SLOTS, stack(1));
+        inventory.set_selected(9);
+        assert_eq!(inventory.get_selected(), 0);
+
+        // The server sending too few slots doesn't make the hotbar disappear
+        inventory.set_slots(vec![stack(3)]);
+        inventory.set_slot(HOTBAR_START, stack(4));
+        assert_eq!(inventory.get_slot(0), stack(3).as_ref());
+        assert_eq!(inventory.get_held(), stack(4).as_ref());
+    }
+}
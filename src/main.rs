@@ -1,4 +1,4 @@
-#![feature(cursor_remaining, int_roundings, drain_filter)]
+#![feature(cursor_remaining, int_roundings, drain_filter, test)]
 
 extern crate chrono;
 extern crate egui;
@@ -16,8 +16,10 @@ use crate::resources::BLOCK_MODELS_PARSED;
 mod network;
 
 use egui::{FontData, FontDefinitions, FontFamily};
-use egui_winit::winit::{event::Event, window::WindowBuilder};
-use glam::Vec3;
+use egui_winit::winit::{
+    event::{Event, VirtualKeyCode},
+    window::WindowBuilder,
+};
 use glium::glutin;
 use log::{debug, error, info};
 
@@ -33,6 +35,8 @@ use state::State;
 pub mod chat;
 pub mod entities;
 pub mod gui;
+pub mod inventory;
+pub mod particles;
 pub mod player;
 pub mod renderer;
 pub mod resources;
@@ -45,6 +49,12 @@ fn main() {
     env_logger::init();
     debug!("Starting logger");
 
+    // Check the assets and exit instead of starting the game
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some(resources::validate::VALIDATE_COMMAND) {
+        std::process::exit(resources::validate::run(&args[1..]));
+    }
+
     let wb = WindowBuilder::new()
         .with_title("Minceraft!")
         .with_resizable(true)
@@ -98,13 +108,25 @@ impl Application for Client {
         let aspect = dims.0 as f32 / dims.1 as f32;
         self.state.rend.cam.set_aspect_ratio(aspect);
 
+        resources::assets::set_dir(&self.state.settings.assets_dir);
+        resources::packs::set_packs(&self.state.get_resource_packs());
         std::thread::spawn(|| {
             let start = Instant::now();
-            log::info!("Loaded {} blocks.", resources::BLOCKS.len());
-            log::info!("Loaded {} entities.", resources::ENTITIES.len());
-            log::info!("Loaded {} models' data.", resources::BLOCK_MODELS_RAW.len());
-            log::info!("Loaded {} models.", resources::BLOCK_MODELS_PARSED.len());
-            log::info!("Loaded {} block textures.", resources::BLOCK_TEXTURES.len());
+            log::info!("Loaded {} blocks.", resources::BLOCKS.load().len());
+            log::info!("Loaded {} entities.", resources::ENTITIES.load().len());
+            log::info!(
+                "Loaded {} models' data.",
+                resources::BLOCK_MODELS_RAW.load().len()
+            );
+            log::info!(
+                "Loaded {} models.",
+                resources::BLOCK_MODELS_PARSED.load().len()
+            );
+            log::info!(
+                "Loaded {} block textures.",
+                resources::BLOCK_TEXTURES.load().len()
+            );
+            log::info!("Loaded {} items.", resources::ITEMS.load().len());
             let dur = Instant::now() - start;
             info!("Loading assets took {}ms", dur.as_millis());
         });
@@ -153,14 +175,19 @@ impl Application for Client {
                 self.state
                     .rend
                     .cam
-                    .set_pos(*serv.get_player().get_position());
-                self.state.rend.cam.translate(Vec3::new(0.0, 1.7, 0.0));
+                    .set_pos(serv.get_player().get_eye_position());
                 self.state
                     .rend
                     .cam
                     .set_rot(serv.get_player().get_orientation().get_rotations() * -1.0);
 
-                serv.update(ctx, delta, &mut self.state.settings);
+                serv.update(ctx, delta, &mut self.state.settings, &self.state.rend.cam);
+                for (uuid, skin) in serv.take_loaded_skins() {
+                    self.state.rend.load_skin(&ctx.dis, uuid, &skin);
+                }
+                if serv.is_resource_pack_downloaded() {
+                    self.state.resources_changed = true;
+                }
             }
             None => {
                 let State {
@@ -197,11 +224,20 @@ impl Application for Client {
 
         let mut target = dis.draw();
 
+        // Block textures animate at 20 ticks per second, like the game
+        self.state.rend.update_animations((time * 20.0) as u64);
+        self.state
+            .rend
+            .update_clouds(dis, self.state.settings.clouds);
+        self.state
+            .rend
+            .update_mipmaps(dis, self.state.settings.mipmap_levels);
+
         // Render world if it exists
         if let Some(s) = &self.state.server {
             self.state
                 .rend
-                .render_server(&mut target, s, &self.state.settings);
+                .render_server(dis, &mut target, s, &self.state.settings);
         }
 
         // GUI
@@ -236,16 +272,29 @@ impl Application for Client {
 
         target.finish().unwrap();
 
+        if ctx.keyboard.pressed_this_frame(&VirtualKeyCode::F2) {
+            renderer::offscreen::save_screenshot(&ctx.dis);
+        }
+
         // Check for server disconnect
         if let Some(serv) = &mut self.state.server {
+            let disconnected = serv.server_disconnect || serv.client_disconnect;
             if serv.server_disconnect {
                 self.window_manager
                     .push(gui::disconnect_window(serv.disconnect_reason.clone()));
-                self.state.server = None;
-            } else if serv.client_disconnect {
+            }
+            if disconnected {
+                // Stop using the server's resource pack
+                if serv.get_resource_pack().is_some() {
+                    self.state.resources_changed = true;
+                }
                 self.state.server = None;
             }
         }
+
+        if self.state.resources_changed {
+            self.state.reload_resources(&ctx.dis);
+        }
     }
 
     fn close(&mut self, ctx: &Context) {
@@ -0,0 +1,494 @@
+use std::collections::VecDeque;
+
+use glam::{IVec3, Vec2, Vec3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    resources::{BLOCKS, BLOCK_MODELS_PARSED},
+    world::{block_coords, World},
+};
+
+/// Names of the textures in `textures/particle/` that particles can use, in the order
+/// they're stored in the particle texture array
+pub const PARTICLE_TEXTURES: [&str; 28] = [
+    "generic_0",
+    "generic_1",
+    "generic_2",
+    "generic_3",
+    "generic_4",
+    "generic_5",
+    "generic_6",
+    "generic_7",
+    "flame",
+    "soul_fire_flame",
+    "critical_hit",
+    "enchanted_hit",
+    "explosion_0",
+    "explosion_1",
+    "explosion_2",
+    "explosion_3",
+    "explosion_4",
+    "explosion_5",
+    "explosion_6",
+    "explosion_7",
+    "explosion_8",
+    "explosion_9",
+    "explosion_10",
+    "explosion_11",
+    "explosion_12",
+    "explosion_13",
+    "explosion_14",
+    "explosion_15",
+];
+const GENERIC: u32 = 0;
+const FLAME: u32 = 8;
+const SOUL_FIRE_FLAME: u32 = 9;
+const CRITICAL_HIT: u32 = 10;
+const ENCHANTED_HIT: u32 = 11;
+const EXPLOSION: u32 = 12;
+
+/// How many particles there can be at once unless changed in the settings
+pub const DEFAULT_MAX_PARTICLES: usize = 4000;
+
+/// How far a particle falls every tick, in blocks per tick, when its gravity is 1
+const GRAVITY: f32 = 0.04;
+
+/// The kinds of particle that can be shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParticleKind {
+    /// Dust from a block, with its block state ID
+    Block(u32),
+    Smoke,
+    LargeSmoke,
+    Poof,
+    Flame,
+    SoulFireFlame,
+    Crit,
+    EnchantedHit,
+    Explosion,
+    /// A cluster of explosions, from a large explosion
+    ExplosionEmitter,
+}
+
+impl ParticleKind {
+    /// Reads a particle from the ID and extra data sent by the server. IDs are from the 1.16.3
+    /// particle registry, and kinds that aren't drawn give `None`.
+    pub fn from_id(id: i32, data: &[u8]) -> Option<ParticleKind> {
+        match id {
+            // Block and falling dust both have the block state as extra data
+            3 | 23 => read_varint(data).map(|block| ParticleKind::Block(block as u32)),
+            5 | 41 => Some(ParticleKind::Poof),
+            6 => Some(ParticleKind::Crit),
+            17 => Some(ParticleKind::EnchantedHit),
+            21 => Some(ParticleKind::ExplosionEmitter),
+            22 => Some(ParticleKind::Explosion),
+            26 => Some(ParticleKind::Flame),
+            27 => Some(ParticleKind::SoulFireFlame),
+            37 => Some(ParticleKind::LargeSmoke),
+            44 => Some(ParticleKind::Smoke),
+            _ => None,
+        }
+    }
+}
+
+/// Which texture a particle is drawn with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParticleTexture {
+    /// A quarter of a block's texture, by its layer in the block texture array and the corner of
+    /// the part used
+    Block { layer: u32, uv_offset: Vec2 },
+    /// Frames from `PARTICLE_TEXTURES` played through over the particle's life
+    Sprite { first: u32, frames: u32 },
+    /// Like `Sprite` but played backwards, so smoke starts big and shrinks away
+    SpriteReversed { first: u32, frames: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Particle {
+    pub kind: ParticleKind,
+    pub texture: ParticleTexture,
+
+    pub pos: Vec3,
+    /// Where the particle was last tick, to move smoothly between ticks
+    pub last_pos: Vec3,
+    /// In blocks per tick
+    pub vel: Vec3,
+    /// How many times stronger than normal gravity pulls on the particle, which is negative for
+    /// particles that rise
+    pub gravity: f32,
+    /// Velocity is multiplied by this every tick
+    pub friction: f32,
+    pub collides: bool,
+    pub on_ground: bool,
+
+    /// Age and lifetime in ticks
+    pub age: u32,
+    pub lifetime: u32,
+
+    /// Half the width of the particle in blocks
+    pub size: f32,
+    /// Whether the particle gets smaller as it gets older
+    pub shrinks: bool,
+    pub colour: [f32; 4],
+    /// Glowing particles are as bright at night as in the day
+    pub emissive: bool,
+}
+
+impl Particle {
+    pub fn new(kind: ParticleKind, pos: Vec3, vel: Vec3, rng: &mut impl Rng) -> Particle {
+        let mut particle = Particle {
+            kind,
+            texture: ParticleTexture::Sprite {
+                first: GENERIC,
+                frames: 8,
+            },
+            pos,
+            last_pos: pos,
+            vel,
+            gravity: 0.0,
+            friction: 0.98,
+            collides: true,
+            on_ground: false,
+            age: 0,
+            lifetime: 20,
+            size: 0.1,
+            shrinks: false,
+            colour: [1.0; 4],
+            emissive: false,
+        };
+
+        match kind {
+            ParticleKind::Block(block) => {
+                particle.texture = ParticleTexture::Block {
+                    layer: block_particle_layer(block),
+                    uv_offset: Vec2::new(rng.gen_range(0.0..0.75), rng.gen_range(0.0..0.75)),
+                };
+                particle.gravity = 1.0;
+                particle.lifetime = (4.0 / rng.gen_range(0.1..1.0)) as u32;
+                particle.size = 0.05 * rng.gen_range(1.0..2.0);
+                particle.colour = [0.6, 0.6, 0.6, 1.0];
+            }
+            ParticleKind::Smoke | ParticleKind::LargeSmoke => {
+                let grey = rng.gen_range(0.0..0.3);
+                particle.texture = ParticleTexture::SpriteReversed {
+                    first: GENERIC,
+                    frames: 8,
+                };
+                particle.vel = vel * 0.1 + random_direction(rng) * 0.01;
+                particle.gravity = -0.1;
+                particle.friction = 0.96;
+                particle.lifetime = (8.0 / rng.gen_range(0.2..1.0)) as u32;
+                particle.size = 0.075 * rng.gen_range(1.0..2.0);
+                if kind == ParticleKind::LargeSmoke {
+                    particle.size *= 2.5;
+                }
+                particle.colour = [grey, grey, grey, 1.0];
+            }
+            ParticleKind::Poof => {
+                let grey = rng.gen_range(0.7..1.0);
+                particle.texture = ParticleTexture::SpriteReversed {
+                    first: GENERIC,
+                    frames: 8,
+                };
+                particle.vel = vel + random_direction(rng) * 0.05;
+                particle.gravity = -0.1;
+                particle.friction = 0.9;
+                particle.lifetime = (16.0 / rng.gen_range(0.2..1.0)) as u32 + 2;
+                particle.size = 0.1 * rng.gen_range(1.0..2.0);
+                particle.colour = [grey, grey, grey, 1.0];
+            }
+            ParticleKind::Flame | ParticleKind::SoulFireFlame => {
+                let first = match kind {
+                    ParticleKind::Flame => FLAME,
+                    _ => SOUL_FIRE_FLAME,
+                };
+                particle.texture = ParticleTexture::Sprite { first, frames: 1 };
+                particle.vel = vel + random_direction(rng) * 0.002;
+                particle.friction = 0.96;
+                particle.collides = false;
+                particle.lifetime = (8.0 / rng.gen_range(0.2..1.0)) as u32 + 4;
+                particle.size = 0.1 * rng.gen_range(0.8..1.2);
+                particle.shrinks = true;
+                particle.emissive = true;
+            }
+            ParticleKind::Crit | ParticleKind::EnchantedHit => {
+                let first = match kind {
+                    ParticleKind::Crit => CRITICAL_HIT,
+                    _ => ENCHANTED_HIT,
+                };
+                let shade = rng.gen_range(0.6..1.0);
+                particle.texture = ParticleTexture::Sprite { first, frames: 1 };
+                particle.vel = vel * 0.4 + random_direction(rng) * 0.1;
+                particle.gravity = 0.5;
+                particle.friction = 0.7;
+                particle.lifetime = (6.0 / rng.gen_range(0.6..1.4)) as u32;
+                particle.size = 0.075;
+                particle.shrinks = true;
+                particle.colour = match kind {
+                    ParticleKind::EnchantedHit => [shade * 0.3, shade * 0.8, shade, 1.0],
+                    _ => [shade, shade, shade, 1.0],
+                };
+            }
+            ParticleKind::Explosion | ParticleKind::ExplosionEmitter => {
+                let grey = rng.gen_range(0.4..1.0);
+                particle.texture = ParticleTexture::Sprite {
+                    first: EXPLOSION,
+                    frames: 16,
+                };
+                particle.vel = Vec3::ZERO;
+                particle.friction = 0.0;
+                particle.collides = false;
+                particle.lifetime = rng.gen_range(6..10);
+                particle.size = rng.gen_range(1.0..2.0);
+                particle.colour = [grey, grey, grey, 1.0];
+                particle.emissive = true;
+            }
+        }
+
+        particle
+    }
+
+    /// Moves on by a tick, returning false once the particle has died
+    fn tick(&mut self, world: &World) -> bool {
+        self.last_pos = self.pos;
+        self.age += 1;
+        if self.age >= self.lifetime {
+            return false;
+        }
+
+        self.vel.y -= GRAVITY * self.gravity;
+        self.move_by(world);
+        self.vel *= self.friction;
+        if self.on_ground {
+            self.vel.x *= 0.7;
+            self.vel.z *= 0.7;
+        }
+
+        true
+    }
+
+    /// Moves along each axis in turn, stopping along any that would take it into a block
+    fn move_by(&mut self, world: &World) {
+        self.on_ground = false;
+        for axis in 0..3 {
+            let mut next = self.pos;
+            next[axis] += self.vel[axis];
+
+            if self.collides && is_solid(world, next) {
+                if axis == 1 && self.vel.y < 0.0 {
+                    self.on_ground = true;
+                }
+                self.vel[axis] = 0.0;
+            } else {
+                self.pos = next;
+            }
+        }
+    }
+
+    /// Where the particle should be drawn, `partial_tick` of the way through the current tick
+    pub fn get_render_pos(&self, partial_tick: f32) -> Vec3 {
+        self.last_pos.lerp(self.pos, partial_tick)
+    }
+
+    pub fn get_render_size(&self, partial_tick: f32) -> f32 {
+        if self.shrinks {
+            let life = ((self.age as f32 + partial_tick) / self.lifetime as f32).min(1.0);
+            self.size * (1.0 - life * life * 0.5)
+        } else {
+            self.size
+        }
+    }
+
+    /// The frame of the particle's texture to show, as a layer of the particle texture array
+    pub fn get_frame(&self) -> u32 {
+        let frame = |frames: u32| (self.age * frames / self.lifetime.max(1)).min(frames - 1);
+        match self.texture {
+            ParticleTexture::Block { layer, .. } => layer,
+            ParticleTexture::Sprite { first, frames } => first + frame(frames),
+            ParticleTexture::SpriteReversed { first, frames } => first + frames - 1 - frame(frames),
+        }
+    }
+}
+
+/// All the particles in the world, which are simulated on the client 20 times a second like in
+/// the game
+pub struct Particles {
+    particles: VecDeque<Particle>,
+    /// The oldest particles are removed to make room once there are this many
+    max_particles: usize,
+    /// How far through the current tick the particles are, from 0 to 1
+    partial_tick: f32,
+    rng: StdRng,
+}
+
+impl Particles {
+    pub fn new() -> Particles {
+        Particles {
+            particles: VecDeque::new(),
+            max_particles: DEFAULT_MAX_PARTICLES,
+            partial_tick: 0.0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn update(&mut self, delta: f32, world: &World, max_particles: usize) {
+        self.max_particles = max_particles;
+        while self.particles.len() > self.max_particles {
+            self.particles.pop_front();
+        }
+
+        self.partial_tick += delta * 20.0;
+        while self.partial_tick >= 1.0 {
+            self.partial_tick -= 1.0;
+            self.particles.retain_mut(|p| p.tick(world));
+        }
+    }
+
+    pub fn get_particles(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter()
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn get_partial_tick(&self) -> f32 {
+        self.partial_tick
+    }
+
+    /// Adds a particle, making room by removing the oldest one if there are too many
+    pub fn spawn(&mut self, kind: ParticleKind, pos: Vec3, vel: Vec3) {
+        if self.max_particles == 0 {
+            return;
+        }
+
+        if kind == ParticleKind::ExplosionEmitter {
+            for _ in 0..16 {
+                let offset = random_direction(&mut self.rng) * self.rng.gen_range(0.0..4.0);
+                self.spawn(ParticleKind::Explosion, pos + offset, Vec3::ZERO);
+            }
+            return;
+        }
+
+        while self.particles.len() >= self.max_particles {
+            self.particles.pop_front();
+        }
+        let particle = Particle::new(kind, pos, vel, &mut self.rng);
+        self.particles.push_back(particle);
+    }
+
+    /// Spawns particles the way the server asks for them. With a count of 0 a single particle is
+    /// spawned moving in the direction of `offset` at `speed`, otherwise `count` particles are
+    /// scattered around `pos` by about `offset` moving in random directions.
+    pub fn spawn_from_server(
+        &mut self,
+        kind: ParticleKind,
+        pos: Vec3,
+        offset: Vec3,
+        speed: f32,
+        count: i32,
+    ) {
+        if count == 0 {
+            self.spawn(kind, pos, offset * speed);
+            return;
+        }
+
+        for _ in 0..count {
+            let spread = Vec3::new(
+                gaussian(&mut self.rng),
+                gaussian(&mut self.rng),
+                gaussian(&mut self.rng),
+            );
+            let vel = Vec3::new(
+                gaussian(&mut self.rng),
+                gaussian(&mut self.rng),
+                gaussian(&mut self.rng),
+            ) * speed;
+            self.spawn(kind, pos + spread * offset, vel);
+        }
+    }
+
+    /// Breaks a block into a 4x4x4 grid of dust flying out from its centre
+    pub fn spawn_block_break(&mut self, coords: &IVec3, block: u32) {
+        if BLOCKS
+            .load()
+            .get(&block)
+            .map(|b| b.is_air())
+            .unwrap_or(true)
+        {
+            return;
+        }
+
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    let offset = (Vec3::new(x as f32, y as f32, z as f32) + Vec3::splat(0.5)) / 4.0;
+                    let vel = (offset - Vec3::splat(0.5)) * 0.3
+                        + random_direction(&mut self.rng) * 0.05
+                        + Vec3::new(0.0, 0.1, 0.0);
+                    self.spawn(ParticleKind::Block(block), coords.as_vec3() + offset, vel);
+                }
+            }
+        }
+    }
+
+    /// Sends sparks of a critical hit out from around an entity's hitbox
+    pub fn spawn_crit(&mut self, kind: ParticleKind, pos: Vec3, width: f32, height: f32) {
+        for _ in 0..16 {
+            let dir = random_direction(&mut self.rng);
+            let offset = Vec3::new(
+                dir.x * width / 4.0,
+                height / 2.0 + dir.y * height / 4.0,
+                dir.z * width / 4.0,
+            );
+            self.spawn(kind, pos + offset, dir * 0.3);
+        }
+    }
+}
+
+/// The layer in the block texture array of a block's particle texture
+fn block_particle_layer(block: u32) -> u32 {
+    let models = BLOCK_MODELS_PARSED.load();
+    BLOCKS
+        .load()
+        .get(&block)
+        .and_then(|b| b.first_variant())
+        .and_then(|v| models.get(&v.model))
+        .map(|m| m.get_particle_texture().index as u32)
+        .unwrap_or(0)
+}
+
+fn is_solid(world: &World, pos: Vec3) -> bool {
+    world
+        .block_at(&block_coords(&pos))
+        .map(|b| !b.is_air() && !b.is_fluid())
+        .unwrap_or(false)
+}
+
+/// A random direction with a length of up to 1
+fn random_direction(rng: &mut impl Rng) -> Vec3 {
+    Vec3::new(
+        rng.gen_range(-1.0..1.0),
+        rng.gen_range(-1.0..1.0),
+        rng.gen_range(-1.0..1.0),
+    )
+}
+
+/// A random number from a normal distribution with a mean of 0 and standard deviation of 1
+fn gaussian(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
+
+/// Reads a VarInt from the start of some bytes
+fn read_varint(bytes: &[u8]) -> Option<i32> {
+    let mut value: u32 = 0;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some(value as i32);
+        }
+    }
+    None
+}
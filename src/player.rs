@@ -3,11 +3,18 @@ use mcproto_rs::v1_16_3::{ClientChatMode, ClientDisplayedSkinParts, ClientMainHa
 
 use super::entities::components::Orientation;
 
+/// Height of the player's eyes (and so the camera) above their feet
+pub const EYE_HEIGHT: f32 = 1.7;
+/// How long one swing of the player's arm takes in seconds, which is 6 ticks
+pub const SWING_TIME: f32 = 0.3;
+
 pub struct Player {
     pub id: i32,
 
     position: Vec3,
     orientation: Orientation,
+    /// How long the player has been swinging their arm for in seconds, or None if they aren't
+    swing: Option<f32>,
 
     pub health: f32,
     pub food: i32,
@@ -29,6 +36,7 @@ impl Player {
 
             position: Vec3::new(0.0, 0.0, 0.0),
             orientation: Orientation::new_with_values(0.0, 0.0, -89.0, 89.0),
+            swing: None,
 
             health: 20.0,
             food: 20,
@@ -49,6 +57,9 @@ impl Player {
     pub fn get_position(&self) -> &Vec3 {
         &self.position
     }
+    pub fn get_eye_position(&self) -> Vec3 {
+        self.position + Vec3::new(0.0, EYE_HEIGHT, 0.0)
+    }
 
     pub fn set_orientation(&mut self, ori: Orientation) {
         self.orientation = ori;
@@ -63,4 +74,51 @@ impl Player {
     pub fn get_orientation_mut(&mut self) -> &mut Orientation {
         &mut self.orientation
     }
+
+    /// Starts swinging the arm, like when attacking or digging. Swinging again before the last
+    /// swing is half done does nothing, so holding the button down keeps the arm moving smoothly.
+    pub fn swing_arm(&mut self) {
+        match self.swing {
+            Some(time) if time < SWING_TIME / 2.0 => {}
+            _ => self.swing = Some(0.0),
+        }
+    }
+
+    pub fn update_swing(&mut self, delta: f32) {
+        if let Some(time) = &mut self.swing {
+            *time += delta;
+            if *time >= SWING_TIME {
+                self.swing = None;
+            }
+        }
+    }
+
+    /// How far through a swing the arm is from 0 to 1, which is 0 when it isn't swinging
+    pub fn get_swing_progress(&self) -> f32 {
+        self.swing.map_or(0.0, |time| time / SWING_TIME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swings_restart_once_half_done() {
+        let mut player = Player::new();
+        assert_eq!(player.get_swing_progress(), 0.0);
+
+        player.swing_arm();
+        player.update_swing(SWING_TIME / 4.0);
+        player.swing_arm();
+        assert_eq!(player.get_swing_progress(), 0.25);
+
+        player.update_swing(SWING_TIME / 2.0);
+        player.swing_arm();
+        assert_eq!(player.get_swing_progress(), 0.0);
+
+        player.update_swing(SWING_TIME);
+        assert_eq!(player.get_swing_progress(), 0.0);
+        assert!(player.swing.is_none());
+    }
 }
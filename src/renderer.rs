@@ -1,25 +1,56 @@
 use std::collections::HashMap;
 use std::io::Cursor;
 
-use glam::{Mat4, Vec3};
-use glium::index::{NoIndices, PrimitiveType::TrianglesList};
-use glium::texture::{RawImage2d, SrgbTexture2dArray};
-use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use glam::{IVec3, Mat4, Vec2, Vec3};
+use glium::index::NoIndices;
+use glium::texture::{
+    MipmapsOption, RawImage2d, SrgbFormat, SrgbTexture2d, SrgbTexture2dArray, Texture2d,
+    UncompressedFloatFormat,
+};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
 use glium::*;
-use glium::{Display, Surface};
+use glium::{backend::Facade, Surface};
+use image::RgbaImage;
+use mcproto_rs::uuid::UUID4;
 
-use crate::resources::BLOCK_TEXTURES;
+use crate::resources::{block_models::Direction, packs, RenderLayer, BLOCK_TEXTURES};
 use crate::settings::Settings;
-use crate::world::chunks::{MAX_SECTION, MIN_SECTION};
+use crate::world::{
+    chunks::{LayerBuffers, MAX_SECTION, MIN_SECTION},
+    visibility::{find_visible_sections, SectionVisibility},
+    World,
+};
 use crate::{
-    entities::{self, Entity},
-    renderer::camera::Camera,
+    entities::{
+        self,
+        models::{self, EntityModel, RIGHT_ARM},
+        Entity,
+    },
+    renderer::{
+        camera::Camera,
+        clouds::{CloudMode, Clouds},
+        hand::HeldMesh,
+        mipmaps::DEFAULT_MIPMAP_LEVELS,
+        particles::ParticleRenderer,
+        sky::{Sky, SkyState},
+    },
+    resources::{items::Item, ENTITIES, ITEMS, PLAYER_INDEX},
 };
 
 use super::server::Server;
 
-mod camera;
+pub mod camera;
+pub mod clouds;
+pub mod hand;
+pub mod item_icons;
+pub mod mipmaps;
+pub mod offscreen;
+pub mod particles;
 mod shader;
+pub mod sky;
+
+/// Texels less opaque than this are discarded when drawing cutout faces
+const CUTOUT_ALPHA: f32 = 0.5;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Vertex {
@@ -27,12 +58,248 @@ pub struct Vertex {
 }
 implement_vertex!(Vertex, position);
 
+/// A corner of an entity model, in the model's own space
 #[derive(Debug, Copy, Clone)]
-pub struct BlockVertex {
+pub struct EntityVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+}
+implement_vertex!(EntityVertex, position, tex_coords, normal);
+
+/// Positions and texture coordinates of block vertices are stored as fixed point numbers with
+/// this many steps per block (or per texture)
+pub const VERTEX_SCALE: f32 = 1024.0;
+
+/// A corner of a face while a mesh is being built, before it's packed into a `BlockVertex`
+#[derive(Debug, Copy, Clone)]
+pub struct FaceVertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 3],
+    pub tint: [f32; 3],
+    /// How much light reaches this vertex, from the direction the face points and ambient occlusion
+    pub shade: f32,
+}
+
+/// The compact vertex format chunk meshes are uploaded to the GPU in
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlockVertex {
+    /// Position within the chunk section in fixed point, see `VERTEX_SCALE`
+    pub position: [i16; 3],
+    /// Layer of the block texture array
+    pub tex_layer: u16,
+    /// Texture coordinates in fixed point, which can go past 1 on merged faces to repeat
+    pub tex_coords: [i16; 2],
+    /// Tint colour, with how much light reaches the vertex as the alpha
+    pub colour: [u8; 4],
+}
+implement_vertex!(
+    BlockVertex,
+    position normalize(false),
+    tex_layer normalize(false),
+    tex_coords normalize(false),
+    colour normalize(true)
+);
+
+impl BlockVertex {
+    pub fn new(position: Vec3, uv: Vec2, tex_layer: u16, colour: [u8; 4]) -> BlockVertex {
+        BlockVertex {
+            position: position.to_array().map(to_fixed),
+            tex_layer,
+            tex_coords: uv.to_array().map(to_fixed),
+            colour,
+        }
+    }
+
+    pub fn get_position(&self) -> Vec3 {
+        Vec3::from(self.position.map(|p| p as f32)) / VERTEX_SCALE
+    }
+}
+
+impl From<FaceVertex> for BlockVertex {
+    fn from(vert: FaceVertex) -> BlockVertex {
+        let [r, g, b] = vert.tint.map(to_unorm);
+        BlockVertex::new(
+            Vec3::from(vert.position),
+            Vec2::new(vert.tex_coords[0], vert.tex_coords[1]),
+            vert.tex_coords[2] as u16,
+            [r, g, b, to_unorm(vert.shade)],
+        )
+    }
+}
+
+fn to_fixed(val: f32) -> i16 {
+    (val * VERTEX_SCALE)
+        .round()
+        .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+fn to_unorm(val: f32) -> u8 {
+    (val.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// A whole side of a full block with the texture the right way up and the same colour at every
+/// corner, which can be merged with matching faces next to it into one bigger quad
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FullFace {
+    pub pos: IVec3,
+    pub dir: Direction,
+    pub layer: RenderLayer,
+    pub texture: u16,
+    pub colour: [u8; 4],
+}
+
+impl FullFace {
+    /// Checks if a quad on the side of a block facing `dir` is a full face, or returns None if it
+    /// has to be drawn on it's own
+    pub fn from_quad(
+        dir: Direction,
+        layer: RenderLayer,
+        verts: &[FaceVertex; 4],
+    ) -> Option<FullFace> {
+        let verts = verts.map(BlockVertex::from);
+        let first = verts[0];
+        if verts
+            .iter()
+            .any(|v| v.tex_layer != first.tex_layer || v.colour != first.colour)
+        {
+            return None;
+        }
+
+        // Every corner of the side has to be covered, with the texture projected straight on
+        for corner in dir.face_corners(Vec3::ZERO, Vec3::ONE) {
+            let uv = dir.project_uv(corner);
+            let expected = BlockVertex::new(
+                corner,
+                Vec2::new(uv.x, 1.0 - uv.y),
+                first.tex_layer,
+                first.colour,
+            );
+            if !verts.contains(&expected) {
+                return None;
+            }
+        }
+
+        Some(FullFace {
+            pos: IVec3::ZERO,
+            dir,
+            layer,
+            texture: first.tex_layer,
+            colour: first.colour,
+        })
+    }
+
+    /// Whether this face looks the same as another, so they can be merged together
+    pub fn matches(&self, other: &FullFace) -> bool {
+        self.dir == other.dir
+            && self.layer == other.layer
+            && self.texture == other.texture
+            && self.colour == other.colour
+    }
+}
+
+/// Vertices for one render pass, drawn as triangles using the indices
+#[derive(Debug, Clone, Default)]
+pub struct MeshLayer {
+    pub vertices: Vec<BlockVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshLayer {
+    /// Adds some vertices, with indices counting from the first of them
+    pub fn push<V: Into<BlockVertex>>(
+        &mut self,
+        verts: impl IntoIterator<Item = V>,
+        indices: &[u32],
+    ) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend(verts.into_iter().map(Into::into));
+        self.indices.extend(indices.iter().map(|i| base + i));
+    }
+
+    /// Adds a quad, drawn as the triangles (0, 1, 2) and (0, 2, 3)
+    pub fn push_quad<V: Into<BlockVertex>>(&mut self, verts: [V; 4]) {
+        self.push(verts, &[0, 1, 2, 0, 2, 3]);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    fn append(&mut self, other: MeshLayer, offset: IVec3) {
+        let offset = (offset * VERTEX_SCALE as i32).to_array().map(|o| o as i16);
+        self.push(
+            other.vertices.into_iter().map(|mut v| {
+                for (p, o) in v.position.iter_mut().zip(offset) {
+                    *p = p.wrapping_add(o);
+                }
+                v
+            }),
+            &other.indices,
+        );
+    }
+}
+
+/// The mesh for a block or chunk section, split up by the render pass it's drawn in
+#[derive(Debug, Clone, Default)]
+pub struct ChunkMesh {
+    pub opaque: MeshLayer,
+    pub cutout: MeshLayer,
+    pub translucent: MeshLayer,
+    /// Full faces that haven't been turned into vertices yet, so they can be merged
+    pub faces: Vec<FullFace>,
+    /// Which sides of a chunk section can be seen from each other
+    pub visibility: SectionVisibility,
+}
+
+impl ChunkMesh {
+    pub fn new() -> ChunkMesh {
+        ChunkMesh::default()
+    }
+
+    pub fn layer_mut(&mut self, layer: RenderLayer) -> &mut MeshLayer {
+        match layer {
+            RenderLayer::Opaque => &mut self.opaque,
+            RenderLayer::Cutout => &mut self.cutout,
+            RenderLayer::Translucent => &mut self.translucent,
+        }
+    }
+
+    /// Adds another mesh to this one, moved by the offset
+    pub fn append(&mut self, other: ChunkMesh, offset: IVec3) {
+        for (layer, other) in [
+            (RenderLayer::Opaque, other.opaque),
+            (RenderLayer::Cutout, other.cutout),
+            (RenderLayer::Translucent, other.translucent),
+        ] {
+            self.layer_mut(layer).append(other, offset);
+        }
+        self.faces.extend(other.faces.into_iter().map(|mut face| {
+            face.pos += offset;
+            face
+        }));
+    }
+
+    /// Total number of vertices in every layer
+    #[cfg(test)]
+    pub fn vertex_count(&self) -> usize {
+        self.opaque.vertices.len() + self.cutout.vertices.len() + self.translucent.vertices.len()
+    }
+
+    /// Total number of indices in every layer
+    #[cfg(test)]
+    pub fn index_count(&self) -> usize {
+        self.opaque.indices.len() + self.cutout.indices.len() + self.translucent.indices.len()
+    }
+}
+
+/// An entity model on the GPU, with a vertex buffer for each of its parts
+struct EntityModelBuffers {
+    model: EntityModel,
+    texture: SrgbTexture2d,
+    parts: Vec<VertexBuffer<EntityVertex>>,
 }
-implement_vertex!(BlockVertex, position, tex_coords);
 
 pub struct Renderer {
     pub cam: Camera,
@@ -42,11 +309,29 @@ pub struct Renderer {
     hitbox_prog: Program,
     hitbox_model: VertexBuffer<Vertex>,
 
+    sky: Sky,
+    clouds: Clouds,
+    particles: ParticleRenderer,
+
+    entity_prog: Program,
+    /// Models for each type of entity that has one, by entity id
+    entity_models: HashMap<u32, EntityModelBuffers>,
+    /// Skins of other players, drawn on their models instead of the default texture
+    player_skins: HashMap<UUID4, SrgbTexture2d>,
+    /// The id and mesh of the item last drawn in the player's hand, kept until it changes
+    held_item: Option<(u32, HeldMesh)>,
+
     block_textures: SrgbTexture2dArray,
+    /// How many mipmap levels the block textures were made with
+    mipmap_levels: u32,
+    /// One texel per texture layer holding the layer to show now, the layer after it and how far
+    /// to blend between them, so animated textures can be looked up in the shader
+    animations: Texture2d,
+    animation_tick: u64,
 }
 
 impl Renderer {
-    pub fn new(dis: &Display) -> Renderer {
+    pub fn new(dis: &impl Facade) -> Renderer {
         let hitbox_model = glium::VertexBuffer::new(dis, &entities::hitbox_model()).unwrap();
 
         let prog = shader::compile_shaders(
@@ -63,11 +348,18 @@ impl Renderer {
         )
         .expect("Failed to compile shaders");
 
+        let entity_prog = shader::compile_shaders(
+            dis,
+            include_bytes!("../shaders/entities/v.glsl"),
+            include_bytes!("../shaders/entities/f.glsl"),
+        )
+        .expect("Failed to compile shaders");
+
         log::debug!("Setup renderer!");
 
         Renderer {
             cam: Camera::new_with_values(
-                dis.get_framebuffer_dimensions(),
+                dis.get_context().get_framebuffer_dimensions(),
                 Vec3::new(0.0, 0.0, 2.0),
                 Vec3::new(0.0, 0.0, 0.0),
                 90.0,
@@ -77,35 +369,291 @@ impl Renderer {
             chunk_prog: prog,
             hitbox_prog,
 
+            sky: Sky::new(dis),
+            clouds: Clouds::new(dis),
+            particles: ParticleRenderer::new(dis),
+
+            entity_prog,
+            entity_models: HashMap::new(),
+            player_skins: HashMap::new(),
+            held_item: None,
+
             block_textures: SrgbTexture2dArray::empty(dis, 0, 0, 1).unwrap(),
+            mipmap_levels: DEFAULT_MIPMAP_LEVELS,
+            animations: Texture2d::empty_with_format(
+                dis,
+                UncompressedFloatFormat::F32F32F32F32,
+                MipmapsOption::NoMipmap,
+                1,
+                1,
+            )
+            .unwrap(),
+            animation_tick: 0,
         }
     }
 
-    pub fn load_textures(&mut self, dis: &Display) {
-        let mut textures1 = vec![RawImage2d::from_raw_rgba_reversed(
-            image::load(
-                Cursor::new(&include_bytes!("../assets/missing_texture.png")),
-                image::ImageFormat::Png,
-            )
-            .unwrap()
-            .to_rgba8()
-            .as_raw(),
-            (16, 16),
-        )];
+    pub fn load_textures(&mut self, dis: &impl Facade) {
+        self.load_block_textures(dis);
+        self.sky.load_textures(dis);
+        self.clouds.load_textures();
+        self.particles.load_textures(dis);
+        self.load_entity_models(dis);
+        self.held_item = None;
+    }
 
-        let mut textures: Vec<_> = BLOCK_TEXTURES.values().collect();
+    /// Uploads every frame of every block texture as a layer of one texture array, along with
+    /// their mipmaps. Textures can be any size, but they all have to be the same size to share
+    /// the array, so smaller ones are scaled up to the size of the biggest.
+    fn load_block_textures(&mut self, dis: &impl Facade) {
+        let missing = image::load(
+            Cursor::new(&include_bytes!("../assets/missing_texture.png")),
+            image::ImageFormat::Png,
+        )
+        .unwrap()
+        .to_rgba8();
+
+        let block_textures = BLOCK_TEXTURES.load();
+        let mut textures: Vec<_> = block_textures.values().collect();
         textures.sort_by(|t1, t2| t1.index.cmp(&t2.index));
-        let mut textures: Vec<_> = textures
-            .into_iter()
-            .flat_map(|t| t.frames.iter())
-            .map(|t| RawImage2d::from_raw_rgba_reversed(t.as_raw(), (16, 16)))
+        let mut layers = vec![(&missing, RenderLayer::Opaque)];
+        layers.extend(
+            textures
+                .into_iter()
+                .flat_map(|t| t.frames.iter().map(move |f| (f, t.layer))),
+        );
+
+        let size = layers.iter().map(|(img, _)| img.width()).max().unwrap();
+        let levels = self.mipmap_levels.min(mipmaps::max_levels(size));
+        let mipmap_option = match levels {
+            0 => MipmapsOption::NoMipmap,
+            levels => MipmapsOption::EmptyMipmapsMax(levels),
+        };
+        self.block_textures = SrgbTexture2dArray::empty_with_format(
+            dis,
+            SrgbFormat::U8U8U8U8,
+            mipmap_option,
+            size,
+            size,
+            layers.len() as u32,
+        )
+        .unwrap();
+
+        for (i, (img, layer)) in layers.iter().enumerate() {
+            let img = if img.width() == size {
+                (*img).clone()
+            } else {
+                image::imageops::resize(*img, size, size, image::imageops::FilterType::Nearest)
+            };
+
+            for (level, mipmap) in mipmaps::generate_mipmaps(&img, *layer, levels)
+                .iter()
+                .enumerate()
+            {
+                let (width, height) = mipmap.dimensions();
+                self.block_textures
+                    .mipmap(level as u32)
+                    .and_then(|m| m.layer(i as u32))
+                    .unwrap()
+                    .write(
+                        Rect {
+                            left: 0,
+                            bottom: 0,
+                            width,
+                            height,
+                        },
+                        RawImage2d::from_raw_rgba_reversed(mipmap.as_raw(), (width, height)),
+                    );
+            }
+        }
+
+        self.animations = Texture2d::with_format(
+            dis,
+            vec![Self::animation_table(layers.len(), self.animation_tick)],
+            UncompressedFloatFormat::F32F32F32F32,
+            MipmapsOption::NoMipmap,
+        )
+        .unwrap();
+    }
+
+    /// Uploads the model and texture of every type of entity that has a model
+    fn load_entity_models(&mut self, dis: &impl Facade) {
+        self.entity_models.clear();
+
+        for entity in ENTITIES.load().values() {
+            let model = match EntityModel::for_entity(entity) {
+                Some(model) => model,
+                None => continue,
+            };
+
+            let image = match packs::load_image(model.texture) {
+                Ok(img) => img,
+                Err(e) => {
+                    log::error!("Couldn't load entity texture {}: {:?}", model.texture, e);
+                    image::load(
+                        Cursor::new(&include_bytes!("../assets/missing_texture.png")),
+                        image::ImageFormat::Png,
+                    )
+                    .unwrap()
+                    .to_rgba8()
+                }
+            };
+            let dims = image.dimensions();
+            let texture = SrgbTexture2d::new(
+                dis,
+                RawImage2d::from_raw_rgba_reversed(image.as_raw(), dims),
+            )
+            .unwrap();
+
+            let parts = model
+                .parts
+                .iter()
+                .map(|part| {
+                    VertexBuffer::new(dis, &part.generate_vertices(model.texture_size)).unwrap()
+                })
+                .collect();
+
+            self.entity_models.insert(
+                entity.id,
+                EntityModelBuffers {
+                    model,
+                    texture,
+                    parts,
+                },
+            );
+        }
+    }
+
+    /// Uploads a player's skin so their model is drawn with it
+    pub fn load_skin(&mut self, dis: &impl Facade, uuid: UUID4, skin: &RgbaImage) {
+        let texture = SrgbTexture2d::new(
+            dis,
+            RawImage2d::from_raw_rgba_reversed(skin.as_raw(), skin.dimensions()),
+        )
+        .unwrap();
+        self.player_skins.insert(uuid, texture);
+    }
+
+    /// Switches the clouds to a different mode, regenerating them if it has changed
+    pub fn update_clouds(&mut self, dis: &impl Facade, mode: CloudMode) {
+        self.clouds.set_mode(dis, mode);
+    }
+
+    /// Remakes the block textures with a different number of mipmap levels if it has changed
+    pub fn update_mipmaps(&mut self, dis: &impl Facade, levels: u32) {
+        if levels != self.mipmap_levels {
+            self.mipmap_levels = levels;
+            self.load_block_textures(dis);
+        }
+    }
+
+    /// Moves animated textures on to the frame they should show at the given game tick
+    pub fn update_animations(&mut self, tick: u64) {
+        if tick == self.animation_tick {
+            return;
+        }
+        self.animation_tick = tick;
+
+        let layers = self.animations.get_width() as usize;
+        let table = Self::animation_table(layers, tick);
+        self.animations.write(
+            Rect {
+                left: 0,
+                bottom: 0,
+                width: layers as u32,
+                height: 1,
+            },
+            vec![table],
+        );
+    }
+
+    /// Works out which layer every texture layer should be drawn with at a given tick. Layers that
+    /// aren't the first frame of an animated texture are left pointing at themselves.
+    fn animation_table(layers: usize, tick: u64) -> Vec<(f32, f32, f32, f32)> {
+        let mut table: Vec<_> = (0..layers)
+            .map(|i| (i as f32, i as f32, 0.0, 0.0))
             .collect();
-        textures1.append(&mut textures);
 
-        self.block_textures = SrgbTexture2dArray::new(dis, textures1).unwrap();
+        for tex in BLOCK_TEXTURES
+            .load()
+            .values()
+            .filter(|t| !t.animation.is_empty())
+        {
+            if let Some(texel) = table.get_mut(tex.index) {
+                let (current, next, blend) = tex.animation_state(tick);
+                *texel = (current as f32, next as f32, blend, 0.0);
+            }
+        }
+
+        table
     }
 
-    pub fn render_hitboxes(&mut self, target: &mut Frame, ents: &HashMap<i32, Entity>) {
+    /// Draws every entity that has a model, turned to face the way its body and head are facing
+    pub fn render_entities(
+        &self,
+        target: &mut impl Surface,
+        ents: &HashMap<i32, Entity>,
+        sky: &SkyState,
+        settings: &Settings,
+    ) {
+        let fog_col = sky.fog_colour;
+        let params = DrawParameters {
+            depth: Depth {
+                test: draw_parameters::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            backface_culling: BackfaceCullingMode::CullClockwise,
+            ..Default::default()
+        };
+        let behaviour = glium::uniforms::SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            ..Default::default()
+        };
+
+        let inds = NoIndices(glium::index::PrimitiveType::TrianglesList);
+        let pvmat = self.cam.get_pvmat().to_cols_array_2d();
+
+        for ent in ents.values() {
+            let buffers = match self.entity_models.get(&ent.get_type().id) {
+                Some(buffers) => buffers,
+                None => continue,
+            };
+
+            let body_yaw = ent.ori.get_yaw();
+            let head_yaw = (ent.ori_head.get_yaw() - body_yaw).to_radians();
+            let head_pitch = ent.ori_head.get_pitch().to_radians();
+            let model_mat = models::get_model_matrix(ent.pos, body_yaw);
+
+            let texture = self
+                .player_skins
+                .get(&ent.get_uuid())
+                .unwrap_or(&buffers.texture);
+
+            for (part, vertices) in buffers.model.parts.iter().zip(&buffers.parts) {
+                let tmat = model_mat * part.get_transform(head_yaw, head_pitch);
+                let uniforms = uniform! {
+                    pvmat: pvmat,
+                    tmat: tmat.to_cols_array_2d(),
+                    tex: glium::uniforms::Sampler(texture, behaviour),
+                    fogCol: [fog_col.x, fog_col.y, fog_col.z, 1.0],
+                    fogNear: settings.fog_near,
+                    fogFar: settings.fog_far,
+                    daylight: sky.get_terrain_brightness(),
+                };
+
+                target
+                    .draw(vertices, inds, &self.entity_prog, &uniforms, &params)
+                    .expect("Error rendering entity");
+            }
+        }
+    }
+
+    pub fn render_hitboxes<'a>(
+        &self,
+        target: &mut impl Surface,
+        ents: impl Iterator<Item = &'a Entity>,
+    ) {
         let params = DrawParameters {
             depth: Depth {
                 test: draw_parameters::DepthTest::IfLess,
@@ -121,7 +669,7 @@ impl Renderer {
         let inds = NoIndices(glium::index::PrimitiveType::LinesList);
         let pvmat = self.cam.get_pvmat().to_cols_array_2d();
 
-        for ent in ents.values() {
+        for ent in ents {
             let e = ent.get_type();
 
             let mut tmat = Mat4::IDENTITY;
@@ -146,9 +694,194 @@ impl Renderer {
         }
     }
 
-    pub fn render_server(&mut self, target: &mut Frame, serv: &Server, settings: &Settings) {
-        let col = serv.get_sky_colour(&settings.day_colour);
+    pub fn render_server(
+        &mut self,
+        dis: &impl Facade,
+        target: &mut impl Surface,
+        serv: &Server,
+        settings: &Settings,
+    ) {
+        let sky = SkyState::at(
+            serv.get_day_time(),
+            serv.get_world_time(),
+            Vec3::from(settings.day_colour),
+        );
+        self.render_world(target, serv.get_world(), &sky, settings);
+
+        self.render_entities(target, serv.get_entities(), &sky, settings);
+        self.particles.render(
+            dis,
+            target,
+            &self.cam,
+            serv.get_particles(),
+            &self.block_textures,
+            &sky,
+            settings,
+        );
+        self.clouds.render(
+            target,
+            &self.cam,
+            &sky,
+            serv.get_world_time(),
+            serv.get_partial_tick(),
+            settings,
+        );
+
+        // Entities without a model are still drawn as their hitbox so they can be seen
+        let hitboxes = serv.get_entities().values().filter(|ent| {
+            settings.show_hitboxes || !self.entity_models.contains_key(&ent.get_type().id)
+        });
+        self.render_hitboxes(target, hitboxes);
+
+        let items = ITEMS.load();
+        let held = serv
+            .get_inventory()
+            .get_held()
+            .and_then(|stack| items.get(&stack.item));
+        let swing = serv.get_player().get_swing_progress();
+        self.render_hand(dis, target, held, swing, &sky, settings);
+    }
+
+    /// Draws the item the player is holding, or their arm if they aren't holding anything, in
+    /// front of everything else
+    pub fn render_hand(
+        &mut self,
+        dis: &impl Facade,
+        target: &mut impl Surface,
+        held: Option<&Item>,
+        swing: f32,
+        sky: &SkyState,
+        settings: &Settings,
+    ) {
+        let params = DrawParameters {
+            depth: Depth {
+                test: draw_parameters::DepthTest::IfLess,
+                write: true,
+                range: hand::HAND_DEPTH_RANGE,
+                ..Default::default()
+            },
+            backface_culling: BackfaceCullingMode::CullClockwise,
+            ..Default::default()
+        };
+        let behaviour = glium::uniforms::SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            ..Default::default()
+        };
+
+        let inds = NoIndices(glium::index::PrimitiveType::TrianglesList);
+        let pmat = hand::get_hand_pmat(self.cam.get_aspect_ratio()).to_cols_array_2d();
+        let col = sky.fog_colour;
+
+        let item = match held {
+            Some(item) => item,
+            None => {
+                if let Some(buffers) = self.entity_models.get(&(PLAYER_INDEX as u32)) {
+                    let tmat = hand::arm_transform(swing)
+                        * buffers.model.parts[RIGHT_ARM].get_transform(0.0, 0.0);
+                    let uniforms = uniform! {
+                        pvmat: pmat,
+                        tmat: tmat.to_cols_array_2d(),
+                        tex: glium::uniforms::Sampler(&buffers.texture, behaviour),
+                        fogCol: [col.x, col.y, col.z, 1.0],
+                        fogNear: settings.fog_near,
+                        fogFar: settings.fog_far,
+                        daylight: sky.get_terrain_brightness(),
+                    };
+                    target
+                        .draw(
+                            &buffers.parts[RIGHT_ARM],
+                            inds,
+                            &self.entity_prog,
+                            &uniforms,
+                            &params,
+                        )
+                        .expect("Error rendering arm");
+                }
+                return;
+            }
+        };
+
+        if self.held_item.as_ref().map(|(id, _)| *id) != Some(item.id) {
+            self.held_item = Some((item.id, HeldMesh::new(dis, item)));
+        }
+        let display = item
+            .model
+            .as_ref()
+            .and_then(|model| model.get_display("firstperson_righthand"));
+        let tmat = hand::item_transform(swing, display);
+
+        match &self.held_item.as_ref().unwrap().1 {
+            HeldMesh::Block(buffers) => {
+                let uniforms = |alpha_cutoff: f32| {
+                    uniform! {
+                        pvmat: pmat,
+                        tmat: tmat.to_cols_array_2d(),
+                        textures: glium::uniforms::Sampler(&self.block_textures, behaviour),
+                        animations: glium::uniforms::Sampler(&self.animations, behaviour),
+                        fogCol: [col.x, col.y, col.z, 1.0],
+                        fogNear: settings.fog_near,
+                        fogFar: settings.fog_far,
+                        alphaCutoff: alpha_cutoff,
+                        daylight: sky.get_terrain_brightness(),
+                    }
+                };
+                let translucent_params = DrawParameters {
+                    depth: Depth {
+                        write: false,
+                        ..params.depth
+                    },
+                    blend: Blend::alpha_blending(),
+                    ..params.clone()
+                };
+
+                for (layer, alpha_cutoff, params) in [
+                    (&buffers.opaque, 0.0, &params),
+                    (&buffers.cutout, CUTOUT_ALPHA, &params),
+                    (&buffers.translucent, 0.0, &translucent_params),
+                ] {
+                    if let Some(layer) = layer {
+                        target
+                            .draw(
+                                &layer.vertices,
+                                &layer.indices,
+                                &self.chunk_prog,
+                                &uniforms(alpha_cutoff),
+                                params,
+                            )
+                            .expect("Error rendering held item");
+                    }
+                }
+            }
+            HeldMesh::Sprite(vertices, texture) => {
+                let uniforms = uniform! {
+                    pvmat: pmat,
+                    tmat: tmat.to_cols_array_2d(),
+                    tex: glium::uniforms::Sampler(texture, behaviour),
+                    fogCol: [col.x, col.y, col.z, 1.0],
+                    fogNear: settings.fog_near,
+                    fogFar: settings.fog_far,
+                    daylight: sky.get_terrain_brightness(),
+                };
+                target
+                    .draw(vertices, inds, &self.entity_prog, &uniforms, &params)
+                    .expect("Error rendering held item");
+            }
+            HeldMesh::None => {}
+        }
+    }
+
+    /// Clears the screen and draws the sky and the blocks of a world
+    pub fn render_world(
+        &self,
+        target: &mut impl Surface,
+        world: &World,
+        sky: &SkyState,
+        settings: &Settings,
+    ) {
+        let col = sky.fog_colour;
         target.clear_color_and_depth((col.x, col.y, col.z, 0.0), 1.0);
+        self.sky.render(target, &self.cam, sky);
 
         let params = DrawParameters {
             depth: Depth {
@@ -165,14 +898,49 @@ impl Renderer {
             magnify_filter: MagnifySamplerFilter::Nearest,
             ..Default::default()
         };
-
-        let inds = NoIndices(TrianglesList);
+        // Merged faces have texture coordinates past 1 so the texture repeats across them.
+        // Further away blocks use the smaller mipmaps, blending between them so there's no
+        // visible line where it switches, but each one is still sampled with nearest filtering
+        // to keep the pixelated look.
+        let block_behaviour = glium::uniforms::SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Repeat,
+                SamplerWrapFunction::Repeat,
+                SamplerWrapFunction::Repeat,
+            ),
+            minify_filter: match self.mipmap_levels {
+                0 => MinifySamplerFilter::Nearest,
+                _ => MinifySamplerFilter::NearestMipmapLinear,
+            },
+            max_anisotropy: settings.anisotropy.max(1),
+            ..behaviour
+        };
 
         let vf = self.cam.generate_view_frustum();
         let pvmat = self.cam.get_pvmat().to_cols_array_2d();
+        // Sections hidden behind the terrain, like caves underground, aren't drawn
+        let visible = find_visible_sections(world, *self.cam.get_pos(), &vf);
         let mut points = vec![Vec3::new(0.0, 0.0, 0.0); 8];
 
-        for (pos, chunk) in serv.get_world().get_chunks() {
+        let block_textures = &self.block_textures;
+        let animations = &self.animations;
+        let uniforms = |offset: Vec3, alpha_cutoff: f32| {
+            uniform! {
+                pvmat: pvmat,
+                tmat: Mat4::from_translation(offset).to_cols_array_2d(),
+                textures: glium::uniforms::Sampler(block_textures, block_behaviour),
+                animations: glium::uniforms::Sampler(animations, behaviour),
+                fogCol: [col.x, col.y, col.z, 1.0],
+                fogNear: settings.fog_near,
+                fogFar: settings.fog_far,
+                alphaCutoff: alpha_cutoff,
+                daylight: sky.get_terrain_brightness(),
+            }
+        };
+
+        let mut translucent: Vec<(Vec3, &LayerBuffers)> = Vec::new();
+
+        for (pos, chunk) in world.get_chunks() {
             // Try to frustum cull this whole chunk column
             let cx = (pos.x * 16) as f32;
             let cz = (pos.y * 16) as f32;
@@ -191,7 +959,7 @@ impl Renderer {
             }
 
             for y in MIN_SECTION..=MAX_SECTION {
-                if let Some(vbo) = chunk.get_section_vbo(y) {
+                if let Some(buffers) = chunk.get_section_buffers(y) {
                     let cy = (y * 16) as f32;
 
                     // Get points for corners of chunk section
@@ -224,25 +992,67 @@ impl Renderer {
                     if !vf.accept_points(&points) {
                         continue;
                     }
+                    if !visible.contains(&IVec3::new(pos.x, y, pos.y)) {
+                        continue;
+                    }
 
-                    let tmat: Mat4 = Mat4::from_translation(Vec3::new(cx, cy, cz));
+                    let offset = Vec3::new(cx, cy, cz);
 
-                    let uniforms = uniform! {
-                        pvmat: pvmat,
-                        tmat: tmat.to_cols_array_2d(),
-                        textures: glium::uniforms::Sampler(&self.block_textures, behaviour),
-                        fogCol: [col.x, col.y, col.z, 1.0],
-                        fogNear: settings.fog_near,
-                        fogFar: settings.fog_far,
-                    };
-
-                    target
-                        .draw(vbo, inds, &self.chunk_prog, &uniforms, &params)
-                        .unwrap();
+                    if let Some(layer) = &buffers.opaque {
+                        target
+                            .draw(
+                                &layer.vertices,
+                                &layer.indices,
+                                &self.chunk_prog,
+                                &uniforms(offset, 0.0),
+                                &params,
+                            )
+                            .unwrap();
+                    }
+                    if let Some(layer) = &buffers.cutout {
+                        target
+                            .draw(
+                                &layer.vertices,
+                                &layer.indices,
+                                &self.chunk_prog,
+                                &uniforms(offset, CUTOUT_ALPHA),
+                                &params,
+                            )
+                            .unwrap();
+                    }
+                    if let Some(layer) = &buffers.translucent {
+                        translucent.push((offset, layer));
+                    }
                 }
             }
         }
 
-        self.render_hitboxes(target, serv.get_entities());
+        // Translucent faces are drawn last, furthest away first, so whatever is behind them has
+        // already been drawn to blend with
+        let cam_pos = *self.cam.get_pos();
+        let distance = |offset: &Vec3| (*offset + Vec3::splat(8.0)).distance_squared(cam_pos);
+        translucent.sort_by(|(a, _), (b, _)| distance(b).total_cmp(&distance(a)));
+
+        let translucent_params = DrawParameters {
+            depth: Depth {
+                test: draw_parameters::DepthTest::IfLess,
+                write: false,
+                ..Default::default()
+            },
+            blend: Blend::alpha_blending(),
+            backface_culling: BackfaceCullingMode::CullClockwise,
+            ..Default::default()
+        };
+        for (offset, layer) in translucent {
+            target
+                .draw(
+                    &layer.vertices,
+                    &layer.indices,
+                    &self.chunk_prog,
+                    &uniforms(offset, 0.0),
+                    &translucent_params,
+                )
+                .unwrap();
+        }
     }
 }
@@ -155,6 +155,10 @@ impl Camera {
         self.fov
     }
 
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect
+    }
+
     /// Returns the perspectiva matrix for this camera
     pub fn get_pmat(&self) -> &Mat4 {
         &self.pmat
@@ -196,6 +200,17 @@ impl Camera {
         self.update_pvmat();
     }
 
+    /// Projects a point in the world into normalised device coordinates, where x and y are in the
+    /// range -1 to 1 if the point is on screen. Returns None if the point is behind the camera
+    pub fn project(&self, point: Vec3) -> Option<Vec3> {
+        let clip = self.pvmat * point.extend(1.0);
+        if clip.w <= NEAR_PLANE {
+            return None;
+        }
+
+        Some(clip.xyz() / clip.w)
+    }
+
     pub fn get_look_vector(&self) -> Vec3 {
         let mut dir: Vec4 = Vec4::new(0.0, 0.0, -1.0, 1.0);
 
@@ -361,6 +376,20 @@ impl ViewFrustum {
         true
     }
 
+    /// Checks if any part of the axis aligned box between `min` and `max` might be in view
+    pub fn accept_box(&self, min: Vec3, max: Vec3) -> bool {
+        let points = (0..8)
+            .map(|i| {
+                Vec3::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                )
+            })
+            .collect();
+        self.accept_points(&points)
+    }
+
     fn check_plane(plane_pos: &Vec3, plane_norm: &Vec3, point: &Vec3) -> bool {
         let v = *point - *plane_pos;
         plane_norm.dot(v) > 0.0
@@ -0,0 +1,343 @@
+use glam::{Mat4, Vec3};
+use glium::{
+    backend::Facade,
+    draw_parameters::{BackfaceCullingMode, Blend, Depth, DepthTest},
+    index::{NoIndices, PrimitiveType},
+    DrawParameters, Program, Surface, VertexBuffer,
+};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::{resources::packs, settings::Settings};
+
+use super::{camera::Camera, shader, sky::SkyState};
+
+/// Height of the bottom of the clouds
+const CLOUD_HEIGHT: f32 = 128.33;
+/// How tall 3D clouds are
+const CLOUD_THICKNESS: f32 = 4.0;
+/// How many blocks wide each pixel of the cloud texture is
+const CELL_SIZE: f32 = 12.0;
+/// How far the clouds move every tick, in blocks
+const SCROLL_SPEED: f32 = 0.03;
+
+/// How the clouds are drawn
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudMode {
+    Off,
+    /// A single layer which can be seen from above and below
+    Flat,
+    /// Boxes with sides, like fancy clouds in the game
+    Fancy,
+}
+
+impl Default for CloudMode {
+    fn default() -> CloudMode {
+        CloudMode::Fancy
+    }
+}
+
+/// A corner of a cloud, with how much the face it's part of is shaded
+#[derive(Debug, Copy, Clone)]
+pub struct CloudVertex {
+    pub position: [f32; 3],
+    pub shade: f32,
+}
+implement_vertex!(CloudVertex, position, shade);
+
+/// Which pixels of the cloud texture have clouds in them. The texture wraps round, so clouds
+/// off one edge carry on from the opposite one.
+pub struct CloudMap {
+    width: i32,
+    height: i32,
+    cells: Vec<bool>,
+}
+
+impl CloudMap {
+    pub fn from_image(img: &RgbaImage) -> CloudMap {
+        let (width, height) = img.dimensions();
+        CloudMap {
+            width: width as i32,
+            height: height as i32,
+            cells: img.pixels().map(|p| p[3] >= 128).collect(),
+        }
+    }
+
+    pub fn is_cloud(&self, x: i32, z: i32) -> bool {
+        let x = x.rem_euclid(self.width);
+        let z = z.rem_euclid(self.height);
+        self.cells[(z * self.width + x) as usize]
+    }
+
+    /// How far the clouds go in each direction before they repeat
+    pub fn get_size(&self) -> (f32, f32) {
+        (
+            self.width as f32 * CELL_SIZE,
+            self.height as f32 * CELL_SIZE,
+        )
+    }
+
+    /// Generates a mesh of the clouds, with the bottom of them at a height of 0
+    pub fn generate_mesh(&self, mode: CloudMode) -> Vec<CloudVertex> {
+        let mut verts = Vec::new();
+        if mode == CloudMode::Off {
+            return verts;
+        }
+
+        let top = match mode {
+            CloudMode::Fancy => CLOUD_THICKNESS,
+            _ => 0.0,
+        };
+
+        for z in 0..self.height {
+            // Tops and bottoms are joined along each row so there are fewer of them
+            let mut x = 0;
+            while x < self.width {
+                if !self.is_cloud(x, z) {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < self.width && self.is_cloud(x, z) {
+                    x += 1;
+                }
+
+                let (x0, x1) = (start as f32 * CELL_SIZE, x as f32 * CELL_SIZE);
+                let (z0, z1) = (z as f32 * CELL_SIZE, (z + 1) as f32 * CELL_SIZE);
+                push_quad(
+                    &mut verts,
+                    [
+                        Vec3::new(x0, top, z0),
+                        Vec3::new(x0, top, z1),
+                        Vec3::new(x1, top, z1),
+                        Vec3::new(x1, top, z0),
+                    ],
+                    1.0,
+                );
+                if mode == CloudMode::Fancy {
+                    push_quad(
+                        &mut verts,
+                        [
+                            Vec3::new(x0, 0.0, z0),
+                            Vec3::new(x1, 0.0, z0),
+                            Vec3::new(x1, 0.0, z1),
+                            Vec3::new(x0, 0.0, z1),
+                        ],
+                        0.7,
+                    );
+                }
+            }
+
+            if mode != CloudMode::Fancy {
+                continue;
+            }
+
+            // Sides are only needed where a cloud is next to clear sky
+            for x in 0..self.width {
+                if !self.is_cloud(x, z) {
+                    continue;
+                }
+                let (x0, x1) = (x as f32 * CELL_SIZE, (x + 1) as f32 * CELL_SIZE);
+                let (z0, z1) = (z as f32 * CELL_SIZE, (z + 1) as f32 * CELL_SIZE);
+                let (y0, y1) = (0.0, CLOUD_THICKNESS);
+
+                if !self.is_cloud(x + 1, z) {
+                    push_quad(
+                        &mut verts,
+                        [
+                            Vec3::new(x1, y0, z0),
+                            Vec3::new(x1, y1, z0),
+                            Vec3::new(x1, y1, z1),
+                            Vec3::new(x1, y0, z1),
+                        ],
+                        0.9,
+                    );
+                }
+                if !self.is_cloud(x - 1, z) {
+                    push_quad(
+                        &mut verts,
+                        [
+                            Vec3::new(x0, y0, z0),
+                            Vec3::new(x0, y0, z1),
+                            Vec3::new(x0, y1, z1),
+                            Vec3::new(x0, y1, z0),
+                        ],
+                        0.9,
+                    );
+                }
+                if !self.is_cloud(x, z + 1) {
+                    push_quad(
+                        &mut verts,
+                        [
+                            Vec3::new(x0, y0, z1),
+                            Vec3::new(x1, y0, z1),
+                            Vec3::new(x1, y1, z1),
+                            Vec3::new(x0, y1, z1),
+                        ],
+                        0.8,
+                    );
+                }
+                if !self.is_cloud(x, z - 1) {
+                    push_quad(
+                        &mut verts,
+                        [
+                            Vec3::new(x0, y0, z0),
+                            Vec3::new(x0, y1, z0),
+                            Vec3::new(x1, y1, z0),
+                            Vec3::new(x1, y0, z0),
+                        ],
+                        0.8,
+                    );
+                }
+            }
+        }
+
+        verts
+    }
+}
+
+/// Adds two triangles for a face, with the corners going anticlockwise when looked at from the
+/// front
+fn push_quad(verts: &mut Vec<CloudVertex>, corners: [Vec3; 4], shade: f32) {
+    for i in [0, 1, 2, 0, 2, 3] {
+        verts.push(CloudVertex {
+            position: corners[i].to_array(),
+            shade,
+        });
+    }
+}
+
+/// Draws the cloud layer, which drifts along with the world time
+pub struct Clouds {
+    prog: Program,
+    map: Option<CloudMap>,
+    /// The mode the current mesh was generated for
+    mode: CloudMode,
+    mesh: Option<VertexBuffer<CloudVertex>>,
+}
+
+impl Clouds {
+    pub fn new(dis: &impl Facade) -> Clouds {
+        let prog = shader::compile_shaders(
+            dis,
+            include_bytes!("../../shaders/clouds/v.glsl"),
+            include_bytes!("../../shaders/clouds/f.glsl"),
+        )
+        .expect("Failed to compile shaders");
+
+        Clouds {
+            prog,
+            map: None,
+            mode: CloudMode::Off,
+            mesh: None,
+        }
+    }
+
+    pub fn load_textures(&mut self) {
+        let path = "textures/environment/clouds.png";
+        match packs::load_image(path) {
+            Ok(img) => self.map = Some(CloudMap::from_image(&img)),
+            Err(e) => log::error!("Couldn't load cloud texture {}: {:?}", path, e),
+        }
+        self.mesh = None;
+    }
+
+    /// Regenerates the mesh if the cloud mode has been changed
+    pub fn set_mode(&mut self, dis: &impl Facade, mode: CloudMode) {
+        if self.mesh.is_some() && self.mode == mode {
+            return;
+        }
+        self.mode = mode;
+        self.mesh = self
+            .map
+            .as_ref()
+            .map(|map| map.generate_mesh(mode))
+            .filter(|verts| !verts.is_empty())
+            .map(|verts| VertexBuffer::new(dis, &verts).unwrap());
+    }
+
+    pub fn render(
+        &self,
+        target: &mut impl Surface,
+        cam: &Camera,
+        sky: &SkyState,
+        world_time: i64,
+        partial_tick: f32,
+        settings: &Settings,
+    ) {
+        let (map, mesh) = match (&self.map, &self.mesh) {
+            (Some(map), Some(mesh)) => (map, mesh),
+            _ => return,
+        };
+
+        // The clouds move along x, wrapping round once they've moved the whole width of the
+        // texture. Only the time since they last wrapped is used so it stays precise.
+        let (width, depth) = map.get_size();
+        let wrap_ticks = (width / SCROLL_SPEED) as i64;
+        let scroll = (world_time.rem_euclid(wrap_ticks) as f32 + partial_tick) * SCROLL_SPEED;
+
+        let brightness = sky.daylight * 0.9 + 0.1;
+        let fog_col = sky.fog_colour;
+        let pvmat = cam.get_pvmat().to_cols_array_2d();
+        let cam_pos = *cam.get_pos();
+
+        // The mesh is drawn as many times as it takes to cover everything in fog distance
+        let reach = settings.fog_far;
+        let tiles_x = ((cam_pos.x + scroll - reach) / width).floor() as i32
+            ..=((cam_pos.x + scroll + reach) / width).floor() as i32;
+        let tiles_z = ((cam_pos.z - reach) / depth).floor() as i32
+            ..=((cam_pos.z + reach) / depth).floor() as i32;
+
+        let culling = match self.mode {
+            CloudMode::Fancy => BackfaceCullingMode::CullClockwise,
+            _ => BackfaceCullingMode::CullingDisabled,
+        };
+        // Depth is drawn first on its own so only the closest faces get blended with what's
+        // behind, otherwise faces further back would show through
+        let depth_params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            color_mask: (false, false, false, false),
+            backface_culling: culling,
+            ..Default::default()
+        };
+        let colour_params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            blend: Blend::alpha_blending(),
+            backface_culling: culling,
+            ..Default::default()
+        };
+
+        let inds = NoIndices(PrimitiveType::TrianglesList);
+        for params in [&depth_params, &colour_params] {
+            for tile_x in tiles_x.clone() {
+                for tile_z in tiles_z.clone() {
+                    let offset = Vec3::new(
+                        tile_x as f32 * width - scroll,
+                        CLOUD_HEIGHT,
+                        tile_z as f32 * depth,
+                    );
+                    let uniforms = uniform! {
+                        pvmat: pvmat,
+                        tmat: Mat4::from_translation(offset).to_cols_array_2d(),
+                        camPos: cam_pos.to_array(),
+                        cloudCol: [brightness, brightness, brightness, 0.8],
+                        fogCol: [fog_col.x, fog_col.y, fog_col.z, 1.0],
+                        fogNear: settings.fog_near,
+                        fogFar: settings.fog_far,
+                    };
+                    target
+                        .draw(mesh, inds, &self.prog, &uniforms, params)
+                        .expect("Error rendering clouds");
+                }
+            }
+        }
+    }
+}
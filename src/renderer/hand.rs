@@ -0,0 +1,244 @@
+use std::f32::consts::PI;
+
+use glam::{Mat4, Vec2, Vec3};
+use glium::{
+    backend::Facade,
+    texture::{RawImage2d, SrgbTexture2d},
+    VertexBuffer,
+};
+use image::RgbaImage;
+
+use crate::{
+    resources::{
+        block_models::Display,
+        items::{Item, ItemModel},
+    },
+    world::chunks::SectionBuffers,
+};
+
+use super::{item_icons::load_layers, ChunkMesh, EntityVertex};
+
+/// Field of view the hand is drawn with, so it looks the same whatever the camera's is
+pub const HAND_FOV: f32 = 70.0;
+/// The hand is drawn into the front of the depth buffer, so it's always in front of the world
+pub const HAND_DEPTH_RANGE: (f32, f32) = (0.0, 0.1);
+
+/// The projection matrix the hand is drawn with
+pub fn get_hand_pmat(aspect: f32) -> Mat4 {
+    Mat4::perspective_rh(HAND_FOV.to_radians(), aspect, 0.05, 10.0)
+}
+
+/// Moves the player's right arm from its model's space, in pixels, to in front of the camera.
+/// `swing` is how far through swinging the arm is, from 0 to 1. Matches how the game places the
+/// arm, which is then drawn around its pivot in the model.
+pub fn arm_transform(swing: f32) -> Mat4 {
+    let root = swing.sqrt();
+    Mat4::from_translation(Vec3::new(
+        0.64 - 0.3 * (root * PI).sin(),
+        0.4 * (root * 2.0 * PI).sin() - 0.6,
+        -0.72 - 0.4 * (swing * PI).sin(),
+    )) * Mat4::from_rotation_y(45f32.to_radians())
+        * Mat4::from_rotation_y(((root * PI).sin() * 70.0).to_radians())
+        * Mat4::from_rotation_z(((swing * swing * PI).sin() * -20.0).to_radians())
+        * Mat4::from_translation(Vec3::new(-1.0, 3.6, 3.5))
+        * Mat4::from_rotation_z(120f32.to_radians())
+        * Mat4::from_rotation_x(200f32.to_radians())
+        * Mat4::from_rotation_y(-135f32.to_radians())
+        * Mat4::from_translation(Vec3::new(5.6, 0.0, 0.0))
+        * Mat4::from_scale(Vec3::splat(1.0 / 16.0))
+}
+
+/// Moves a held item's model, which goes from 0 to 1 like a block, to in front of the camera,
+/// using the transform the model gives for being held in the right hand in first person
+pub fn item_transform(swing: f32, display: Option<&Display>) -> Mat4 {
+    let root = swing.sqrt();
+    let turn = (root * PI).sin();
+    Mat4::from_translation(Vec3::new(
+        -0.4 * turn,
+        0.2 * (root * 2.0 * PI).sin(),
+        -0.2 * (swing * PI).sin(),
+    )) * Mat4::from_translation(Vec3::new(0.56, -0.52, -0.72))
+        * Mat4::from_rotation_y((45.0 - (swing * swing * PI).sin() * 20.0).to_radians())
+        * Mat4::from_rotation_z((turn * -20.0).to_radians())
+        * Mat4::from_rotation_x((turn * -80.0).to_radians())
+        * Mat4::from_rotation_y(-45f32.to_radians())
+        * display.map_or(Mat4::IDENTITY, |d| d.get_matrix())
+        * Mat4::from_translation(Vec3::splat(-0.5))
+}
+
+/// The mesh of the item being held, on the GPU
+pub enum HeldMesh {
+    /// A block model, drawn with the block textures
+    Block(SectionBuffers),
+    /// A sprite given a pixel of thickness, drawn with its own texture
+    Sprite(VertexBuffer<EntityVertex>, SrgbTexture2d),
+    /// The item has no model that can be drawn
+    None,
+}
+
+impl HeldMesh {
+    pub fn new(dis: &impl Facade, item: &Item) -> HeldMesh {
+        match &item.model {
+            Some(ItemModel::Block(model)) => {
+                let mut mesh = ChunkMesh::new();
+                for (layer, quad) in model.get_quads(item.tint.colour(None)) {
+                    mesh.layer_mut(layer).push_quad(quad);
+                }
+                HeldMesh::Block(SectionBuffers::new(dis, &mesh))
+            }
+            Some(ItemModel::Generated { layers, .. }) => match load_layers(layers) {
+                Some(sprite) => {
+                    let texture = SrgbTexture2d::new(
+                        dis,
+                        RawImage2d::from_raw_rgba_reversed(sprite.as_raw(), sprite.dimensions()),
+                    )
+                    .unwrap();
+                    let vertices = VertexBuffer::new(dis, &sprite_vertices(&sprite)).unwrap();
+                    HeldMesh::Sprite(vertices, texture)
+                }
+                None => HeldMesh::None,
+            },
+            Some(ItemModel::Builtin) | None => HeldMesh::None,
+        }
+    }
+}
+
+/// Turns a sprite into a model a pixel thick, like the game does with items. The front and back
+/// are the whole sprite, with the sides of each pixel added where it's next to a clear one.
+fn sprite_vertices(sprite: &RgbaImage) -> Vec<EntityVertex> {
+    let (width, height) = sprite.dimensions();
+    let size = Vec2::new(width as f32, height as f32);
+    let (back, front) = (7.5 / 16.0, 8.5 / 16.0);
+    let mut verts = Vec::new();
+
+    // The sprite covers x and y from 0 to 1, with texture coordinates matching
+    let whole = |pos: Vec3| Vec2::new(pos.x, pos.y);
+    let square = |z: f32| {
+        [
+            Vec3::new(0.0, 0.0, z),
+            Vec3::new(1.0, 0.0, z),
+            Vec3::new(1.0, 1.0, z),
+            Vec3::new(0.0, 1.0, z),
+        ]
+    };
+    push_quad(&mut verts, square(front), Vec3::Z, whole);
+    push_quad(&mut verts, square(back), -Vec3::Z, whole);
+
+    let is_clear = |x: i64, y: i64| {
+        x < 0
+            || y < 0
+            || x >= width as i64
+            || y >= height as i64
+            || sprite.get_pixel(x as u32, y as u32)[3] == 0
+    };
+    for (x, y, pixel) in sprite.enumerate_pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+
+        // Rows of the image go down while y goes up
+        let min = Vec2::new(x as f32, (height - y - 1) as f32) / size;
+        let max = min + Vec2::ONE / size;
+        let centre = (min + max) / 2.0;
+        let (x, y) = (x as i64, y as i64);
+
+        let sides = [
+            (x - 1, y, -Vec3::X, [min.x, min.y, min.x, max.y]),
+            (x + 1, y, Vec3::X, [max.x, min.y, max.x, max.y]),
+            (x, y - 1, Vec3::Y, [min.x, max.y, max.x, max.y]),
+            (x, y + 1, -Vec3::Y, [min.x, min.y, max.x, min.y]),
+        ];
+        for (nx, ny, normal, [x0, y0, x1, y1]) in sides {
+            if !is_clear(nx, ny) {
+                continue;
+            }
+            let corners = [
+                Vec3::new(x0, y0, back),
+                Vec3::new(x1, y1, back),
+                Vec3::new(x1, y1, front),
+                Vec3::new(x0, y0, front),
+            ];
+            // The whole side is the colour of the pixel it's on
+            push_quad(&mut verts, corners, normal, |_| centre);
+        }
+    }
+
+    verts
+}
+
+/// Adds two triangles making up a quad, turned so they face the way of `normal`
+fn push_quad(
+    verts: &mut Vec<EntityVertex>,
+    mut corners: [Vec3; 4],
+    normal: Vec3,
+    uv: impl Fn(Vec3) -> Vec2,
+) {
+    if (corners[1] - corners[0])
+        .cross(corners[2] - corners[0])
+        .dot(normal)
+        < 0.0
+    {
+        corners.reverse();
+    }
+
+    for i in [0, 1, 2, 0, 2, 3] {
+        verts.push(EntityVertex {
+            position: corners[i].to_array(),
+            tex_coords: uv(corners[i]).to_array(),
+            normal: normal.to_array(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn sprites_only_have_outside_edges() {
+        let mut sprite = RgbaImage::new(3, 3);
+        assert_eq!(sprite_vertices(&sprite).len(), 2 * 6);
+
+        sprite.put_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        assert_eq!(sprite_vertices(&sprite).len(), 6 * 6);
+
+        // Pixels next to each other don't have sides between them
+        sprite.put_pixel(2, 1, Rgba([255, 255, 255, 255]));
+        assert_eq!(sprite_vertices(&sprite).len(), 8 * 6);
+    }
+
+    #[test]
+    fn sprite_faces_point_outwards() {
+        let sprite = RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        for tri in sprite_vertices(&sprite).chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(tri[i].position));
+            let normal = Vec3::from(tri[0].normal);
+            assert!((b - a).cross(c - a).dot(normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn held_things_are_in_view() {
+        let pmat = get_hand_pmat(16.0 / 9.0);
+        let on_screen = |point: Vec3| {
+            let clip = pmat * point.extend(1.0);
+            clip.w > 0.0 && (clip.truncate() / clip.w).abs().max_element() <= 1.0
+        };
+
+        // How the game's item/generated model is held
+        let display = Display {
+            rotation: Vec3::new(0.0, -90.0, 25.0),
+            translation: Vec3::new(1.13, 3.2, 1.13),
+            scale: Vec3::splat(0.68),
+        };
+        for swing in [0.0, 0.5, 1.0] {
+            let item = item_transform(swing, Some(&display)).transform_point3(Vec3::splat(0.5));
+            assert!(on_screen(item), "{} {:?}", swing, item);
+        }
+        // The hand at the end of the arm, which is 12 pixels long
+        let hand = arm_transform(0.0).transform_point3(Vec3::new(-6.0, 12.0, 0.0));
+        assert!(on_screen(hand), "{:?}", hand);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use glam::{Mat4, Vec2, Vec3};
+use image::{imageops, GenericImageView, Rgba, RgbaImage};
+
+use crate::resources::{
+    block_models::{BlockModel, Display},
+    items::{texture_path, Item, ItemModel},
+    packs, RenderLayer, BLOCK_TEXTURES,
+};
+
+use super::FaceVertex;
+
+/// Width and height of item icons in pixels
+pub const ICON_SIZE: u32 = 32;
+
+/// Draws an item the way it looks in the inventory, or gives None if it has no model that can
+/// be drawn
+pub fn render_icon(item: &Item) -> Option<RgbaImage> {
+    match item.model.as_ref()? {
+        ItemModel::Generated { layers, .. } => generated_icon(layers),
+        ItemModel::Block(model) => Some(block_icon(model, item.tint.colour(None))),
+        ItemModel::Builtin => None,
+    }
+}
+
+/// Loads the first frame of an item's sprite texture
+pub fn load_sprite(texture: &str) -> Option<RgbaImage> {
+    match packs::load_image(&texture_path(texture)) {
+        // Animated sprites have their frames stacked on top of each other
+        Ok(img) if img.height() > img.width() => {
+            Some(img.view(0, 0, img.width(), img.width()).to_image())
+        }
+        Ok(img) => Some(img),
+        Err(e) => {
+            log::error!("Couldn't load item texture {}: {}", texture, e);
+            None
+        }
+    }
+}
+
+/// Draws the layers of a sprite on top of each other, at the size of the first layer
+pub fn load_layers(layers: &[String]) -> Option<RgbaImage> {
+    let mut sprite: Option<RgbaImage> = None;
+    for layer in layers.iter().filter_map(|layer| load_sprite(layer)) {
+        match &mut sprite {
+            Some(sprite) => {
+                let (width, height) = sprite.dimensions();
+                let layer = imageops::resize(&layer, width, height, imageops::FilterType::Nearest);
+                imageops::overlay(sprite, &layer, 0, 0);
+            }
+            None => sprite = Some(layer),
+        }
+    }
+    sprite
+}
+
+fn generated_icon(layers: &[String]) -> Option<RgbaImage> {
+    let sprite = load_layers(layers)?;
+    Some(imageops::resize(
+        &sprite,
+        ICON_SIZE,
+        ICON_SIZE,
+        imageops::FilterType::Nearest,
+    ))
+}
+
+/// Draws a block model turned to show its top and two of its sides, unless the model says
+/// otherwise
+fn block_icon(model: &BlockModel, tint: [f32; 3]) -> RgbaImage {
+    let transform = model
+        .get_display("gui")
+        .map(|d| d.get_matrix())
+        .unwrap_or_else(default_gui_transform);
+
+    // Each layer of the block texture array, so faces can be textured by their layer
+    let textures = BLOCK_TEXTURES.load();
+    let mut layers = HashMap::new();
+    for texture in textures.values() {
+        for (i, frame) in texture.frames.iter().enumerate() {
+            layers.insert(texture.index + i, frame);
+        }
+    }
+
+    rasterise(&model.get_quads(tint), transform, |layer| {
+        layers.get(&layer).copied()
+    })
+}
+
+/// The transform blocks get in the GUI in the game's `block/block` model
+fn default_gui_transform() -> Mat4 {
+    Display {
+        rotation: Vec3::new(30.0, 225.0, 0.0),
+        translation: Vec3::ZERO,
+        scale: Vec3::splat(0.625),
+    }
+    .get_matrix()
+}
+
+/// Draws the faces of a model into an icon, looking along -z with the model centred on the
+/// origin after it's been transformed. Faces use the texture array layer from their texture
+/// coordinates, and are left magenta if there isn't a texture for it.
+fn rasterise<'a>(
+    quads: &[(RenderLayer, [FaceVertex; 4])],
+    transform: Mat4,
+    texture: impl Fn(usize) -> Option<&'a RgbaImage>,
+) -> RgbaImage {
+    let size = ICON_SIZE as usize;
+    let mut icon = RgbaImage::new(ICON_SIZE, ICON_SIZE);
+    let mut depth = vec![f32::NEG_INFINITY; size * size];
+
+    // Translucent faces are blended over everything else once it's been drawn
+    let mut quads: Vec<_> = quads.iter().collect();
+    quads.sort_by_key(|(layer, _)| *layer == RenderLayer::Translucent);
+
+    for (layer, quad) in quads {
+        // Positions in pixels, with y going down the image and z towards the viewer
+        let points = quad.map(|v| {
+            let pos = transform.transform_point3(Vec3::from(v.position) - Vec3::splat(0.5));
+            Vec3::new(
+                (pos.x + 0.5) * ICON_SIZE as f32,
+                (0.5 - pos.y) * ICON_SIZE as f32,
+                pos.z,
+            )
+        });
+        let tex = texture(quad[0].tex_coords[2] as usize);
+
+        for tri in [[0, 1, 2], [0, 2, 3]] {
+            let [a, b, c] = tri.map(|i| points[i]);
+            let area = edge(a, b, c);
+            if area.abs() < f32::EPSILON {
+                continue;
+            }
+
+            let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as usize;
+            let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as usize;
+            let max_x = (a.x.max(b.x).max(c.x).ceil() as usize).min(size);
+            let max_y = (a.y.max(b.y).max(c.y).ceil() as usize).min(size);
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let p = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+                    // Barycentric weights of each corner, which are all positive inside the
+                    // triangle whichever way round it is
+                    let weights = Vec3::new(edge(b, c, p), edge(c, a, p), edge(a, b, p)) / area;
+                    if weights.min_element() < 0.0 {
+                        continue;
+                    }
+
+                    let z = weights.dot(Vec3::new(a.z, b.z, c.z));
+                    let i = y * size + x;
+                    if z <= depth[i] {
+                        continue;
+                    }
+
+                    let [va, vb, vc] = tri.map(|i| quad[i]);
+                    let interpolate = |f: &dyn Fn(&FaceVertex) -> f32| {
+                        weights.dot(Vec3::new(f(&va), f(&vb), f(&vc)))
+                    };
+                    let uv = Vec2::new(
+                        interpolate(&|v| v.tex_coords[0]),
+                        interpolate(&|v| v.tex_coords[1]),
+                    );
+                    let shade = interpolate(&|v| v.shade);
+                    let tint = Vec3::new(
+                        interpolate(&|v| v.tint[0]),
+                        interpolate(&|v| v.tint[1]),
+                        interpolate(&|v| v.tint[2]),
+                    );
+
+                    let texel = tex
+                        .map(|t| sample(t, uv))
+                        .unwrap_or(Rgba([255, 0, 255, 255]));
+                    let alpha = texel[3] as f32 / 255.0;
+                    let colour =
+                        Vec3::new(texel[0] as f32, texel[1] as f32, texel[2] as f32) * tint * shade;
+
+                    let pixel = icon.get_pixel_mut(x as u32, y as u32);
+                    match layer {
+                        RenderLayer::Opaque => {
+                            *pixel = Rgba([colour.x as u8, colour.y as u8, colour.z as u8, 255]);
+                            depth[i] = z;
+                        }
+                        RenderLayer::Cutout => {
+                            if alpha < 0.5 {
+                                continue;
+                            }
+                            *pixel = Rgba([colour.x as u8, colour.y as u8, colour.z as u8, 255]);
+                            depth[i] = z;
+                        }
+                        RenderLayer::Translucent => {
+                            let below =
+                                Vec3::new(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+                            let blended = colour * alpha + below * (1.0 - alpha);
+                            let below_alpha = pixel[3] as f32 / 255.0;
+                            let out_alpha = alpha + below_alpha * (1.0 - alpha);
+                            *pixel = Rgba([
+                                blended.x as u8,
+                                blended.y as u8,
+                                blended.z as u8,
+                                (out_alpha * 255.0) as u8,
+                            ]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    icon
+}
+
+/// Twice the signed area of the triangle abc, looking only at x and y
+fn edge(a: Vec3, b: Vec3, c: Vec3) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+/// Gets the nearest pixel of a texture. Texture coordinates have v going up the texture, since
+/// that's how textures are uploaded.
+fn sample(texture: &RgbaImage, uv: Vec2) -> Rgba<u8> {
+    let (width, height) = texture.dimensions();
+    let x = ((uv.x * width as f32) as u32).min(width - 1);
+    let y = (((1.0 - uv.y) * height as f32) as u32).min(height - 1);
+    *texture.get_pixel(x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square facing the viewer at depth z, covering the whole icon when it's 1 wide
+    fn square(z: f32, width: f32, layer: f32, shade: f32) -> [FaceVertex; 4] {
+        let lo = 0.5 - width / 2.0;
+        let hi = 0.5 + width / 2.0;
+        let corners = [[lo, lo], [hi, lo], [hi, hi], [lo, hi]];
+        corners.map(|[x, y]| FaceVertex {
+            position: [x, y, z + 0.5],
+            tex_coords: [(x - lo) / width, (y - lo) / width, layer],
+            tint: [1.0, 1.0, 1.0],
+            shade,
+        })
+    }
+
+    #[test]
+    fn nearer_faces_are_drawn_over_further_ones() {
+        let red = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 255, 255]));
+        let texture = |layer| match layer {
+            1 => Some(&red),
+            2 => Some(&blue),
+            _ => None,
+        };
+
+        // The blue square is in front but drawn first
+        let quads = [
+            (RenderLayer::Opaque, square(0.1, 0.5, 2.0, 1.0)),
+            (RenderLayer::Opaque, square(0.0, 1.0, 1.0, 0.5)),
+        ];
+        let icon = rasterise(&quads, Mat4::IDENTITY, texture);
+
+        assert_eq!(
+            *icon.get_pixel(ICON_SIZE / 2, ICON_SIZE / 2),
+            Rgba([0, 0, 255, 255])
+        );
+        // Shaded by half
+        assert_eq!(*icon.get_pixel(1, 1), Rgba([127, 0, 0, 255]));
+    }
+
+    #[test]
+    fn textures_are_mapped_the_right_way_up() {
+        // Top row white, bottom row black
+        let mut texture = RgbaImage::from_pixel(1, 2, Rgba([0, 0, 0, 255]));
+        texture.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+
+        // Texture coordinates have v going up, like they do on the GPU
+        let icon = rasterise(
+            &[(RenderLayer::Opaque, square(0.0, 1.0, 1.0, 1.0))],
+            Mat4::IDENTITY,
+            |_| Some(&texture),
+        );
+        assert_eq!(icon.get_pixel(ICON_SIZE / 2, 1)[0], 255);
+        assert_eq!(icon.get_pixel(ICON_SIZE / 2, ICON_SIZE - 2)[0], 0);
+    }
+
+    #[test]
+    fn cutout_pixels_are_left_clear() {
+        let clear = RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 0]));
+        let icon = rasterise(
+            &[(RenderLayer::Cutout, square(0.0, 1.0, 1.0, 1.0))],
+            Mat4::IDENTITY,
+            |_| Some(&clear),
+        );
+        assert!(icon.pixels().all(|p| p[3] == 0));
+    }
+
+    #[test]
+    fn default_transform_fits_a_block() {
+        // Corners of a block stay inside the icon once it's turned to face the viewer
+        let transform = default_gui_transform();
+        for x in [-0.5, 0.5] {
+            for y in [-0.5, 0.5] {
+                for z in [-0.5, 0.5] {
+                    let pos = transform.transform_point3(Vec3::new(x, y, z));
+                    assert!(pos.x.abs() < 0.5 && pos.y.abs() < 0.5, "{:?}", pos);
+                }
+            }
+        }
+    }
+}
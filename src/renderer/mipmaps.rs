@@ -0,0 +1,141 @@
+use image::{Rgba, RgbaImage};
+
+use crate::resources::RenderLayer;
+
+/// How many mipmap levels block textures get unless the settings say otherwise, like the game
+pub const DEFAULT_MIPMAP_LEVELS: u32 = 4;
+
+/// The most mipmap levels a square texture of this size can have, past which they'd be smaller
+/// than a pixel
+pub fn max_levels(size: u32) -> u32 {
+    if size == 0 {
+        0
+    } else {
+        31 - size.leading_zeros()
+    }
+}
+
+/// Makes the full set of images for a mipmapped texture, starting with the texture itself and
+/// then each level being half the size of the one before. Cutout textures stay fully opaque or
+/// fully clear at every level so they still work with the alpha cutoff.
+pub fn generate_mipmaps(img: &RgbaImage, layer: RenderLayer, levels: u32) -> Vec<RgbaImage> {
+    let levels = levels.min(max_levels(img.width().max(img.height())));
+
+    let mut mipmaps = vec![img.clone()];
+    for _ in 0..levels {
+        let next = downsample(mipmaps.last().unwrap(), layer);
+        mipmaps.push(next);
+    }
+    mipmaps
+}
+
+/// Halves the size of an image, with each pixel being the average of the four it covers.
+/// Colours are averaged by how opaque they are so the colour of clear pixels, which is usually
+/// black, doesn't bleed into the edges of the rest of the texture.
+fn downsample(img: &RgbaImage, layer: RenderLayer) -> RgbaImage {
+    let (width, height) = img.dimensions();
+
+    RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let mut colour = [0.0; 3];
+        let mut alpha = 0.0;
+        let mut opaque = 0;
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let pixel = img.get_pixel((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1));
+            let a = pixel[3] as f32 / 255.0;
+            for (c, channel) in colour.iter_mut().enumerate() {
+                *channel += srgb_to_linear(pixel[c]) * a;
+            }
+            alpha += a;
+            if pixel[3] >= 128 {
+                opaque += 1;
+            }
+        }
+
+        if alpha == 0.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let alpha_out = match layer {
+            // Half or more of the pixels have to be solid for the smaller one to be, otherwise
+            // thin parts like leaves and flowers would thicken or vanish at a distance
+            RenderLayer::Cutout => {
+                if opaque >= 2 {
+                    255
+                } else {
+                    0
+                }
+            }
+            _ => (alpha / 4.0 * 255.0).round() as u8,
+        };
+        Rgba([
+            linear_to_srgb(colour[0] / alpha),
+            linear_to_srgb(colour[1] / alpha),
+            linear_to_srgb(colour[2] / alpha),
+            alpha_out,
+        ])
+    })
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    (c as f32 / 255.0).powf(2.2)
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    (c.powf(1.0 / 2.2) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A texture with the left half solid red and the right half clear
+    fn half_clear(size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, _| {
+            if x < size / 2 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    #[test]
+    fn levels_halve_in_size() {
+        let mipmaps = generate_mipmaps(&half_clear(64), RenderLayer::Opaque, 4);
+        let sizes: Vec<_> = mipmaps.iter().map(|m| m.width()).collect();
+        assert_eq!(sizes, vec![64, 32, 16, 8, 4]);
+    }
+
+    #[test]
+    fn levels_stop_at_one_pixel() {
+        let mipmaps = generate_mipmaps(&half_clear(16), RenderLayer::Opaque, 10);
+        assert_eq!(mipmaps.len(), 5);
+        assert_eq!(mipmaps.last().unwrap().dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn cutout_alpha_stays_binary() {
+        let img = RgbaImage::from_fn(16, 16, |x, y| {
+            if (x + y * 3) % 5 < 2 {
+                Rgba([40, 200, 40, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        for mipmap in generate_mipmaps(&img, RenderLayer::Cutout, 4) {
+            assert!(mipmap.pixels().all(|p| p[3] == 0 || p[3] == 255));
+        }
+    }
+
+    #[test]
+    fn clear_pixels_dont_darken_colour() {
+        let img = RgbaImage::from_fn(2, 2, |x, _| {
+            if x == 0 {
+                Rgba([200, 100, 50, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let mipmaps = generate_mipmaps(&img, RenderLayer::Translucent, 1);
+        assert_eq!(mipmaps[1].get_pixel(0, 0), &Rgba([200, 100, 50, 128]));
+    }
+}
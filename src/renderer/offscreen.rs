@@ -0,0 +1,216 @@
+use std::path::PathBuf;
+
+use glium::{
+    backend::Facade,
+    framebuffer::{DepthRenderBuffer, SimpleFrameBuffer},
+    texture::{DepthFormat, MipmapsOption, RawImage2d, SrgbFormat, SrgbTexture2d},
+    Display,
+};
+use image::RgbaImage;
+
+use crate::{settings::Settings, world::World};
+
+use super::{camera::Camera, sky::SkyState, Renderer};
+
+/// Folder screenshots are saved in
+const SCREENSHOT_DIR: &str = "screenshots";
+
+/// Saves whatever was last drawn to the window as a png named after the current time. The image
+/// is written on another thread so the game doesn't stutter.
+pub fn save_screenshot(dis: &Display) {
+    let raw: RawImage2d<u8> = match dis.read_front_buffer() {
+        Ok(raw) => raw,
+        Err(e) => {
+            log::error!("Couldn't read the screen for a screenshot: {:?}", e);
+            return;
+        }
+    };
+
+    let path = screenshot_path();
+    std::thread::spawn(move || {
+        let img = match image_from_raw(raw) {
+            Some(img) => img,
+            None => {
+                log::error!("Screenshot had the wrong number of pixels");
+                return;
+            }
+        };
+
+        let result = std::fs::create_dir_all(SCREENSHOT_DIR)
+            .map_err(|e| e.to_string())
+            .and_then(|_| img.save(&path).map_err(|e| e.to_string()));
+        match result {
+            Ok(_) => log::info!("Saved screenshot as {}", path.display()),
+            Err(e) => log::error!("Couldn't save screenshot {}: {}", path.display(), e),
+        }
+    });
+}
+
+/// A file name for a screenshot taken now, which doesn't clash with any already saved
+fn screenshot_path() -> PathBuf {
+    let time = chrono::Local::now().format("%Y-%m-%d_%H.%M.%S");
+    let mut path = PathBuf::from(SCREENSHOT_DIR).join(format!("{}.png", time));
+    let mut n = 1;
+    while path.exists() {
+        path = PathBuf::from(SCREENSHOT_DIR).join(format!("{}_{}.png", time, n));
+        n += 1;
+    }
+    path
+}
+
+/// Turns pixels read back from OpenGL, which start at the bottom row, into an image
+fn image_from_raw(raw: RawImage2d<u8>) -> Option<RgbaImage> {
+    let img = RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())?;
+    Some(image::imageops::flip_vertical(&img))
+}
+
+/// Draws the blocks of a world from a camera into an image instead of the window. Any meshes
+/// that haven't been built yet are built first. This works with any OpenGL context, including a
+/// headless one using a software driver, so is used for render tests.
+pub fn render_world_to_image(
+    facade: &impl Facade,
+    rend: &mut Renderer,
+    world: &mut World,
+    mut cam: Camera,
+    sky: &SkyState,
+    settings: &Settings,
+    size: (u32, u32),
+) -> RgbaImage {
+    cam.set_window_size(size);
+    world.generate_all_meshes(facade, &cam);
+
+    let colour = SrgbTexture2d::empty_with_format(
+        facade,
+        SrgbFormat::U8U8U8U8,
+        MipmapsOption::NoMipmap,
+        size.0,
+        size.1,
+    )
+    .unwrap();
+    let depth = DepthRenderBuffer::new(facade, DepthFormat::F32, size.0, size.1).unwrap();
+    let mut target = SimpleFrameBuffer::with_depth_buffer(facade, &colour, &depth).unwrap();
+
+    let old_cam = std::mem::replace(&mut rend.cam, cam);
+    rend.render_world(&mut target, world, sky, settings);
+    rend.cam = old_cam;
+
+    image_from_raw(colour.read()).expect("Rendered image had the wrong number of pixels")
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{IVec2, IVec3, Vec3};
+    use glium::{
+        glutin::{
+            dpi::PhysicalSize, event_loop::EventLoop, platform::unix::EventLoopExtUnix,
+            ContextBuilder,
+        },
+        HeadlessRenderer,
+    };
+
+    use super::*;
+    use crate::world::chunks::{block_index_to_pos, Chunk, ChunkArray, ChunkSection};
+
+    const SIZE: (u32, u32) = (256, 256);
+
+    // Block state ids from the 1.16.3 block list
+    const STONE: u16 = 1;
+    const GRASS_BLOCK: u16 = 9;
+    const DIRT: u16 = 10;
+    const BEDROCK: u16 = 33;
+    const WATER: u16 = 34;
+
+    /// An OpenGL context without a window. The event loop has to live as long as the context.
+    struct Headless {
+        facade: HeadlessRenderer,
+        _event_loop: EventLoop<()>,
+    }
+
+    impl Headless {
+        fn new() -> Headless {
+            let event_loop = EventLoop::new_any_thread();
+            let context = ContextBuilder::new()
+                .build_headless(&event_loop, PhysicalSize::new(SIZE.0, SIZE.1))
+                .expect("Couldn't create a headless OpenGL context");
+            Headless {
+                facade: HeadlessRenderer::new(context).unwrap(),
+                _event_loop: event_loop,
+            }
+        }
+    }
+
+    /// A world with one chunk of blocks in the middle, surrounded by empty chunks so its mesh
+    /// gets built
+    fn world_with(block: impl Fn(IVec3) -> u16) -> World {
+        let mut world = World::new();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                world.insert_chunk(Chunk::empty(IVec2::new(x, z)));
+            }
+        }
+
+        let mut blocks: ChunkArray = [0; 4096];
+        for (i, b) in blocks.iter_mut().enumerate() {
+            *b = block(block_index_to_pos(i));
+        }
+        if let Some(chunk) = world.get_chunk_mut(&IVec2::ZERO) {
+            chunk.put_section(ChunkSection::new(0, blocks));
+        }
+        world.queue_chunk_mesh(IVec2::ZERO);
+
+        world
+    }
+
+    fn render(world: &mut World, pos: Vec3, rot: Vec3) -> RgbaImage {
+        let headless = Headless::new();
+        let mut rend = Renderer::new(&headless.facade);
+        rend.load_textures(&headless.facade);
+
+        let settings = Settings::default();
+        let sky = SkyState::at(6000, 0, Vec3::from(settings.day_colour));
+        let cam = Camera::new_with_values(SIZE, pos, rot, 70.0);
+        render_world_to_image(
+            &headless.facade,
+            &mut rend,
+            world,
+            cam,
+            &sky,
+            &settings,
+            SIZE,
+        )
+    }
+
+    // This needs the game's assets and an OpenGL driver, so run it with
+    // `LIBGL_ALWAYS_SOFTWARE=1 cargo test -- --ignored` to use Mesa's software renderer.
+
+    #[test]
+    #[ignore]
+    fn renders_terrain() {
+        let mut world = world_with(|pos| {
+            let height = 6 + (pos.x + pos.z) / 6;
+            match pos.y {
+                0 => BEDROCK,
+                y if y < height - 3 => STONE,
+                y if y < height => DIRT,
+                y if y == height => GRASS_BLOCK,
+                y if y <= 8 && pos.x < 4 => WATER,
+                _ => 0,
+            }
+        });
+        let (pos, rot) = (Vec3::new(-6.0, 20.0, -6.0), Vec3::new(35.0, 135.0, 0.0));
+        let img = render(&mut world, pos, rot);
+        let empty = render(&mut world_with(|_| 0), pos, rot);
+
+        // Compared with just the sky, a good part of the view should be covered by terrain
+        let terrain = img
+            .pixels()
+            .zip(empty.pixels())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert!(
+            terrain > (img.width() * img.height() / 4) as usize,
+            "only {} pixels have terrain",
+            terrain
+        );
+    }
+}
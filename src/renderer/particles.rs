@@ -0,0 +1,184 @@
+use glam::Vec2;
+use glium::{
+    backend::Facade,
+    draw_parameters::{Depth, DepthTest},
+    index::{NoIndices, PrimitiveType},
+    texture::{RawImage2d, SrgbTexture2dArray},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior},
+    DrawParameters, Program, Surface, VertexBuffer,
+};
+use image::imageops::FilterType;
+
+use crate::{
+    particles::{ParticleTexture, Particles, PARTICLE_TEXTURES},
+    resources::packs,
+    settings::Settings,
+};
+
+use super::{camera::Camera, shader, sky::SkyState};
+
+/// Particle textures are all scaled to this size so they fit in one texture array
+const PARTICLE_TEXTURE_SIZE: u32 = 16;
+
+/// A corner of the square every particle is drawn with
+#[derive(Debug, Copy, Clone)]
+pub struct ParticleCorner {
+    pub corner: [f32; 2],
+}
+implement_vertex!(ParticleCorner, corner);
+
+/// Everything about a single particle needed to draw it
+#[derive(Debug, Copy, Clone)]
+pub struct ParticleInstance {
+    pub centre: [f32; 3],
+    pub size: f32,
+    pub colour: [f32; 4],
+    pub layer: f32,
+    /// Offset then scale of the part of the texture to use
+    pub uv_rect: [f32; 4],
+    pub emissive: f32,
+}
+implement_vertex!(
+    ParticleInstance,
+    centre,
+    size,
+    colour,
+    layer,
+    uv_rect,
+    emissive
+);
+
+/// Draws particles as squares facing the camera, all in one go with instancing
+pub struct ParticleRenderer {
+    prog: Program,
+    quad: VertexBuffer<ParticleCorner>,
+    textures: SrgbTexture2dArray,
+}
+
+impl ParticleRenderer {
+    pub fn new(dis: &impl Facade) -> ParticleRenderer {
+        let prog = shader::compile_shaders(
+            dis,
+            include_bytes!("../../shaders/particles/v.glsl"),
+            include_bytes!("../../shaders/particles/f.glsl"),
+        )
+        .expect("Failed to compile shaders");
+
+        let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+        let quad: Vec<_> = [0, 1, 2, 0, 2, 3]
+            .iter()
+            .map(|&i| ParticleCorner { corner: corners[i] })
+            .collect();
+
+        ParticleRenderer {
+            prog,
+            quad: VertexBuffer::new(dis, &quad).unwrap(),
+            textures: SrgbTexture2dArray::empty(dis, 1, 1, 1).unwrap(),
+        }
+    }
+
+    pub fn load_textures(&mut self, dis: &impl Facade) {
+        let size = PARTICLE_TEXTURE_SIZE;
+        let textures = PARTICLE_TEXTURES
+            .iter()
+            .map(|name| {
+                let path = format!("textures/particle/{}.png", name);
+                let img = match packs::load_image(&path) {
+                    Ok(img) => img,
+                    Err(e) => {
+                        log::error!("Couldn't load particle texture {}: {:?}", path, e);
+                        image::RgbaImage::new(size, size)
+                    }
+                };
+                let img = image::imageops::resize(&img, size, size, FilterType::Nearest);
+                RawImage2d::from_raw_rgba_reversed(img.as_raw(), (size, size))
+            })
+            .collect();
+        self.textures = SrgbTexture2dArray::new(dis, textures).unwrap();
+    }
+
+    pub fn render(
+        &self,
+        dis: &impl Facade,
+        target: &mut impl Surface,
+        cam: &Camera,
+        particles: &Particles,
+        block_textures: &SrgbTexture2dArray,
+        sky: &SkyState,
+        settings: &Settings,
+    ) {
+        // Block dust and the rest use different textures so are drawn separately
+        let mut block_instances = Vec::new();
+        let mut sprite_instances = Vec::new();
+        let partial_tick = particles.get_partial_tick();
+        for particle in particles.get_particles() {
+            let (uv_offset, uv_scale) = match particle.texture {
+                ParticleTexture::Block { uv_offset, .. } => (uv_offset, Vec2::splat(0.25)),
+                _ => (Vec2::ZERO, Vec2::ONE),
+            };
+            let instance = ParticleInstance {
+                centre: particle.get_render_pos(partial_tick).to_array(),
+                size: particle.get_render_size(partial_tick),
+                colour: particle.colour,
+                layer: particle.get_frame() as f32,
+                uv_rect: [uv_offset.x, uv_offset.y, uv_scale.x, uv_scale.y],
+                emissive: if particle.emissive { 1.0 } else { 0.0 },
+            };
+            match particle.texture {
+                ParticleTexture::Block { .. } => block_instances.push(instance),
+                _ => sprite_instances.push(instance),
+            }
+        }
+
+        let params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let behaviour = SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            ..Default::default()
+        };
+
+        // Particles always face the camera, so they're spread out along its right and up
+        let vmat = cam.get_vmat();
+        let pvmat = cam.get_pvmat().to_cols_array_2d();
+        let fog_col = sky.fog_colour;
+
+        let batches = [
+            (block_instances, block_textures),
+            (sprite_instances, &self.textures),
+        ];
+        for (instances, textures) in batches {
+            if instances.is_empty() {
+                continue;
+            }
+            let instances = VertexBuffer::new(dis, &instances).unwrap();
+
+            let uniforms = uniform! {
+                pvmat: pvmat,
+                camRight: vmat.row(0).truncate().to_array(),
+                camUp: vmat.row(1).truncate().to_array(),
+                camPos: cam.get_pos().to_array(),
+                textures: Sampler(textures, behaviour),
+                fogCol: [fog_col.x, fog_col.y, fog_col.z, 1.0],
+                fogNear: settings.fog_near,
+                fogFar: settings.fog_far,
+                daylight: sky.get_terrain_brightness(),
+            };
+            target
+                .draw(
+                    (&self.quad, instances.per_instance().unwrap()),
+                    NoIndices(PrimitiveType::TrianglesList),
+                    &self.prog,
+                    &uniforms,
+                    &params,
+                )
+                .expect("Error rendering particles");
+        }
+    }
+}
@@ -1,9 +1,9 @@
 use std::string::FromUtf8Error;
 
-use glium::{Display, Program};
+use glium::{backend::Facade, Program};
 
 pub fn compile_shaders(
-    d: &Display,
+    d: &impl Facade,
     v_bytes: &[u8],
     f_bytes: &[u8],
 ) -> Result<Program, FromUtf8Error> {
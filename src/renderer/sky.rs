@@ -0,0 +1,384 @@
+use std::f32::consts::{PI, TAU};
+
+use glam::{Mat4, Vec3, Vec4};
+use glium::{
+    backend::Facade,
+    draw_parameters::{Blend, BlendingFunction, LinearBlendingFactor},
+    index::{NoIndices, PrimitiveType},
+    texture::{RawImage2d, SrgbTexture2d},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior},
+    DrawParameters, IndexBuffer, Program, Surface, VertexBuffer,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::resources::packs;
+
+use super::{camera::Camera, shader, Vertex};
+
+/// How far away the sky is drawn, it just has to be closer than the far plane
+const SKY_RADIUS: f32 = 100.0;
+const SUN_SIZE: f32 = 30.0;
+const MOON_SIZE: f32 = 20.0;
+const STAR_COUNT: usize = 1500;
+/// How dark the terrain gets in the middle of the night
+const NIGHT_BRIGHTNESS: f32 = 0.2;
+
+/// Colours and brightness of the sky at a moment in time
+#[derive(Debug, Clone, Copy)]
+pub struct SkyState {
+    /// How far through the day the sun is, from 0 at noon round to 1
+    pub celestial_angle: f32,
+    /// Which of the 8 phases the moon is in, 0 being a full moon
+    pub moon_phase: u32,
+    /// How much the sun lights the world, from 0 at night to 1 in the day
+    pub daylight: f32,
+    /// Colour of the sky straight up
+    pub sky_colour: Vec3,
+    /// Colour of the sky at the horizon, which the fog fades to
+    pub fog_colour: Vec3,
+    /// Colour of the glow around the horizon at sunrise and sunset, with its strength as the alpha
+    pub sunrise_colour: Option<Vec4>,
+    pub star_brightness: f32,
+}
+
+impl SkyState {
+    /// Works out how the sky looks at a time of day and on a day, given by the total ticks the
+    /// world has existed for. `day_colour` is the colour of the sky at noon.
+    pub fn at(day_time: i64, world_time: i64, day_colour: Vec3) -> SkyState {
+        let celestial_angle = celestial_angle(day_time);
+        let cos = (celestial_angle * TAU).cos();
+
+        let daylight = (cos * 2.0 + 0.5).clamp(0.0, 1.0);
+        let fog_colour = (day_colour * 0.5 + Vec3::splat(0.5)) * (daylight * 0.94 + 0.06);
+
+        // The glow is strongest just as the sun crosses the horizon
+        let sunrise_colour = if (-0.4..=0.4).contains(&cos) {
+            let g = cos / 0.4 * 0.5 + 0.5;
+            let alpha = 1.0 - (1.0 - (g * PI).sin()) * 0.99;
+            Some(Vec4::new(
+                g * 0.3 + 0.7,
+                g * g * 0.7 + 0.2,
+                0.2,
+                alpha * alpha,
+            ))
+        } else {
+            None
+        };
+
+        let star_brightness = 1.0 - (cos * 2.0 + 0.25).clamp(0.0, 1.0);
+
+        SkyState {
+            celestial_angle,
+            moon_phase: (world_time.div_euclid(24_000) % 8) as u32,
+            daylight,
+            sky_colour: day_colour * daylight,
+            fog_colour,
+            sunrise_colour,
+            star_brightness: star_brightness * star_brightness * 0.5,
+        }
+    }
+
+    /// How bright terrain should be drawn, which never goes fully dark
+    pub fn get_terrain_brightness(&self) -> f32 {
+        NIGHT_BRIGHTNESS + (1.0 - NIGHT_BRIGHTNESS) * self.daylight
+    }
+
+    /// Rotation of the sun, moon and stars around the world. The sun starts straight up and
+    /// moves from east to west.
+    pub fn get_celestial_rotation(&self) -> Mat4 {
+        Mat4::from_rotation_y(-PI / 2.0) * Mat4::from_rotation_x(self.celestial_angle * TAU)
+    }
+
+    pub fn get_sun_direction(&self) -> Vec3 {
+        self.get_celestial_rotation().transform_vector3(Vec3::Y)
+    }
+}
+
+/// How far round the sky the sun is at a time of day, from 0 at noon round to 1. The sun moves a
+/// little faster around sunrise and sunset like in the game. A negative time is the same as a
+/// positive one, the server just sends it that way when the daylight cycle is stopped.
+pub fn celestial_angle(day_time: i64) -> f32 {
+    let d = (day_time.abs().rem_euclid(24_000) as f32 / 24_000.0 - 0.25).rem_euclid(1.0);
+    let e = 0.5 - (d * PI).cos() / 2.0;
+    (d * 2.0 + e) / 3.0
+}
+
+/// A corner of the sun, moon or a star
+#[derive(Debug, Copy, Clone)]
+pub struct SkyVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+}
+implement_vertex!(SkyVertex, position, tex_coords);
+
+/// Draws the sky behind everything else
+pub struct Sky {
+    dome_prog: Program,
+    dome: VertexBuffer<Vertex>,
+    dome_indices: IndexBuffer<u16>,
+
+    celestial_prog: Program,
+    sun: VertexBuffer<SkyVertex>,
+    moon: VertexBuffer<SkyVertex>,
+    stars: VertexBuffer<SkyVertex>,
+
+    sun_texture: SrgbTexture2d,
+    /// All 8 phases of the moon, in 4 columns and 2 rows
+    moon_texture: SrgbTexture2d,
+    /// Plain white, for stars
+    white: SrgbTexture2d,
+}
+
+impl Sky {
+    pub fn new(dis: &impl Facade) -> Sky {
+        let dome_prog = shader::compile_shaders(
+            dis,
+            include_bytes!("../../shaders/sky/v.glsl"),
+            include_bytes!("../../shaders/sky/f.glsl"),
+        )
+        .expect("Failed to compile shaders");
+        let celestial_prog = shader::compile_shaders(
+            dis,
+            include_bytes!("../../shaders/celestial/v.glsl"),
+            include_bytes!("../../shaders/celestial/f.glsl"),
+        )
+        .expect("Failed to compile shaders");
+
+        let (dome, dome_indices) = dome_mesh(16, 32);
+        let white = RawImage2d::from_raw_rgba(vec![255u8; 4], (1, 1));
+
+        Sky {
+            dome_prog,
+            dome: VertexBuffer::new(dis, &dome).unwrap(),
+            dome_indices: IndexBuffer::new(dis, PrimitiveType::TrianglesList, &dome_indices)
+                .unwrap(),
+
+            celestial_prog,
+            sun: VertexBuffer::new(dis, &celestial_quad(Vec3::Y, SUN_SIZE)).unwrap(),
+            moon: VertexBuffer::new(dis, &celestial_quad(-Vec3::Y, MOON_SIZE)).unwrap(),
+            stars: VertexBuffer::new(dis, &star_mesh()).unwrap(),
+
+            sun_texture: SrgbTexture2d::empty(dis, 1, 1).unwrap(),
+            moon_texture: SrgbTexture2d::empty(dis, 1, 1).unwrap(),
+            white: SrgbTexture2d::new(dis, white).unwrap(),
+        }
+    }
+
+    pub fn load_textures(&mut self, dis: &impl Facade) {
+        self.sun_texture = load_texture(dis, "textures/environment/sun.png");
+        self.moon_texture = load_texture(dis, "textures/environment/moon_phases.png");
+    }
+
+    pub fn render(&self, target: &mut impl Surface, cam: &Camera, state: &SkyState) {
+        let additive = DrawParameters {
+            blend: Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::SourceAlpha,
+                    destination: LinearBlendingFactor::One,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::One,
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            ..Default::default()
+        };
+        let behaviour = SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            ..Default::default()
+        };
+
+        // The sky is centred on the camera so it never gets any closer
+        let pvmat = cam.get_pvmat().to_cols_array_2d();
+        let centre = Mat4::from_translation(*cam.get_pos());
+
+        // The sunrise glow is on the side of the horizon the sun is on
+        let sun_dir = state.get_sun_direction();
+        let sunrise = state.sunrise_colour.unwrap_or(Vec4::ZERO);
+        let dome_uniforms = uniform! {
+            pvmat: pvmat,
+            tmat: (centre * Mat4::from_scale(Vec3::splat(SKY_RADIUS))).to_cols_array_2d(),
+            skyCol: state.sky_colour.to_array(),
+            fogCol: state.fog_colour.to_array(),
+            sunriseCol: sunrise.to_array(),
+            sunriseDir: Vec3::new(sun_dir.x.signum(), 0.0, 0.0).to_array(),
+        };
+        target
+            .draw(
+                &self.dome,
+                &self.dome_indices,
+                &self.dome_prog,
+                &dome_uniforms,
+                &Default::default(),
+            )
+            .expect("Error rendering sky");
+
+        let tmat = (centre * state.get_celestial_rotation()).to_cols_array_2d();
+        let inds = NoIndices(PrimitiveType::TrianglesList);
+
+        let star_uniforms = uniform! {
+            pvmat: pvmat,
+            tmat: tmat,
+            tex: Sampler(&self.white, behaviour),
+            uvOffset: [0.0f32, 0.0],
+            uvScale: [1.0f32, 1.0],
+            tint: [1.0, 1.0, 1.0, state.star_brightness],
+        };
+        if state.star_brightness > 0.0 {
+            target
+                .draw(
+                    &self.stars,
+                    inds,
+                    &self.celestial_prog,
+                    &star_uniforms,
+                    &additive,
+                )
+                .expect("Error rendering stars");
+        }
+
+        let sun_uniforms = uniform! {
+            pvmat: pvmat,
+            tmat: tmat,
+            tex: Sampler(&self.sun_texture, behaviour),
+            uvOffset: [0.0f32, 0.0],
+            uvScale: [1.0f32, 1.0],
+            tint: [1.0f32, 1.0, 1.0, 1.0],
+        };
+        target
+            .draw(
+                &self.sun,
+                inds,
+                &self.celestial_prog,
+                &sun_uniforms,
+                &additive,
+            )
+            .expect("Error rendering sun");
+
+        // Phases go along the top row of the texture then the bottom, which is the first row
+        // since textures are stored upside down
+        let column = state.moon_phase % 4;
+        let row = state.moon_phase / 4;
+        let moon_uniforms = uniform! {
+            pvmat: pvmat,
+            tmat: tmat,
+            tex: Sampler(&self.moon_texture, behaviour),
+            uvOffset: [column as f32 / 4.0, (1 - row) as f32 / 2.0],
+            uvScale: [0.25f32, 0.5],
+            tint: [1.0f32, 1.0, 1.0, 1.0],
+        };
+        target
+            .draw(
+                &self.moon,
+                inds,
+                &self.celestial_prog,
+                &moon_uniforms,
+                &additive,
+            )
+            .expect("Error rendering moon");
+    }
+}
+
+fn load_texture(dis: &impl Facade, path: &str) -> SrgbTexture2d {
+    let img = match packs::load_image(path) {
+        Ok(img) => img,
+        Err(e) => {
+            log::error!("Couldn't load sky texture {}: {:?}", path, e);
+            image::RgbaImage::new(1, 1)
+        }
+    };
+    let dims = img.dimensions();
+    SrgbTexture2d::new(dis, RawImage2d::from_raw_rgba_reversed(img.as_raw(), dims)).unwrap()
+}
+
+/// A sphere of radius 1 split into `rings` from top to bottom and `segments` around
+fn dome_mesh(rings: u16, segments: u16) -> (Vec<Vertex>, Vec<u16>) {
+    let mut verts = Vec::new();
+    for ring in 0..=rings {
+        let theta = ring as f32 / rings as f32 * PI;
+        for segment in 0..=segments {
+            let phi = segment as f32 / segments as f32 * TAU;
+            verts.push(Vertex {
+                position: [
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                ],
+            });
+        }
+    }
+
+    let mut indices = Vec::new();
+    let stride = segments + 1;
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let i = ring * stride + segment;
+            indices.extend_from_slice(&[i, i + stride, i + 1, i + 1, i + stride, i + stride + 1]);
+        }
+    }
+
+    (verts, indices)
+}
+
+/// A square `size` wide facing in towards the centre of the sky from the direction `dir`, which
+/// should be straight up or down
+fn celestial_quad(dir: Vec3, size: f32) -> Vec<SkyVertex> {
+    let centre = dir * SKY_RADIUS;
+    let half = size / 2.0;
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let verts: Vec<_> = corners
+        .iter()
+        .map(|&(x, z): &(f32, f32)| SkyVertex {
+            position: (centre + Vec3::new(x * half, 0.0, z * half)).to_array(),
+            tex_coords: [(x + 1.0) / 2.0, (z + 1.0) / 2.0],
+        })
+        .collect();
+
+    [0, 1, 2, 0, 2, 3].iter().map(|&i| verts[i]).collect()
+}
+
+/// Small squares scattered around the sky, always in the same places
+fn star_mesh() -> Vec<SkyVertex> {
+    let mut rng = StdRng::seed_from_u64(10842);
+    let mut verts = Vec::with_capacity(STAR_COUNT * 6);
+
+    while verts.len() < STAR_COUNT * 6 {
+        let dir = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let length = dir.length();
+        if !(0.01..1.0).contains(&length) {
+            continue;
+        }
+        let dir = dir / length;
+        let size = rng.gen_range(0.15..0.25);
+
+        // Two directions across the face of the star, turned by a random amount
+        let side = dir.cross(Vec3::Y).try_normalize().unwrap_or(Vec3::X);
+        let up = side.cross(dir);
+        let spin = rng.gen_range(0.0..TAU);
+        let (a, b) = (
+            (side * spin.cos() + up * spin.sin()) * size,
+            (up * spin.cos() - side * spin.sin()) * size,
+        );
+
+        let centre = dir * SKY_RADIUS;
+        let corners = [
+            centre - a - b,
+            centre + a - b,
+            centre + a + b,
+            centre - a + b,
+        ];
+        for i in [0, 1, 2, 0, 2, 3] {
+            verts.push(SkyVertex {
+                position: corners[i].to_array(),
+                tex_coords: [0.5, 0.5],
+            });
+        }
+    }
+
+    verts
+}
@@ -1,13 +1,26 @@
-use std::{collections::HashMap, io::Cursor};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::Cursor,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+};
 
 use inflector::Inflector;
 use lazy_static::lazy_static;
 use serde_json::{self, Value};
+use simple_error::{bail, require_with};
 
-use self::block_models::BlockModel;
+use self::{block_models::BlockModel, items::Item};
 
+pub mod assets;
 pub mod block_models;
+pub mod blockstates;
+pub mod items;
+pub mod packs;
+pub mod validate;
 
+#[derive(Clone)]
 pub struct Entity {
     pub name: String,
     pub id: u32,
@@ -20,8 +33,63 @@ pub struct Entity {
 pub struct BlockState {
     pub name: String,
     pub id: u32,
-    pub models: Option<Vec<String>>,
+    pub models: Option<Vec<WeightedVariants>>,
     pub collision_shape: Option<u64>,
+    pub tint: Tint,
+    /// The fluid in this block, either because it is one or it's waterlogged
+    pub fluid: Option<FluidState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FluidState {
+    pub fluid: Fluid,
+    /// 0 for a source block, 1-7 as it flows further away, or 8 and above if it's falling
+    pub level: u8,
+}
+
+/// One way of rendering part of a block state: a model rotated in steps of 90 degrees
+#[derive(Debug, Clone)]
+pub struct BlockVariant {
+    pub model: String,
+    pub x: i32,
+    pub y: i32,
+    pub uvlock: bool,
+    pub weight: u32,
+}
+
+/// A list of variants for one part of a block state, one of which is picked (according to their
+/// weights) for each block in the world. Multipart blocks have several of these which are all drawn
+pub type WeightedVariants = Vec<BlockVariant>;
+
+/// Which colour (if any) the tinted faces of a block's model are multiplied by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tint {
+    None,
+    Grass,
+    Foliage,
+    Water,
+    Fixed(u32),
+}
+
+#[derive(Clone)]
+pub struct Biome {
+    pub name: String,
+    pub id: u32,
+    pub temperature: f32,
+    pub downfall: f32,
+    pub water_colour: u32,
+}
+
+#[derive(Default)]
+pub struct Colourmaps {
+    pub grass: image::RgbaImage,
+    pub foliage: image::RgbaImage,
 }
 
 #[derive(Debug)]
@@ -30,214 +98,781 @@ pub struct BlockTexture {
     pub interpolation: bool,
     pub frames: Vec<image::RgbaImage>,
     pub frametime: usize,
+    pub layer: RenderLayer,
+    /// The order frames are shown in if the texture is animated, otherwise empty
+    pub animation: Vec<AnimationFrame>,
+}
+
+impl BlockTexture {
+    pub fn get_ref(&self) -> TextureRef {
+        TextureRef {
+            index: self.index,
+            layer: self.layer,
+        }
+    }
+}
+
+/// What meshes need to know about a block texture, which stays the same while the textures are
+/// loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureRef {
+    /// Which layer of the block texture array it's in
+    pub index: usize,
+    pub layer: RenderLayer,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationFrame {
+    /// Which of the texture's frames to show
+    pub frame: usize,
+    /// How many ticks to show it for
+    pub time: usize,
+}
+
+/// Which pass faces using a texture are drawn in, depending on how transparent the texture is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderLayer {
+    /// Every pixel is solid
+    Opaque,
+    /// Pixels are either solid or completely see-through, like leaves and flowers
+    Cutout,
+    /// Some pixels are partially see-through, like water and stained glass, so need blending
+    Translucent,
 }
 
 pub const PLAYER_INDEX: usize = 106;
 
+pub const DEFAULT_BIOME: u32 = 1;
+const DEFAULT_WATER_COLOUR: u32 = 0x3F76E4;
+
 pub const MISSING_TEXTURE: BlockTexture = BlockTexture {
     index: 0,
     interpolation: false,
     frames: Vec::new(),
     frametime: 0,
+    layer: RenderLayer::Opaque,
+    animation: Vec::new(),
 };
 
 lazy_static! {
+    pub static ref ENTITIES: Reloadable<HashMap<u32, Entity>> =
+        Reloadable::new("entities", load_entities);
+    pub static ref BLOCKS: Reloadable<HashMap<u32, Arc<BlockState>>> =
+        Reloadable::new("blocks", load_blocks);
+    pub static ref BIOMES: Reloadable<HashMap<u32, Biome>> = Reloadable::new("biomes", load_biomes);
+    pub static ref COLOURMAPS: Reloadable<Colourmaps> = Reloadable::new("colourmaps", || {
+        Ok(Colourmaps {
+            grass: load_colourmap("textures/colormap/grass.png", [124, 189, 107]),
+            foliage: load_colourmap("textures/colormap/foliage.png", [72, 181, 24]),
+        })
+    });
+    pub static ref BLOCK_MODELS_RAW: Reloadable<HashMap<String, Value>> =
+        Reloadable::new("block models", load_block_models_raw);
+    pub static ref BLOCK_MODELS_PARSED: Reloadable<HashMap<String, BlockModel>> =
+        Reloadable::new("parsed block models", parse_block_models);
+    pub static ref BLOCK_TEXTURES: Reloadable<HashMap<String, BlockTexture>> =
+        Reloadable::new("block textures", load_block_textures);
+    pub static ref ITEMS: Reloadable<HashMap<u32, Item>> =
+        Reloadable::new("items", items::load_items);
+}
 
-    pub static ref ENTITIES: HashMap<u32, Entity> = {
-        let mut entities = HashMap::new();
-
-        let json: HashMap<String, Value> =
-            serde_json::from_slice(include_bytes!("../assets/entities.min.json"))
-                .expect("Failed to interpret entities.json");
-        for (name, val) in json.iter() {
-            if let Some(id) = val.get("id") {
-                entities.insert(
-                    id.as_u64().unwrap() as u32,
-                    Entity {
-                        name: format_name(name),
-                        id: id.as_u64().unwrap() as u32,
-                        translation_key: val
-                            .get("loot_table")
-                            .unwrap()
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                        width: val.get("width").unwrap().as_f64().unwrap() as f32,
-                        height: val.get("height").unwrap().as_f64().unwrap() as f32,
-                    },
-                );
+/// A resource which can be swapped for a new version while the game is running, for when the
+/// resource packs change. Anything using it holds onto the version it loaded, so meshes being built
+/// on other threads keep working, and old versions are freed once nothing is using them.
+///
+/// If loading fails the game carries on without the resource: the first time it's loaded it's
+/// left empty, and after that the previous version is kept. The error is kept so it can be shown.
+pub struct Reloadable<T> {
+    name: &'static str,
+    current: RwLock<Arc<T>>,
+    loader: fn() -> Result<T, Box<dyn Error>>,
+    error: Mutex<Option<String>>,
+}
+
+impl<T: Default> Reloadable<T> {
+    pub fn new(name: &'static str, loader: fn() -> Result<T, Box<dyn Error>>) -> Reloadable<T> {
+        let (current, error) = match loader() {
+            Ok(current) => (current, None),
+            Err(e) => {
+                log::error!("Couldn't load {}: {}", name, e);
+                (T::default(), Some(e.to_string()))
+            }
+        };
+
+        Reloadable {
+            name,
+            current: RwLock::new(Arc::new(current)),
+            loader,
+            error: Mutex::new(error),
+        }
+    }
+
+    /// The current version of the resource
+    pub fn load(&self) -> Arc<T> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Loads the resource again and uses the new version from now on, unless it can't be loaded
+    pub fn reload(&self) {
+        match (self.loader)() {
+            Ok(new) => {
+                *self.current.write().unwrap() = Arc::new(new);
+                *self.error.lock().unwrap() = None;
+            }
+            Err(e) => {
+                log::error!("Couldn't reload {}: {}", self.name, e);
+                *self.error.lock().unwrap() = Some(e.to_string());
             }
         }
+    }
 
-        entities
-    };
-    pub static ref BLOCKS: HashMap<u32, BlockState> = {
-        let mut blocks = HashMap::new();
-
-        let json: HashMap<String, Value> =
-            serde_json::from_slice(include_bytes!("../assets/blocks.min.json"))
-                .expect("Failed to interpret blocks.json");
-        for (name, val) in json.iter() {
-            let name = format_name(name);
-            for (id, state) in val.get("states").unwrap().as_object().unwrap().iter() {
-                let id = id.parse().unwrap();
-                blocks.insert(
+    /// Why the resource couldn't be loaded the last time it was tried
+    pub fn get_error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+}
+
+/// The versions of the resources chunk meshes are built from. They're loaded once per mesh so
+/// every block in it comes from the same version, even if the resources are reloaded while it's
+/// being built.
+pub struct MeshResources {
+    pub blocks: Arc<HashMap<u32, Arc<BlockState>>>,
+    pub biomes: Arc<HashMap<u32, Biome>>,
+    pub models: Arc<HashMap<String, BlockModel>>,
+    pub textures: Arc<HashMap<String, BlockTexture>>,
+}
+
+impl MeshResources {
+    pub fn load() -> MeshResources {
+        MeshResources {
+            blocks: BLOCKS.load(),
+            biomes: BIOMES.load(),
+            models: BLOCK_MODELS_PARSED.load(),
+            textures: BLOCK_TEXTURES.load(),
+        }
+    }
+}
+
+/// Switches to a different set of resource packs, on top of the game's assets, and loads
+/// everything that comes from them again. Textures have to be uploaded again and chunks remeshed
+/// afterwards to see the changes.
+pub fn reload(resource_packs: &[PathBuf]) {
+    let start = std::time::Instant::now();
+    packs::set_packs(resource_packs);
+
+    // Models have to be parsed after their json is loaded
+    BLOCK_MODELS_RAW.reload();
+    BLOCK_MODELS_PARSED.reload();
+    BLOCK_TEXTURES.reload();
+    BLOCKS.reload();
+    COLOURMAPS.reload();
+    ENTITIES.reload();
+    BIOMES.reload();
+    ITEMS.reload();
+
+    log::info!("Reloading resources took {}ms", start.elapsed().as_millis());
+}
+
+/// Everything that couldn't be loaded, to tell the player about
+pub fn get_load_errors() -> Vec<String> {
+    vec![
+        ENTITIES.get_error(),
+        BLOCKS.get_error(),
+        BIOMES.get_error(),
+        BLOCK_MODELS_RAW.get_error(),
+        BLOCK_MODELS_PARSED.get_error(),
+        BLOCK_TEXTURES.get_error(),
+        ITEMS.get_error(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Gets the object at the top of one of the json files in the assets folder
+fn as_object<'a>(
+    json: &'a Value,
+    name: &str,
+) -> Result<&'a serde_json::Map<String, Value>, Box<dyn Error>> {
+    match json.as_object() {
+        Some(object) => Ok(object),
+        None => bail!("{} isn't a json object", name),
+    }
+}
+
+fn load_entities() -> Result<HashMap<u32, Entity>, Box<dyn Error>> {
+    let mut entities = HashMap::new();
+
+    let json = assets::load_json("entities.min.json")?;
+    for (name, val) in as_object(&json, "entities.min.json")?.iter() {
+        if let Some(id) = val.get("id") {
+            let id = require_with!(id.as_u64(), "Entity {} has an invalid id", name) as u32;
+            let (width, height) = match entity_size(val) {
+                Some(size) => size,
+                None => {
+                    log::error!("Entity {} has no width or height", name);
+                    continue;
+                }
+            };
+            entities.insert(
+                id,
+                Entity {
+                    name: format_name(name),
                     id,
-                    BlockState {
-                        name: name.clone(),
-                        id,
-                        models: {
-                            match state.get("render") {
-                                // Has a single model
-                                Some(serde_json::Value::Object(render)) => {
-                                    if let Some(serde_json::Value::String(model)) = render.get("model") {
-                                        Some(vec![model.clone()])
-                                    } else {
-                                        log::error!("Couldn't find models in block state: {:?}", state);
-                                        None
-                                    }
-                                },
-                                // Has an array of models
-                                Some(serde_json::Value::Array(render)) => {
-                                    let mut vec = Vec::new();
-
-                                    for render in render {
-                                        if let Some(serde_json::Value::String(model)) = render.get("model") {
-                                            vec.push(model.clone());
-                                        } else {
-                                            if let serde_json::Value::Array(render) = render {
-                                                for render in render {
-                                                    if let Some(serde_json::Value::String(model)) = render.get("model") {
-                                                        vec.push(model.clone());
-                                                    } else {
-                                                        log::error!("Couldn't find model in render section 2");
-                                                    }
-                                                }
-                                            } else {
-                                                println!("{:?}", render);
-                                                panic!();
-                                            }
-                                        }
-                                    }
-
-                                    if vec.len() == 0 {
-                                        println!("{:?}", render);
-                                        panic!("No models in render section");
-                                    }
-
-                                    Some(vec)
-                                },
-                                _ => None,
-                            }
-                        },
-                        collision_shape: {
-                            match state.get("collision_shape") {
-                                Some(collision_shape) => collision_shape.as_u64(),
-                                None => None,
-                            }
-                        },
+                    translation_key: val
+                        .get("loot_table")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    width,
+                    height,
+                },
+            );
+        }
+    }
+
+    Ok(entities)
+}
+
+/// The width and height of an entity from its json in the assets
+pub fn entity_size(json: &Value) -> Option<(f32, f32)> {
+    let width = json.get("width")?.as_f64()?;
+    let height = json.get("height")?.as_f64()?;
+    Some((width as f32, height as f32))
+}
+
+fn load_biomes() -> Result<HashMap<u32, Biome>, Box<dyn Error>> {
+    let mut biomes = HashMap::new();
+
+    let json = assets::load_json("biomes.min.json")?;
+    for (name, val) in as_object(&json, "biomes.min.json")?.iter() {
+        if let Some(id) = val.get("id") {
+            let id = require_with!(id.as_u64(), "Biome {} has an invalid id", name) as u32;
+            biomes.insert(
+                id,
+                Biome {
+                    name: format_name(name),
+                    id,
+                    temperature: val
+                        .get("temperature")
+                        .and_then(|t| t.as_f64())
+                        .unwrap_or(0.5) as f32,
+                    downfall: val.get("downfall").and_then(|d| d.as_f64()).unwrap_or(0.5) as f32,
+                    water_colour: val
+                        .get("water_color")
+                        .and_then(|c| c.as_u64())
+                        .map(|c| c as u32)
+                        .unwrap_or(DEFAULT_WATER_COLOUR),
+                },
+            );
+        }
+    }
+
+    Ok(biomes)
+}
+
+fn load_blocks() -> Result<HashMap<u32, Arc<BlockState>>, Box<dyn Error>> {
+    let mut blocks = HashMap::new();
+
+    let json = assets::load_json("blocks.min.json")?;
+    for (name, val) in as_object(&json, "blocks.min.json")?.iter() {
+        let name_raw = name.as_str();
+        let tint = Tint::for_block(name);
+        let name = format_name(name);
+        // Resource packs can change which models a block uses with a blockstate file
+        let blockstate = packs::read_json(&format!(
+            "blockstates/{}.json",
+            name_raw.trim_start_matches("minecraft:")
+        ));
+        let states = require_with!(
+            val.get("states").and_then(|s| s.as_object()),
+            "Block {} has no states",
+            name_raw
+        );
+        for (id, state) in states.iter() {
+            let id = match id.parse() {
+                Ok(id) => id,
+                Err(_) => bail!("Block {} has an invalid state id {}", name_raw, id),
+            };
+            blocks.insert(
+                id,
+                Arc::new(BlockState {
+                    name: name.clone(),
+                    id,
+                    models: match &blockstate {
+                        Some(blockstate) => {
+                            blockstates::parse_blockstate(blockstate, state.get("properties"))
+                        }
+                        None => parse_render(state.get("render")),
                     },
-                );
+                    collision_shape: {
+                        match state.get("collision_shape") {
+                            Some(collision_shape) => collision_shape.as_u64(),
+                            None => None,
+                        }
+                    },
+                    tint,
+                    fluid: FluidState::parse(name_raw, state.get("properties")),
+                }),
+            );
+        }
+    }
+
+    Ok(blocks)
+}
+
+/// Loads the json of every block model, with any models from resource packs replacing the game's
+/// ones
+fn load_block_models_raw() -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let json = assets::load_json("models.min.json")?;
+    let mut models: HashMap<String, Value> = as_object(&json, "models.min.json")?
+        .iter()
+        .map(|(name, model)| (name.clone(), model.clone()))
+        .collect();
+
+    for file in packs::list("models/block") {
+        let name = match file.strip_suffix(".json") {
+            Some(name) => name,
+            None => continue,
+        };
+        if let Some(json) = packs::read_json(&format!("models/block/{}", file)) {
+            models.insert(format!("minecraft:block/{}", name), json);
+        }
+    }
+
+    Ok(models)
+}
+
+fn parse_block_models() -> Result<HashMap<String, BlockModel>, Box<dyn Error>> {
+    let mut models = HashMap::new();
+
+    for (key, data) in BLOCK_MODELS_RAW.load().iter() {
+        if models.contains_key(key) {
+            continue;
+        }
+
+        match BlockModel::parse(data, Some(&mut models)) {
+            Ok(model) => {
+                models.insert(key.clone(), model);
+            }
+            Err(e) => {
+                log::debug!("Couldn't parse block model: {:?}", e);
             }
         }
+    }
 
-        blocks
-    };
-    pub static ref BLOCK_MODELS_RAW: HashMap<String, Value> =
-        serde_json::from_slice(include_bytes!("../assets/models.min.json"))
-            .expect("Failed to interpret models.json");
-    pub static ref BLOCK_MODELS_PARSED: HashMap<String, BlockModel> = {
-        let mut models = HashMap::new();
-
-        for (key, data) in BLOCK_MODELS_RAW.iter() {
-            if models.contains_key(key) { continue; }
-
-            match BlockModel::parse(data, Some(&mut models)) {
-                Ok(model) => { models.insert(key.clone(), model); },
-                Err(e) => { log::debug!("Couldn't parse block model: {:?}", e); },
+    Ok(models)
+}
+
+fn load_block_textures() -> Result<HashMap<String, BlockTexture>, Box<dyn Error>> {
+    // Get list of texture and metadata files available in any resource pack
+    let mut textures: Vec<_> = packs::list("textures/block")
+        .into_iter()
+        .filter(|name| name.ends_with(".png") || name.ends_with(".mcmeta"))
+        .collect();
+    let metadata: Vec<_> = textures
+        .drain_filter(|name| name.ends_with(".mcmeta"))
+        .collect();
+
+    let mut out = HashMap::new();
+
+    // Load textures
+    out.insert(String::new(), MISSING_TEXTURE);
+
+    let mut index: usize = 1; // Reserve index 0 for missing texture
+    for tex in textures {
+        let full_name = tex;
+        let name = full_name.split(".").nth(0).unwrap();
+
+        let data = match packs::read(&format!("textures/block/{}", full_name)) {
+            Some(data) => data,
+            None => continue,
+        };
+        let img = match image::load(Cursor::new(&data), image::ImageFormat::Png) {
+            Ok(img) => img.to_rgba8(),
+            Err(e) => {
+                log::error!("Couldn't load block texture {}: {:?}", full_name, e);
+                continue;
             }
+        };
+
+        // Textures are square, animated ones have their frames stacked on top of each other
+        let size = img.width();
+        if size == 0 || img.height() % size != 0 {
+            log::error!(
+                "Block texture {} isn't square or a stack of square frames",
+                full_name
+            );
+            continue;
         }
 
-        models
-    };
-    pub static ref BLOCK_TEXTURES: HashMap<String, BlockTexture> = {
-        // Get list of texture and metadata files available
-        let mut textures: Vec<_> = std::fs::read_dir("assets/textures/block/")
-            .expect("Couldn't find textures directory")
-            .filter_map(|f| f.ok())
-            .filter(|f| {
-                let name = f.file_name();
-                let name = name.to_string_lossy();
-                name.ends_with(".png") || name.ends_with(".mcmeta")
-            }).collect();
-        let metadata: Vec<_> = textures.drain_filter(|f| {
-            f.file_name().to_string_lossy().ends_with(".mcmeta")
-        }).collect();
-
-        let mut out = HashMap::new();
-
-        // Load textures
-        image::load(Cursor::new(&include_bytes!("../assets/missing_texture.png")), image::ImageFormat::Png).unwrap().to_rgba8();
-        out.insert(String::new(), MISSING_TEXTURE);
-
-        let mut index: usize = 1; // Reserve index 0 for missing texture
-        for tex in textures {
-            let full_name = tex.file_name();
-            let full_name = full_name.to_string_lossy();
-            let name = full_name.split(".").nth(0).unwrap();
-
-            let data = std::fs::read(tex.path());
-            if data.is_err() {continue;}
-            let data = data.unwrap();
-            let img = image::load(Cursor::new(&data), image::ImageFormat::Png).unwrap().to_rgba8();
-
-            let mut frames = Vec::new();
-            if img.height() == 16 {
-                // Load single texture
-                frames.push(img);
-            } else {
-                // Load as multiple textures
-                let num_frames = img.height() / 16;
-                for i in 0..num_frames {
-                    frames.push(image::SubImage::new(&img, 0, i * 16, 16, 16).to_image());
-                }
+        let mut frames = Vec::new();
+        if img.height() == size {
+            // Load single texture
+            frames.push(img);
+        } else {
+            // Load as multiple textures
+            let num_frames = img.height() / size;
+            for i in 0..num_frames {
+                frames.push(image::SubImage::new(&img, 0, i * size, size, size).to_image());
             }
-            let inc = frames.len();
+        }
+        let inc = frames.len();
+        let layer = RenderLayer::from_frames(&frames);
 
-            out.insert(format!("minecraft:block/{}", name), BlockTexture {
+        out.insert(
+            format!("minecraft:block/{}", name),
+            BlockTexture {
                 index,
                 interpolation: false,
                 frames,
                 frametime: 0,
-            });
+                layer,
+                animation: Vec::new(),
+            },
+        );
+
+        index += inc;
+    }
+
+    // Add any metadata
+    for metadata in metadata {
+        let full_name = metadata;
+        let name = full_name.split(".").nth(0).unwrap();
+
+        let tex = match out.get_mut(&format!("minecraft:block/{}", name)) {
+            Some(tex) => tex,
+            None => continue,
+        };
+
+        let meta = packs::read(&format!("textures/block/{}", full_name))
+            .and_then(|contents| serde_json::from_slice::<serde_json::Value>(&contents).ok());
+        let meta = match meta {
+            Some(meta) => meta,
+            None => {
+                log::error!("Couldn't read texture metadata: {}", full_name);
+                continue;
+            }
+        };
+
+        if let Some(anim) = meta.get("animation") {
+            tex.interpolation = anim
+                .get("interpolate")
+                .and_then(|i| i.as_bool())
+                .unwrap_or(false);
+            tex.frametime = anim
+                .get("frametime")
+                .and_then(|t| t.as_u64())
+                .unwrap_or(1)
+                .max(1) as usize;
+            tex.animation =
+                parse_animation_frames(anim.get("frames"), tex.frames.len(), tex.frametime);
+        }
+    }
+
+    Ok(out)
+}
 
-            index += inc;
+impl BlockState {
+    /// Returns the first variant of the first part of this block state's models, if it has any
+    pub fn first_variant(&self) -> Option<&BlockVariant> {
+        self.models
+            .as_ref()
+            .and_then(|parts| parts.first())
+            .and_then(|variants| variants.first())
+    }
+
+    /// Returns true for any of the air blocks, which are never rendered or targeted
+    pub fn is_air(&self) -> bool {
+        matches!(self.name.as_str(), "Air" | "Cave Air" | "Void Air")
+    }
+
+    pub fn is_fluid(&self) -> bool {
+        matches!(self.name.as_str(), "Water" | "Lava")
+    }
+}
+
+impl BlockTexture {
+    /// The texture array layers of the current and next frames of the animation, and how far
+    /// to blend between them, at the given tick
+    pub fn animation_state(&self, tick: u64) -> (usize, usize, f32) {
+        let total: usize = self.animation.iter().map(|f| f.time).sum();
+        if total == 0 {
+            return (self.index, self.index, 0.0);
         }
 
-        // Add any metadata
-        for metadata in metadata {
-            let full_name = metadata.file_name();
-            let full_name = full_name.to_string_lossy();
-            let name = full_name.split(".").nth(0).unwrap();
+        let mut time = (tick % total as u64) as usize;
+        for (i, frame) in self.animation.iter().enumerate() {
+            if time < frame.time {
+                let next = self.animation[(i + 1) % self.animation.len()];
+                let blend = if self.interpolation {
+                    time as f32 / frame.time as f32
+                } else {
+                    0.0
+                };
+                return (self.index + frame.frame, self.index + next.frame, blend);
+            }
+            time -= frame.time;
+        }
 
-            if !out.contains_key(name){continue;}
-            let tex = out.get_mut(name).unwrap();
+        (self.index, self.index, 0.0)
+    }
+}
 
-            let contents = std::fs::read_to_string(metadata.path()).unwrap();
-            let meta = serde_json::from_str::<serde_json::Value>(&contents).unwrap();
+/// Reads the order of frames from a texture's animation metadata, which can be the frame indices
+/// or objects with an index and time. All the frames are shown in order if it isn't given.
+fn parse_animation_frames(
+    frames: Option<&Value>,
+    num_frames: usize,
+    frametime: usize,
+) -> Vec<AnimationFrame> {
+    let frames = match frames {
+        Some(Value::Array(frames)) => frames,
+        _ => {
+            return (0..num_frames)
+                .map(|frame| AnimationFrame {
+                    frame,
+                    time: frametime,
+                })
+                .collect()
+        }
+    };
 
-            if let Some(anim) = meta.get("animation") {
-                if let Some(interp) = anim.get("interpolate") {
-                    tex.interpolation = interp.as_bool().unwrap();
-                }
-                if let Some(frametime) = anim.get("frametime") {
-                    tex.frametime = frametime.as_u64().unwrap() as usize;
-                }
+    frames
+        .iter()
+        .filter_map(|frame| match frame {
+            Value::Number(index) => Some(AnimationFrame {
+                frame: index.as_u64()? as usize,
+                time: frametime,
+            }),
+            Value::Object(_) => Some(AnimationFrame {
+                frame: frame.get("index")?.as_u64()? as usize,
+                time: frame
+                    .get("time")
+                    .and_then(|t| t.as_u64())
+                    .map(|t| t.max(1) as usize)
+                    .unwrap_or(frametime),
+            }),
+            _ => None,
+        })
+        .filter(|frame| frame.frame < num_frames)
+        .collect()
+}
+
+impl FluidState {
+    /// Works out which fluid (if any) is in a block from its resource name and properties
+    pub fn parse(name: &str, properties: Option<&Value>) -> Option<FluidState> {
+        let property = |key: &str| properties.and_then(|p| p.get(key));
+        let level = property("level").and_then(|l| l.as_u64()).unwrap_or(0) as u8;
+
+        match name.trim_start_matches("minecraft:") {
+            "water" => Some(FluidState {
+                fluid: Fluid::Water,
+                level,
+            }),
+            "lava" => Some(FluidState {
+                fluid: Fluid::Lava,
+                level,
+            }),
+            // These are always underwater
+            "kelp" | "kelp_plant" | "seagrass" | "tall_seagrass" | "bubble_column" => {
+                Some(FluidState::source(Fluid::Water))
+            }
+            _ => match property("waterlogged").and_then(|w| w.as_bool()) {
+                Some(true) => Some(FluidState::source(Fluid::Water)),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn source(fluid: Fluid) -> FluidState {
+        FluidState { fluid, level: 0 }
+    }
+
+    /// How high the surface of this fluid is in the block, if there isn't the same fluid above it
+    pub fn height(&self) -> f32 {
+        if self.level == 0 || self.level >= 8 {
+            8.0 / 9.0
+        } else {
+            (8 - self.level) as f32 / 9.0
+        }
+    }
+}
+
+impl RenderLayer {
+    /// Works out which layer a texture belongs in from the alpha of its pixels
+    pub fn from_frames(frames: &[image::RgbaImage]) -> RenderLayer {
+        let mut layer = RenderLayer::Opaque;
+        for pixel in frames.iter().flat_map(|f| f.pixels()) {
+            match pixel.0[3] {
+                255 => {}
+                0 => layer = RenderLayer::Cutout,
+                _ => return RenderLayer::Translucent,
             }
         }
+        layer
+    }
+}
 
-        out
+impl BlockVariant {
+    pub fn parse(json: &Value) -> Option<BlockVariant> {
+        Some(BlockVariant {
+            model: json.get("model")?.as_str()?.to_string(),
+            x: json.get("x").and_then(|x| x.as_i64()).unwrap_or(0) as i32,
+            y: json.get("y").and_then(|y| y.as_i64()).unwrap_or(0) as i32,
+            uvlock: json
+                .get("uvlock")
+                .and_then(|u| u.as_bool())
+                .unwrap_or(false),
+            weight: json.get("weight").and_then(|w| w.as_u64()).unwrap_or(1) as u32,
+        })
+    }
+}
+
+/// Picks one of the weighted variants using a seed, which should be derived from the block's
+/// position so the same block always looks the same
+pub fn pick_variant(variants: &[BlockVariant], seed: u64) -> Option<&BlockVariant> {
+    let total: u64 = variants.iter().map(|v| v.weight.max(1) as u64).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut target = seed % total;
+    for variant in variants {
+        let weight = variant.weight.max(1) as u64;
+        if target < weight {
+            return Some(variant);
+        }
+        target -= weight;
+    }
+
+    variants.last()
+}
+
+/// Parses the render data of a block state. This is either a single variant, or a list of parts
+/// for multipart blocks where each part is either a single variant or a list of weighted variants
+fn parse_render(render: Option<&Value>) -> Option<Vec<WeightedVariants>> {
+    let parse_part = |part: &Value| -> Option<WeightedVariants> {
+        match part {
+            Value::Object(_) => BlockVariant::parse(part).map(|v| vec![v]),
+            Value::Array(variants) => {
+                let variants: WeightedVariants =
+                    variants.iter().filter_map(BlockVariant::parse).collect();
+                if variants.is_empty() {
+                    None
+                } else {
+                    Some(variants)
+                }
+            }
+            _ => None,
+        }
     };
+
+    match render {
+        // Has a single model
+        Some(part @ Value::Object(_)) => match parse_part(part) {
+            Some(part) => Some(vec![part]),
+            None => {
+                log::error!("Couldn't find model in block state render: {:?}", render);
+                None
+            }
+        },
+        // Has multiple parts
+        Some(Value::Array(parts)) => {
+            let parts: Vec<WeightedVariants> = parts.iter().filter_map(parse_part).collect();
+            if parts.is_empty() {
+                log::error!("No models in render section: {:?}", render);
+                return None;
+            }
+            Some(parts)
+        }
+        _ => None,
+    }
+}
+
+impl Tint {
+    /// Works out which tint applies to a block from its resource name, e.g. `minecraft:oak_leaves`
+    pub fn for_block(name: &str) -> Tint {
+        match name.trim_start_matches("minecraft:") {
+            "grass_block" | "grass" | "tall_grass" | "fern" | "large_fern" | "potted_fern"
+            | "sugar_cane" => Tint::Grass,
+            "oak_leaves" | "jungle_leaves" | "acacia_leaves" | "dark_oak_leaves" | "vine" => {
+                Tint::Foliage
+            }
+            "spruce_leaves" => Tint::Fixed(0x619961),
+            "birch_leaves" => Tint::Fixed(0x80A755),
+            "lily_pad" => Tint::Fixed(0x208030),
+            "water" | "bubble_column" => Tint::Water,
+            _ => Tint::None,
+        }
+    }
+
+    /// Returns the colour to multiply tinted faces by in the given biome
+    pub fn colour(&self, biome: Option<&Biome>) -> [f32; 3] {
+        let biomes = BIOMES.load();
+        let biome = biome.or_else(|| biomes.get(&DEFAULT_BIOME));
+
+        match (self, biome) {
+            (Tint::None, _) => [1.0, 1.0, 1.0],
+            (Tint::Fixed(col), _) => hex_to_colour(*col),
+            (Tint::Grass, Some(b)) => b.grass_colour(),
+            (Tint::Foliage, Some(b)) => b.foliage_colour(),
+            (Tint::Water, Some(b)) => hex_to_colour(b.water_colour),
+            (Tint::Grass, None) => hex_to_colour(0x7CBD6B),
+            (Tint::Foliage, None) => hex_to_colour(0x48B518),
+            (Tint::Water, None) => hex_to_colour(DEFAULT_WATER_COLOUR),
+        }
+    }
+}
+
+impl Biome {
+    pub fn grass_colour(&self) -> [f32; 3] {
+        sample_colourmap(&COLOURMAPS.load().grass, self.temperature, self.downfall)
+    }
+
+    pub fn foliage_colour(&self) -> [f32; 3] {
+        sample_colourmap(&COLOURMAPS.load().foliage, self.temperature, self.downfall)
+    }
+}
+
+/// Looks up a colour in a 256x256 vanilla colourmap the same way the game does, using the
+/// temperature along the x axis and the temperature-scaled downfall along the y axis
+fn sample_colourmap(map: &image::RgbaImage, temperature: f32, downfall: f32) -> [f32; 3] {
+    let temperature = temperature.clamp(0.0, 1.0);
+    let downfall = downfall.clamp(0.0, 1.0) * temperature;
+
+    let x = ((1.0 - temperature) * (map.width() - 1) as f32) as u32;
+    let y = ((1.0 - downfall) * (map.height() - 1) as f32) as u32;
+    let pixel = map.get_pixel(x, y);
+
+    [
+        pixel[0] as f32 / 255.0,
+        pixel[1] as f32 / 255.0,
+        pixel[2] as f32 / 255.0,
+    ]
+}
+
+/// Loads a colourmap texture, falling back to a single flat colour if it can't be read
+fn load_colourmap(path: &str, fallback: [u8; 3]) -> image::RgbaImage {
+    match packs::load_image(path) {
+        Ok(img) => img,
+        Err(e) => {
+            log::error!("Couldn't load colourmap {}: {:?}", path, e);
+            image::RgbaImage::from_pixel(
+                1,
+                1,
+                image::Rgba([fallback[0], fallback[1], fallback[2], 255]),
+            )
+        }
+    }
+}
+
+pub fn hex_to_colour(col: u32) -> [f32; 3] {
+    [
+        ((col >> 16) & 0xFF) as f32 / 255.0,
+        ((col >> 8) & 0xFF) as f32 / 255.0,
+        (col & 0xFF) as f32 / 255.0,
+    ]
 }
 
 pub fn format_name(name: &str) -> String {
@@ -245,3 +880,23 @@ pub fn format_name(name: &str) -> String {
         .replace('_', " ")
         .to_title_case()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_versions_are_freed_once_unused() {
+        let resource = Reloadable::new("test", || Ok(vec![1, 2, 3]));
+        let held = resource.load();
+        let old = Arc::downgrade(&held);
+
+        resource.reload();
+        assert!(old.upgrade().is_some());
+        assert_eq!(*resource.load(), vec![1, 2, 3]);
+        assert!(!Arc::ptr_eq(&held, &resource.load()));
+
+        drop(held);
+        assert!(old.upgrade().is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use lazy_static::lazy_static;
+use serde_json::Value;
+use simple_error::bail;
+
+/// Where the game's assets are unless the settings say otherwise
+pub const DEFAULT_ASSETS_DIR: &str = "assets";
+
+lazy_static! {
+    static ref ASSETS: AssetManager = AssetManager::new(DEFAULT_ASSETS_DIR);
+}
+
+/// Loads the game data downloaded by `get_assets.sh` from the assets folder. What's parsed from
+/// it is kept by the resources built from it, so the json itself isn't kept once they're loaded.
+pub struct AssetManager {
+    dir: RwLock<PathBuf>,
+}
+
+impl AssetManager {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> AssetManager {
+        AssetManager {
+            dir: RwLock::new(dir.into()),
+        }
+    }
+
+    pub fn get_dir(&self) -> PathBuf {
+        self.dir.read().unwrap().clone()
+    }
+
+    /// Switches to a different assets folder
+    pub fn set_dir<P: Into<PathBuf>>(&self, dir: P) {
+        *self.dir.write().unwrap() = dir.into();
+    }
+
+    /// Loads and parses a json file from the assets folder
+    pub fn load_json(&self, name: &str) -> Result<Value, Box<dyn Error>> {
+        let path = self.get_dir().join(name);
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) => bail!(
+                "Couldn't read {}: {}. Run get_assets.sh to download the game's assets",
+                path.display(),
+                e
+            ),
+        };
+        match serde_json::from_slice(&data) {
+            Ok(json) => Ok(json),
+            Err(e) => bail!("{} isn't valid json: {}", path.display(), e),
+        }
+    }
+}
+
+pub fn get_dir() -> PathBuf {
+    ASSETS.get_dir()
+}
+
+/// Switches to a different assets folder. Resources have to be reloaded afterwards to use it.
+pub fn set_dir<P: AsRef<Path>>(dir: P) {
+    ASSETS.set_dir(dir.as_ref());
+}
+
+/// Loads and parses a json file from the assets folder
+pub fn load_json(name: &str) -> Result<Value, Box<dyn Error>> {
+    ASSETS.load_json(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("minceraft-assets-{}", test));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn loads_from_the_current_folder() {
+        let (old, new) = (assets_dir("old"), assets_dir("new"));
+        std::fs::write(old.join("blocks.min.json"), r#"{"a": 1}"#).unwrap();
+        std::fs::write(new.join("blocks.min.json"), r#"{"a": 2}"#).unwrap();
+        let assets = AssetManager::new(&old);
+        assert_eq!(assets.load_json("blocks.min.json").unwrap()["a"], 1);
+
+        // Changes to the files are picked up the next time they're loaded
+        std::fs::write(old.join("blocks.min.json"), r#"{"a": 3}"#).unwrap();
+        assert_eq!(assets.load_json("blocks.min.json").unwrap()["a"], 3);
+
+        assets.set_dir(&new);
+        assert_eq!(assets.load_json("blocks.min.json").unwrap()["a"], 2);
+    }
+
+    #[test]
+    fn errors_name_the_file() {
+        let dir = assets_dir("errors");
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+        let assets = AssetManager::new(&dir);
+
+        let missing = assets.load_json("missing.json").unwrap_err().to_string();
+        assert!(missing.contains("missing.json"), "{}", missing);
+        assert!(missing.contains("get_assets.sh"), "{}", missing);
+
+        let broken = assets.load_json("broken.json").unwrap_err().to_string();
+        assert!(broken.contains("broken.json"), "{}", broken);
+    }
+}
@@ -1,11 +1,14 @@
-use std::{collections::HashMap, error::Error};
+use std::{collections::HashMap, error::Error, sync::Arc};
 
-use glam::{Vec2, Vec3};
+use glam::{IVec3, Mat3, Mat4, Vec2, Vec3};
 use simple_error::{bail, require_with};
 
-use crate::renderer::BlockVertex;
+use crate::renderer::{ChunkMesh, FaceVertex, FullFace};
 
-use super::{BLOCKS, BLOCK_MODELS_PARSED, BLOCK_MODELS_RAW, BLOCK_TEXTURES, MISSING_TEXTURE};
+use super::{
+    BlockState, BlockTexture, BlockVariant, MeshResources, RenderLayer, TextureRef,
+    BLOCK_MODELS_RAW, BLOCK_TEXTURES, MISSING_TEXTURE,
+};
 
 #[derive(Clone, Debug)]
 pub struct BlockModel {
@@ -16,8 +19,10 @@ pub struct BlockModel {
     cull_against: bool,
 }
 
+/// How a model is moved when it's shown as an item somewhere, like in the GUI or the player's
+/// hand. Translations are in sixteenths of a block and rotations in degrees.
 #[derive(Clone, Debug)]
-struct Display {
+pub struct Display {
     pub rotation: Vec3,
     pub translation: Vec3,
     pub scale: Vec3,
@@ -29,7 +34,18 @@ struct Element {
     pub to: Vec3,
     pub rot: Option<Rotation>,
     pub shade: bool,
-    pub faces: HashMap<String, Face>,
+    pub faces: HashMap<Direction, Face>,
+}
+
+/// The 6 faces of a block
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Down,
+    Up,
+    North,
+    South,
+    West,
+    East,
 }
 
 #[derive(Clone, Debug)]
@@ -49,9 +65,10 @@ struct Rotation {
 
 #[derive(Clone, Debug)]
 struct Face {
-    pub uv: (Vec2, Vec2),
+    /// None if the UVs should be worked out from the position of the element
+    pub uv: Option<(Vec2, Vec2)>,
     pub texture: String,
-    pub cullface: String,
+    pub cullface: Option<Direction>,
     pub rotation: f32,
     pub tintindex: f32,
 }
@@ -78,63 +95,63 @@ impl BlockModel {
     pub fn block_cube() -> BlockModel {
         let mut faces = HashMap::new();
         faces.insert(
-            "up".to_string(),
+            Direction::Up,
             Face {
-                uv: (Vec2::ZERO, Vec2::ONE),
+                uv: None,
                 texture: "#up".to_string(),
-                cullface: "up".to_string(),
+                cullface: Some(Direction::Up),
                 rotation: 0.0,
-                tintindex: 0.0,
+                tintindex: -1.0,
             },
         );
         faces.insert(
-            "down".to_string(),
+            Direction::Down,
             Face {
-                uv: (Vec2::ZERO, Vec2::ONE),
+                uv: None,
                 texture: "#down".to_string(),
-                cullface: "down".to_string(),
+                cullface: Some(Direction::Down),
                 rotation: 0.0,
-                tintindex: 0.0,
+                tintindex: -1.0,
             },
         );
         faces.insert(
-            "north".to_string(),
+            Direction::North,
             Face {
-                uv: (Vec2::ZERO, Vec2::ONE),
+                uv: None,
                 texture: "#north".to_string(),
-                cullface: "north".to_string(),
+                cullface: Some(Direction::North),
                 rotation: 0.0,
-                tintindex: 0.0,
+                tintindex: -1.0,
             },
         );
         faces.insert(
-            "east".to_string(),
+            Direction::East,
             Face {
-                uv: (Vec2::ZERO, Vec2::ONE),
+                uv: None,
                 texture: "#east".to_string(),
-                cullface: "east".to_string(),
+                cullface: Some(Direction::East),
                 rotation: 0.0,
-                tintindex: 0.0,
+                tintindex: -1.0,
             },
         );
         faces.insert(
-            "south".to_string(),
+            Direction::South,
             Face {
-                uv: (Vec2::ZERO, Vec2::ONE),
+                uv: None,
                 texture: "#south".to_string(),
-                cullface: "south".to_string(),
+                cullface: Some(Direction::South),
                 rotation: 0.0,
-                tintindex: 0.0,
+                tintindex: -1.0,
             },
         );
         faces.insert(
-            "west".to_string(),
+            Direction::West,
             Face {
-                uv: (Vec2::ZERO, Vec2::ONE),
+                uv: None,
                 texture: "#west".to_string(),
-                cullface: "west".to_string(),
+                cullface: Some(Direction::West),
                 rotation: 0.0,
-                tintindex: 0.0,
+                tintindex: -1.0,
             },
         );
 
@@ -171,228 +188,150 @@ impl BlockModel {
         base
     }
 
+    /// How the model is shown as an item in a position like `gui` or `firstperson_righthand`
+    pub fn get_display(&self, position: &str) -> Option<&Display> {
+        self.display.get(position)
+    }
+
+    /// Every face of the model with nothing culled or darkened by ambient occlusion, for drawing
+    /// it on its own like an item. Tinted faces are multiplied by `tint`.
+    pub fn get_quads(&self, tint: [f32; 3]) -> Vec<(RenderLayer, [FaceVertex; 4])> {
+        let variant = BlockVariant {
+            model: String::new(),
+            x: 0,
+            y: 0,
+            uvlock: false,
+            weight: 1,
+        };
+
+        let textures = BLOCK_TEXTURES.load();
+        let mut quads = Vec::new();
+        for element in &self.elements {
+            for (dir, face) in &element.faces {
+                let texture = get_texture(&textures, &self.textures, &face.texture);
+                let tint = if face.tintindex >= 0.0 {
+                    tint
+                } else {
+                    [1.0, 1.0, 1.0]
+                };
+                quads.push((
+                    texture.layer,
+                    element.face_vertices(*dir, face, &variant, texture.index as f32, tint),
+                ));
+            }
+        }
+        quads
+    }
+
+    /// The textures used by the faces of the model, as they're written in the model which is
+    /// usually a `#` reference to one of the model's textures
+    pub fn get_face_textures(&self) -> Vec<&str> {
+        self.elements
+            .iter()
+            .flat_map(|e| e.faces.values())
+            .map(|f| f.texture.as_str())
+            .collect()
+    }
+
+    /// Finds the name of the texture a face's texture refers to, or None if it's a `#` reference
+    /// the model doesn't define
+    pub fn resolve_texture<'a>(&'a self, texture: &'a str) -> Option<&'a str> {
+        resolve_texture(&self.textures, texture)
+    }
+
+    /// The texture pieces of the block are drawn with when it breaks
+    pub fn get_particle_texture(&self) -> TextureRef {
+        if self.textures.contains_key("particle") {
+            get_texture(&BLOCK_TEXTURES.load(), &self.textures, "#particle")
+        } else {
+            MISSING_TEXTURE.get_ref()
+        }
+    }
+
+    /// Generates the vertices for this model rotated according to the variant. The surrounding
+    /// blocks are used to cull faces that can't be seen, and `occlusion` darkens the corners of
+    /// faces that are tucked in next to other blocks.
     pub fn generate_mesh(
         &self,
+        res: &MeshResources,
+        variant: &BlockVariant,
+        occlusion: &FaceOcclusion,
         above: u16,
         below: u16,
         north: u16,
         east: u16,
         south: u16,
         west: u16,
-    ) -> Vec<BlockVertex> {
-        let mut verts = Vec::new();
-
-        let should_cull_face = |cullface: &str| {
-            let target = match cullface {
-                "up" => above,
-                "down" => below,
-                "north" => north,
-                "east" => east,
-                "south" => south,
-                "west" => west,
-                _ => 0,
-            };
+        tint: [f32; 3],
+    ) -> ChunkMesh {
+        let mut mesh = ChunkMesh::new();
+
+        let neighbour = |dir: Direction| match dir {
+            Direction::Up => above,
+            Direction::Down => below,
+            Direction::North => north,
+            Direction::East => east,
+            Direction::South => south,
+            Direction::West => west,
+        };
 
-            if target == 0 {
-                return false;
-            }
+        let rotation = variant_rotation(variant);
 
-            if let Some(block) = BLOCKS.get(&target.into()) {
-                return match &block.models {
-                    Some(models) => {
-                        let model = models.get(0).map(|s| s.as_ref()).unwrap_or("");
+        // Generate mesh for each element
+        for element in &self.elements {
+            for (dir, face) in &element.faces {
+                let against = face.cullface.map(|c| neighbour(c.rotate(&rotation)));
+                if against.map(|b| is_opaque_block(b, res)).unwrap_or(false) {
+                    continue;
+                }
 
-                        let exceptions: Vec<&str> = vec!["glass", "leaves", "water", "spawner"];
-                        for exception in exceptions {
-                            if model.contains(exception) {
-                                return false;
-                            }
-                        }
+                let texture = get_texture(&res.textures, &self.textures, &face.texture);
+                // Faces between two of the same see-through block (like ice or stained glass)
+                // can't be seen either
+                if texture.layer == RenderLayer::Translucent
+                    && against
+                        .map(|b| is_same_model(b, variant, &res.blocks))
+                        .unwrap_or(false)
+                {
+                    continue;
+                }
 
-                        BLOCK_MODELS_PARSED
-                            .get(model)
-                            .map(|m| m.cull_against)
-                            .unwrap_or(false)
-                    }
-                    None => false,
+                let tint = if face.tintindex >= 0.0 {
+                    tint
+                } else {
+                    [1.0, 1.0, 1.0]
                 };
-            }
 
-            false
-        };
+                let world_dir = dir.rotate(&rotation);
+                let mut face_verts =
+                    element.face_vertices(*dir, face, variant, texture.index as f32, tint);
+                if self.ambient_occlusion {
+                    for vert in &mut face_verts {
+                        vert.shade *= world_dir
+                            .interpolate(&occlusion[world_dir as usize], Vec3::from(vert.position));
+                    }
+                }
 
-        // Generate mesh for each element
-        for element in &self.elements {
-            for (key, face) in &element.faces {
-                if should_cull_face(&face.cullface) {
+                // Whole faces are kept back so the chunk builder can merge them with their
+                // neighbours
+                if let Some(full) = FullFace::from_quad(world_dir, texture.layer, &face_verts) {
+                    mesh.faces.push(full);
                     continue;
                 }
 
-                let texture = get_texture_index(&self.textures, &face.texture);
-
-                match key.as_ref() {
-                    "up" => {
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.from.z],
-                            tex_coords: [face.uv.1.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.to.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.to.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.0.x, face.uv.1.y, texture],
-                        });
-                    }
-                    "down" => {
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.from.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.to.z],
-                            tex_coords: [face.uv.0.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.from.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.1.x, face.uv.0.y, texture],
-                        });
-                    }
-                    "north" => {
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.from.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.to.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.from.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.1.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                    }
-                    "east" => {
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.from.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                    }
-                    "south" => {
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.0.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.to.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.to.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.to.x, element.from.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.0.y, texture],
-                        });
-                    }
-                    "west" => {
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.to.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.to.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.1.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.from.z],
-                            tex_coords: [face.uv.0.x, face.uv.0.y, texture],
-                        });
-                        verts.push(BlockVertex {
-                            position: [element.from.x, element.from.y, element.to.z],
-                            tex_coords: [face.uv.1.x, face.uv.0.y, texture],
-                        });
-                    }
-                    _ => {}
-                }
+                // Split the quad along whichever diagonal keeps the occlusion looking even
+                let indices = if face_verts[0].shade + face_verts[2].shade
+                    < face_verts[1].shade + face_verts[3].shade
+                {
+                    [1, 2, 3, 1, 3, 0]
+                } else {
+                    [0, 1, 2, 0, 2, 3]
+                };
+                mesh.layer_mut(texture.layer).push(face_verts, &indices);
             }
         }
 
-        verts
+        mesh
     }
 
     pub fn parse(
@@ -412,7 +351,12 @@ impl BlockModel {
                     if let Some(cache) = cache {
                         // Parse parent if it isn't already parsed and add it to the cache
                         if cache.get(parent).is_none() {
-                            if let Some(parent_raw) = BLOCK_MODELS_RAW.get(parent) {
+                            // Resource pack models can leave the namespace off their parent
+                            let models_raw = BLOCK_MODELS_RAW.load();
+                            let parent_raw = models_raw
+                                .get(parent)
+                                .or_else(|| models_raw.get(&format!("minecraft:{}", parent)));
+                            if let Some(parent_raw) = parent_raw {
                                 let parent_parsed = Self::parse(parent_raw, Some(cache))?;
                                 cache.insert(parent.clone(), parent_parsed);
                             } else {
@@ -473,10 +417,20 @@ impl Display {
         Display {
             rotation: Vec3::ZERO,
             translation: Vec3::ZERO,
-            scale: Vec3::ZERO,
+            scale: Vec3::ONE,
         }
     }
 
+    /// The transform to apply to a model centred on the origin, in blocks
+    pub fn get_matrix(&self) -> Mat4 {
+        let rotation = self.rotation * std::f32::consts::PI / 180.0;
+        Mat4::from_translation(self.translation / 16.0)
+            * Mat4::from_rotation_x(rotation.x)
+            * Mat4::from_rotation_y(rotation.y)
+            * Mat4::from_rotation_z(rotation.z)
+            * Mat4::from_scale(self.scale)
+    }
+
     pub fn parse(json: &serde_json::Value) -> Result<Display, Box<dyn Error>> {
         let mut base = Self::empty();
 
@@ -538,7 +492,7 @@ impl Element {
             from: Vec3::ZERO,
             to: Vec3::ZERO,
             rot: None,
-            shade: false,
+            shade: true,
             faces: HashMap::new(),
         }
     }
@@ -599,15 +553,95 @@ impl Element {
         // Faces
         if let Some(serde_json::Value::Object(faces)) = json.get("faces") {
             for (face, data) in faces {
-                base.faces.insert(face.clone(), Face::parse(data)?);
+                let dir = match Direction::from_name(face) {
+                    Some(dir) => dir,
+                    None => bail!("Invalid face direction: {}", face),
+                };
+                base.faces.insert(dir, Face::parse(data)?);
             }
         }
 
         Ok(base)
     }
+
+    /// Works out the 4 corners of one of this element's faces, in the order they should be
+    /// drawn as 2 triangles (0, 1, 2) and (0, 2, 3)
+    pub fn face_vertices(
+        &self,
+        dir: Direction,
+        face: &Face,
+        variant: &BlockVariant,
+        texture: f32,
+        tint: [f32; 3],
+    ) -> [FaceVertex; 4] {
+        let rotated = variant.x != 0 || variant.y != 0;
+        let rotation = variant_rotation(variant);
+        let world_dir = dir.rotate(&rotation);
+        let shade = if self.shade { world_dir.shade() } else { 1.0 };
+
+        let corners = dir.face_corners(self.from, self.to);
+
+        // Corners of the UV rectangle, with (u1, v1) at the first corner of the face
+        let (uv1, uv2) = face
+            .uv
+            .unwrap_or_else(|| (dir.project_uv(corners[0]), dir.project_uv(corners[2])));
+        let uvs = [uv1, Vec2::new(uv1.x, uv2.y), uv2, Vec2::new(uv2.x, uv1.y)];
+        // Rotating the face's texture just moves each corner along to the next uv
+        let uv_offset = (face.rotation as i32 / 90).rem_euclid(4) as usize;
+
+        let mut verts = [FaceVertex {
+            position: [0.0; 3],
+            tex_coords: [0.0; 3],
+            tint,
+            shade,
+        }; 4];
+        for (i, corner) in corners.iter().enumerate() {
+            let mut position = *corner;
+            if let Some(rot) = &self.rot {
+                position = rot.apply(position);
+            }
+            position = rotation * (position - Vec3::splat(0.5)) + Vec3::splat(0.5);
+
+            // With uvlock the texture stays lined up with the world rather than turning with the
+            // model
+            let uv = if variant.uvlock && rotated {
+                world_dir.project_uv(position)
+            } else {
+                uvs[(i + uv_offset) % 4]
+            };
+
+            verts[i] = FaceVertex {
+                position: position.to_array(),
+                // Textures are stored upside down, so flip v
+                tex_coords: [uv.x, 1.0 - uv.y, texture],
+                tint,
+                shade,
+            };
+        }
+
+        verts
+    }
 }
 
 impl Rotation {
+    /// Rotates a point in the element around the origin, stretching it back out to the edges
+    /// of the block if rescale is set
+    pub fn apply(&self, pos: Vec3) -> Vec3 {
+        let angle = self.angle.to_radians();
+        let (matrix, scale) = match self.axis {
+            RotationAxis::X => (Mat3::from_rotation_x(angle), Vec3::new(0.0, 1.0, 1.0)),
+            RotationAxis::Y => (Mat3::from_rotation_y(angle), Vec3::new(1.0, 0.0, 1.0)),
+            RotationAxis::Z => (Mat3::from_rotation_z(angle), Vec3::new(1.0, 1.0, 0.0)),
+        };
+
+        let mut offset = matrix * (pos - self.origin);
+        if self.rescale && angle.cos() != 0.0 {
+            offset *= Vec3::ONE + scale * (1.0 / angle.cos() - 1.0);
+        }
+
+        offset + self.origin
+    }
+
     pub fn empty() -> Rotation {
         Rotation {
             origin: Vec3::ZERO,
@@ -658,8 +692,7 @@ impl Rotation {
 
         // Angle
         if let Some(serde_json::Value::Number(angle)) = json.get("angle") {
-            base.angle =
-                require_with!(angle.as_f64(), "Couldn't get angle of rotation.") as f32 / 22.5;
+            base.angle = require_with!(angle.as_f64(), "Couldn't get angle of rotation.") as f32;
         }
 
         // Rescale
@@ -674,11 +707,11 @@ impl Rotation {
 impl Face {
     pub fn empty() -> Face {
         Face {
-            uv: (Vec2::ZERO, Vec2::ONE),
+            uv: None,
             texture: String::from(""),
-            cullface: String::from(""),
+            cullface: None,
             rotation: 0.0,
-            tintindex: 0.0,
+            tintindex: -1.0,
         }
     }
 
@@ -691,18 +724,15 @@ impl Face {
                 bail!("UV coordinates didn't have 4 values.");
             }
 
-            base.uv.0.x = require_with!(uv.get(0).unwrap().as_f64(), "Couldn't read UV coordinate")
-                as f32
-                / 16.0;
-            base.uv.0.y = require_with!(uv.get(1).unwrap().as_f64(), "Couldn't read UV coordinate")
-                as f32
-                / 16.0;
-            base.uv.1.x = require_with!(uv.get(2).unwrap().as_f64(), "Couldn't read UV coordinate")
-                as f32
-                / 16.0;
-            base.uv.1.y = require_with!(uv.get(3).unwrap().as_f64(), "Couldn't read UV coordinate")
-                as f32
-                / 16.0;
+            let mut coords = [0.0; 4];
+            for (i, coord) in uv.iter().enumerate() {
+                coords[i] =
+                    require_with!(coord.as_f64(), "Couldn't read UV coordinate") as f32 / 16.0;
+            }
+            base.uv = Some((
+                Vec2::new(coords[0], coords[1]),
+                Vec2::new(coords[2], coords[3]),
+            ));
         }
 
         // Texture
@@ -712,7 +742,7 @@ impl Face {
 
         // Cullface
         if let Some(serde_json::Value::String(cullface)) = json.get("cullface") {
-            base.cullface = cullface.clone();
+            base.cullface = Direction::from_name(cullface);
         }
 
         // Rotation
@@ -731,23 +761,542 @@ impl Face {
     }
 }
 
-fn get_texture_index(texture_map: &HashMap<String, String>, texture: &str) -> f32 {
-    let texture_key: &str = texture_map
-        .get(&texture[1..])
-        .map(|s| s.as_str())
-        .unwrap_or(texture);
-    let index = BLOCK_TEXTURES
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::Down,
+        Direction::Up,
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "down" | "bottom" => Some(Direction::Down),
+            "up" | "top" => Some(Direction::Up),
+            "north" => Some(Direction::North),
+            "south" => Some(Direction::South),
+            "west" => Some(Direction::West),
+            "east" => Some(Direction::East),
+            _ => None,
+        }
+    }
+
+    pub fn normal(&self) -> IVec3 {
+        match self {
+            Direction::Down => IVec3::new(0, -1, 0),
+            Direction::Up => IVec3::new(0, 1, 0),
+            Direction::North => IVec3::new(0, 0, -1),
+            Direction::South => IVec3::new(0, 0, 1),
+            Direction::West => IVec3::new(-1, 0, 0),
+            Direction::East => IVec3::new(1, 0, 0),
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Down => Direction::Up,
+            Direction::Up => Direction::Down,
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::East => Direction::West,
+        }
+    }
+
+    /// Finds the direction closest to the given vector
+    pub fn from_normal(normal: Vec3) -> Direction {
+        let mut best = Direction::Up;
+        let mut best_dot = f32::MIN;
+        for dir in Self::ALL {
+            let dot = dir.normal().as_vec3().dot(normal);
+            if dot > best_dot {
+                best = dir;
+                best_dot = dot;
+            }
+        }
+        best
+    }
+
+    /// How bright faces pointing this way are, so blocks don't look flat
+    pub fn shade(&self) -> f32 {
+        match self {
+            Direction::Down => 0.5,
+            Direction::Up => 1.0,
+            Direction::North | Direction::South => 0.8,
+            Direction::West | Direction::East => 0.6,
+        }
+    }
+
+    /// The two axes (0 = x, 1 = y, 2 = z) that run along a face pointing this way
+    pub fn tangents(&self) -> (usize, usize) {
+        match self {
+            Direction::Down | Direction::Up => (0, 2),
+            Direction::North | Direction::South => (0, 1),
+            Direction::West | Direction::East => (2, 1),
+        }
+    }
+
+    /// Blends between values at the 4 corners of a face pointing this way, indexed by
+    /// `a + 2 * b` where `a` and `b` are 0 or 1 along the first and second tangents
+    pub fn interpolate(&self, corners: &[f32; 4], pos: Vec3) -> f32 {
+        let (ta, tb) = self.tangents();
+        let a = pos[ta].clamp(0.0, 1.0);
+        let b = pos[tb].clamp(0.0, 1.0);
+
+        let low = corners[0] + (corners[1] - corners[0]) * a;
+        let high = corners[2] + (corners[3] - corners[2]) * a;
+        low + (high - low) * b
+    }
+
+    /// Which way this direction faces after being rotated
+    pub fn rotate(&self, rotation: &Mat3) -> Direction {
+        Self::from_normal(*rotation * self.normal().as_vec3())
+    }
+
+    /// The 4 corners of this face of the box between `from` and `to`, in anticlockwise order
+    /// when looking at the face from outside
+    pub fn face_corners(&self, from: Vec3, to: Vec3) -> [Vec3; 4] {
+        let (f, t) = (from, to);
+        match self {
+            Direction::Down => [
+                Vec3::new(f.x, f.y, t.z),
+                Vec3::new(f.x, f.y, f.z),
+                Vec3::new(t.x, f.y, f.z),
+                Vec3::new(t.x, f.y, t.z),
+            ],
+            Direction::Up => [
+                Vec3::new(f.x, t.y, f.z),
+                Vec3::new(f.x, t.y, t.z),
+                Vec3::new(t.x, t.y, t.z),
+                Vec3::new(t.x, t.y, f.z),
+            ],
+            Direction::North => [
+                Vec3::new(t.x, t.y, f.z),
+                Vec3::new(t.x, f.y, f.z),
+                Vec3::new(f.x, f.y, f.z),
+                Vec3::new(f.x, t.y, f.z),
+            ],
+            Direction::South => [
+                Vec3::new(f.x, t.y, t.z),
+                Vec3::new(f.x, f.y, t.z),
+                Vec3::new(t.x, f.y, t.z),
+                Vec3::new(t.x, t.y, t.z),
+            ],
+            Direction::West => [
+                Vec3::new(f.x, t.y, f.z),
+                Vec3::new(f.x, f.y, f.z),
+                Vec3::new(f.x, f.y, t.z),
+                Vec3::new(f.x, t.y, t.z),
+            ],
+            Direction::East => [
+                Vec3::new(t.x, t.y, t.z),
+                Vec3::new(t.x, f.y, t.z),
+                Vec3::new(t.x, f.y, f.z),
+                Vec3::new(t.x, t.y, f.z),
+            ],
+        }
+    }
+
+    /// Projects a point in the block onto this face to get it's texture coordinates, with (0, 0)
+    /// in the top left of the texture
+    pub fn project_uv(&self, pos: Vec3) -> Vec2 {
+        match self {
+            Direction::Down => Vec2::new(pos.x, 1.0 - pos.z),
+            Direction::Up => Vec2::new(pos.x, pos.z),
+            Direction::North => Vec2::new(1.0 - pos.x, 1.0 - pos.y),
+            Direction::South => Vec2::new(pos.x, 1.0 - pos.y),
+            Direction::West => Vec2::new(pos.z, 1.0 - pos.y),
+            Direction::East => Vec2::new(1.0 - pos.z, 1.0 - pos.y),
+        }
+    }
+}
+
+/// Ambient occlusion at the corners of each face of a block, indexed by `Direction as usize` and
+/// then as described in `Direction::interpolate`
+pub type FaceOcclusion = [[f32; 4]; 6];
+
+/// No ambient occlusion on any face
+pub const NO_OCCLUSION: FaceOcclusion = [[1.0; 4]; 6];
+
+/// Checks if a block is a full opaque cube, which hides the faces of blocks next to it and casts
+/// ambient occlusion
+pub fn is_opaque_block(id: u16, res: &MeshResources) -> bool {
+    if id == 0 {
+        return false;
+    }
+
+    match res
+        .blocks
+        .get(&id.into())
+        .and_then(|block| block.first_variant())
+    {
+        Some(variant) => {
+            let model = variant.model.as_str();
+
+            let exceptions: Vec<&str> = vec!["glass", "leaves", "water", "spawner"];
+            for exception in exceptions {
+                if model.contains(exception) {
+                    return false;
+                }
+            }
+
+            res.models
+                .get(model)
+                .map(|m| m.cull_against)
+                .unwrap_or(false)
+        }
+        None => false,
+    }
+}
+
+/// Checks if a block is drawn using the same model as the variant
+fn is_same_model(id: u16, variant: &BlockVariant, blocks: &HashMap<u32, Arc<BlockState>>) -> bool {
+    blocks
+        .get(&id.into())
+        .and_then(|block| block.first_variant())
+        .map(|other| other.model == variant.model)
+        .unwrap_or(false)
+}
+
+/// The rotation a blockstate variant applies to its model, around the centre of the block
+pub fn variant_rotation(variant: &BlockVariant) -> Mat3 {
+    Mat3::from_rotation_y((-variant.y as f32).to_radians())
+        * Mat3::from_rotation_x((-variant.x as f32).to_radians())
+}
+
+fn resolve_texture<'a>(
+    texture_map: &'a HashMap<String, String>,
+    texture: &'a str,
+) -> Option<&'a str> {
+    let name = match texture.strip_prefix('#') {
+        Some(key) => texture_map.get(key)?.as_str(),
+        None => texture,
+    };
+    if name.starts_with('#') {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn get_texture(
+    textures: &HashMap<String, BlockTexture>,
+    texture_map: &HashMap<String, String>,
+    texture: &str,
+) -> TextureRef {
+    let texture_key = resolve_texture(texture_map, texture).unwrap_or(texture);
+    let tex = textures
         .get(texture_key)
-        .unwrap_or(
-            BLOCK_TEXTURES
-                .get(&format!("minecraft:{}", texture_key))
-                .unwrap_or(&MISSING_TEXTURE),
-        )
-        .index;
+        .or_else(|| textures.get(&format!("minecraft:{}", texture_key)));
 
-    if index == 0 {
-        log::error!("Missing texture: {}", texture_key);
+    match tex {
+        Some(tex) => tex.get_ref(),
+        None => {
+            log::error!("Missing texture: {}", texture_key);
+            MISSING_TEXTURE.get_ref()
+        }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
 
-    index as f32
+    use super::*;
+
+    fn variant(x: i32, y: i32, uvlock: bool) -> BlockVariant {
+        BlockVariant {
+            model: String::new(),
+            x,
+            y,
+            uvlock,
+            weight: 1,
+        }
+    }
+
+    fn face_vertices(
+        element: &serde_json::Value,
+        dir: Direction,
+        variant: &BlockVariant,
+    ) -> [FaceVertex; 4] {
+        let element = Element::parse(element).unwrap();
+        let face = element.faces.get(&dir).unwrap();
+        element.face_vertices(dir, face, variant, 0.0, [1.0; 3])
+    }
+
+    fn assert_positions(verts: &[FaceVertex; 4], expected: [[f32; 3]; 4]) {
+        for (vert, expected) in verts.iter().zip(expected) {
+            assert!(
+                Vec3::from(vert.position).abs_diff_eq(Vec3::from(expected), 1e-4),
+                "{:?} != {:?}",
+                vert.position,
+                expected
+            );
+        }
+    }
+
+    fn assert_uvs(verts: &[FaceVertex; 4], expected: [[f32; 2]; 4]) {
+        for (vert, expected) in verts.iter().zip(expected) {
+            let uv = Vec2::new(vert.tex_coords[0], vert.tex_coords[1]);
+            assert!(
+                uv.abs_diff_eq(Vec2::from(expected), 1e-4),
+                "{:?} != {:?}",
+                uv,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn cube_top_face() {
+        let verts = face_vertices(
+            &json!({
+                "from": [0, 0, 0],
+                "to": [16, 16, 16],
+                "faces": { "up": { "texture": "#top" } }
+            }),
+            Direction::Up,
+            &variant(0, 0, false),
+        );
+
+        assert_positions(
+            &verts,
+            [
+                [0.0, 1.0, 0.0],
+                [0.0, 1.0, 1.0],
+                [1.0, 1.0, 1.0],
+                [1.0, 1.0, 0.0],
+            ],
+        );
+        assert_uvs(&verts, [[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]]);
+    }
+
+    #[test]
+    fn slab_side_uses_bottom_of_texture() {
+        let verts = face_vertices(
+            &json!({
+                "from": [0, 0, 0],
+                "to": [16, 8, 16],
+                "faces": { "north": { "texture": "#side" } }
+            }),
+            Direction::North,
+            &variant(0, 0, false),
+        );
+
+        assert_positions(
+            &verts,
+            [
+                [1.0, 0.5, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [0.0, 0.5, 0.0],
+            ],
+        );
+        assert_uvs(&verts, [[0.0, 0.5], [0.0, 0.0], [1.0, 0.0], [1.0, 0.5]]);
+    }
+
+    #[test]
+    fn face_uv_rotation() {
+        let element = json!({
+            "from": [0, 0, 0],
+            "to": [16, 16, 16],
+            "faces": { "up": { "texture": "#top", "uv": [0, 0, 16, 16], "rotation": 90 } }
+        });
+
+        let verts = face_vertices(&element, Direction::Up, &variant(0, 0, false));
+        assert_uvs(&verts, [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+
+        // Rotating the whole way round should be the same as not rotating at all
+        let mut element = element;
+        element["faces"]["up"]["rotation"] = json!(360);
+        let verts = face_vertices(&element, Direction::Up, &variant(0, 0, false));
+        assert_uvs(&verts, [[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]]);
+    }
+
+    #[test]
+    fn crossed_plant_rescaled() {
+        // First plane of block/cross
+        let verts = face_vertices(
+            &json!({
+                "from": [0.8, 0, 8],
+                "to": [15.2, 16, 8],
+                "rotation": { "origin": [8, 8, 8], "axis": "y", "angle": 45, "rescale": true },
+                "faces": { "north": { "uv": [0, 0, 16, 16], "texture": "#cross" } }
+            }),
+            Direction::North,
+            &variant(0, 0, false),
+        );
+
+        // Stretched out so the plane reaches the corners of the block
+        assert_positions(
+            &verts,
+            [
+                [0.95, 1.0, 0.05],
+                [0.95, 0.0, 0.05],
+                [0.05, 0.0, 0.95],
+                [0.05, 1.0, 0.95],
+            ],
+        );
+    }
+
+    #[test]
+    fn element_rotation_without_rescale() {
+        let verts = face_vertices(
+            &json!({
+                "from": [16, 0, 0],
+                "to": [16, 16, 16],
+                "rotation": { "origin": [8, 8, 8], "axis": "z", "angle": 22.5 },
+                "faces": { "east": { "texture": "#side" } }
+            }),
+            Direction::East,
+            &variant(0, 0, false),
+        );
+
+        let (sin, cos) = 22.5f32.to_radians().sin_cos();
+        let rotate = |x: f32, y: f32| {
+            [
+                0.5 + (x - 0.5) * cos - (y - 0.5) * sin,
+                0.5 + (x - 0.5) * sin + (y - 0.5) * cos,
+            ]
+        };
+        let top = rotate(1.0, 1.0);
+        let bottom = rotate(1.0, 0.0);
+
+        assert_positions(
+            &verts,
+            [
+                [top[0], top[1], 1.0],
+                [bottom[0], bottom[1], 1.0],
+                [bottom[0], bottom[1], 0.0],
+                [top[0], top[1], 0.0],
+            ],
+        );
+    }
+
+    #[test]
+    fn variant_rotation_turns_faces() {
+        let rotation = variant_rotation(&variant(0, 90, false));
+        assert_eq!(Direction::North.rotate(&rotation), Direction::East);
+        assert_eq!(Direction::Up.rotate(&rotation), Direction::Up);
+
+        let rotation = variant_rotation(&variant(90, 0, false));
+        assert_eq!(Direction::Up.rotate(&rotation), Direction::North);
+
+        let verts = face_vertices(
+            &json!({
+                "from": [0, 0, 0],
+                "to": [16, 16, 8],
+                "faces": { "north": { "texture": "#front" } }
+            }),
+            Direction::North,
+            &variant(0, 90, false),
+        );
+        assert!(verts.iter().all(|v| (v.position[0] - 1.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn uvlock_keeps_texture_aligned() {
+        let element = json!({
+            "from": [0, 0, 0],
+            "to": [16, 8, 16],
+            "faces": { "north": { "texture": "#side" } }
+        });
+
+        // Without uvlock the face keeps its texture coordinates wherever it ends up
+        let unlocked = face_vertices(&element, Direction::North, &variant(0, 90, false));
+        assert_uvs(&unlocked, [[0.0, 0.5], [0.0, 0.0], [1.0, 0.0], [1.0, 0.5]]);
+
+        // With uvlock they come from where the face is in the world, so flipping the slab upside
+        // down shows the top half of the texture on what's now the south face
+        let locked = face_vertices(&element, Direction::North, &variant(180, 0, true));
+        assert_positions(
+            &locked,
+            [
+                [1.0, 0.5, 1.0],
+                [1.0, 1.0, 1.0],
+                [0.0, 1.0, 1.0],
+                [0.0, 0.5, 1.0],
+            ],
+        );
+        assert_uvs(&locked, [[1.0, 0.5], [1.0, 1.0], [0.0, 1.0], [0.0, 0.5]]);
+    }
+
+    #[test]
+    fn faces_shaded_by_direction() {
+        let element = json!({
+            "from": [0, 0, 0],
+            "to": [16, 16, 16],
+            "faces": { "down": { "texture": "#bottom" }, "east": { "texture": "#side" } }
+        });
+
+        let verts = face_vertices(&element, Direction::Down, &variant(0, 0, false));
+        assert!(verts.iter().all(|v| v.shade == 0.5));
+
+        // The shade follows the face once it's been rotated to point up
+        let verts = face_vertices(&element, Direction::Down, &variant(180, 0, false));
+        assert!(verts.iter().all(|v| v.shade == 1.0));
+
+        let mut unshaded = element;
+        unshaded["shade"] = json!(false);
+        let verts = face_vertices(&unshaded, Direction::East, &variant(0, 0, false));
+        assert!(verts.iter().all(|v| v.shade == 1.0));
+    }
+
+    #[test]
+    fn occlusion_interpolation() {
+        let corners = [0.0, 1.0, 0.5, 0.25];
+
+        // Up faces run along x then z
+        assert_eq!(
+            Direction::Up.interpolate(&corners, Vec3::new(0.0, 1.0, 0.0)),
+            0.0
+        );
+        assert_eq!(
+            Direction::Up.interpolate(&corners, Vec3::new(1.0, 1.0, 0.0)),
+            1.0
+        );
+        assert_eq!(
+            Direction::Up.interpolate(&corners, Vec3::new(0.0, 1.0, 1.0)),
+            0.5
+        );
+        assert_eq!(
+            Direction::Up.interpolate(&corners, Vec3::new(1.0, 1.0, 1.0)),
+            0.25
+        );
+        assert_eq!(
+            Direction::Up.interpolate(&corners, Vec3::new(0.5, 1.0, 0.5)),
+            0.4375
+        );
+
+        // East faces run along z then y
+        assert_eq!(
+            Direction::East.interpolate(&corners, Vec3::new(1.0, 0.0, 1.0)),
+            1.0
+        );
+        assert_eq!(
+            Direction::East.interpolate(&corners, Vec3::new(1.0, 1.0, 0.0)),
+            0.5
+        );
+    }
+
+    #[test]
+    fn resolves_texture_references() {
+        let model = BlockModel::parse(
+            &json!({
+                "parent": "block/cube",
+                "textures": { "up": "block/stone", "down": "#bottom", "north": "#up" }
+            }),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(model.get_face_textures().len(), 6);
+        assert_eq!(model.resolve_texture("#up"), Some("block/stone"));
+        assert_eq!(model.resolve_texture("#north"), Some("block/stone"));
+        assert_eq!(model.resolve_texture("#down"), None);
+        assert_eq!(model.resolve_texture("#east"), None);
+        assert_eq!(model.resolve_texture("block/dirt"), Some("block/dirt"));
+    }
 }
@@ -0,0 +1,173 @@
+use serde_json::Value;
+
+use super::{BlockVariant, WeightedVariants};
+
+/// Works out the models a block state uses from a blockstate file in the game's format, which
+/// resource packs use to change them. Blockstate files either have a variant for each combination
+/// of properties, or parts which are added depending on the properties.
+pub fn parse_blockstate(json: &Value, properties: Option<&Value>) -> Option<Vec<WeightedVariants>> {
+    if let Some(variants) = json.get("variants").and_then(|v| v.as_object()) {
+        let variant = variants
+            .iter()
+            .find(|(key, _)| matches_variant(key, properties))
+            .and_then(|(_, variant)| parse_variants(variant));
+        if variant.is_none() {
+            log::error!("No blockstate variant matches properties {:?}", properties);
+        }
+        variant.map(|v| vec![v])
+    } else if let Some(multipart) = json.get("multipart").and_then(|m| m.as_array()) {
+        let parts: Vec<_> = multipart
+            .iter()
+            .filter(|part| {
+                part.get("when")
+                    .map(|when| matches_when(when, properties))
+                    .unwrap_or(true)
+            })
+            .filter_map(|part| parse_variants(part.get("apply")?))
+            .collect();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts)
+        }
+    } else {
+        log::error!("Blockstate has no variants or multipart: {:?}", json);
+        None
+    }
+}
+
+/// Parses either a single variant or a list of weighted ones
+fn parse_variants(json: &Value) -> Option<WeightedVariants> {
+    let mut variants: WeightedVariants = match json {
+        Value::Object(_) => vec![BlockVariant::parse(json)?],
+        Value::Array(variants) => variants.iter().filter_map(BlockVariant::parse).collect(),
+        _ => return None,
+    };
+
+    // Models can be named without their namespace in blockstate files
+    for variant in variants.iter_mut() {
+        if !variant.model.contains(':') {
+            variant.model = format!("minecraft:{}", variant.model);
+        }
+    }
+
+    if variants.is_empty() {
+        None
+    } else {
+        Some(variants)
+    }
+}
+
+/// The value of a block state's property as it's written in blockstate files
+fn property(properties: Option<&Value>, name: &str) -> Option<String> {
+    match properties?.get(name)? {
+        Value::String(s) => Some(s.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Checks a variant key like `facing=north,half=top` against a block state's properties. An
+/// empty key matches every state.
+fn matches_variant(key: &str, properties: Option<&Value>) -> bool {
+    key.split(',')
+        .filter(|condition| !condition.is_empty())
+        .all(|condition| match condition.split_once('=') {
+            Some((name, value)) => property(properties, name).as_deref() == Some(value),
+            None => false,
+        })
+}
+
+/// Checks the condition of a multipart part, which is either an object of properties that all
+/// have to match, where each can be a list of values separated by `|`, or an `OR` of conditions
+fn matches_when(when: &Value, properties: Option<&Value>) -> bool {
+    if let Some(any) = when.get("OR").and_then(|o| o.as_array()) {
+        return any.iter().any(|when| matches_when(when, properties));
+    }
+
+    match when.as_object() {
+        Some(conditions) => conditions.iter().all(|(name, values)| {
+            let values = match values {
+                Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+            let actual = property(properties, name);
+            values.split('|').any(|v| actual.as_deref() == Some(v))
+        }),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn models(parts: Option<Vec<WeightedVariants>>) -> Vec<Vec<String>> {
+        parts
+            .unwrap_or_default()
+            .into_iter()
+            .map(|part| part.into_iter().map(|v| v.model).collect())
+            .collect()
+    }
+
+    #[test]
+    fn picks_matching_variant() {
+        let json = json!({
+            "variants": {
+                "facing=north,lit=false": { "model": "block/furnace" },
+                "facing=north,lit=true": { "model": "minecraft:block/furnace_on", "y": 90 },
+            }
+        });
+        let props = json!({ "facing": "north", "lit": true });
+
+        let parts = parse_blockstate(&json, Some(&props));
+        assert_eq!(parts.as_ref().unwrap()[0][0].y, 90);
+        assert_eq!(models(parts), vec![vec!["minecraft:block/furnace_on"]]);
+    }
+
+    #[test]
+    fn empty_variant_matches_everything() {
+        let json = json!({
+            "variants": {
+                "": [
+                    { "model": "block/stone" },
+                    { "model": "block/stone_mirrored", "weight": 2 },
+                ]
+            }
+        });
+
+        assert_eq!(
+            models(parse_blockstate(&json, None)),
+            vec![vec![
+                "minecraft:block/stone",
+                "minecraft:block/stone_mirrored"
+            ]]
+        );
+    }
+
+    #[test]
+    fn adds_matching_multipart_parts() {
+        let json = json!({
+            "multipart": [
+                { "apply": { "model": "block/fence_post" } },
+                { "when": { "north": "true" }, "apply": { "model": "block/fence_side" } },
+                { "when": { "east": "true" }, "apply": { "model": "block/fence_side", "y": 90 } },
+                {
+                    "when": { "OR": [{ "south": "true" }, { "west": "side|up" }] },
+                    "apply": { "model": "block/fence_extra" }
+                },
+            ]
+        });
+        let props = json!({ "north": true, "east": false, "south": false, "west": "up" });
+
+        assert_eq!(
+            models(parse_blockstate(&json, Some(&props))),
+            vec![
+                vec!["minecraft:block/fence_post"],
+                vec!["minecraft:block/fence_side"],
+                vec!["minecraft:block/fence_extra"],
+            ]
+        );
+    }
+}
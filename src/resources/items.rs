@@ -0,0 +1,310 @@
+use std::{collections::HashMap, error::Error};
+
+use serde_json::{json, Map, Value};
+use simple_error::{bail, require_with};
+
+use super::{
+    as_object, assets,
+    block_models::{BlockModel, Display},
+    format_name, packs, Tint, BLOCK_MODELS_RAW,
+};
+
+/// How many parents an item model can have before it's assumed they go round in a loop
+const MAX_PARENTS: usize = 16;
+
+pub struct Item {
+    pub name: String,
+    pub id: u32,
+    pub max_stack_size: u32,
+    /// The colour tinted faces of the item's model are multiplied by, for blocks like grass
+    pub tint: Tint,
+    /// None if the item has no model or it couldn't be loaded
+    pub model: Option<ItemModel>,
+}
+
+/// How an item is drawn
+#[derive(Debug, Clone)]
+pub enum ItemModel {
+    /// A flat sprite, made of texture layers drawn on top of each other
+    Generated {
+        layers: Vec<String>,
+        display: HashMap<String, Display>,
+    },
+    /// Drawn like a block, either because it is one or the item's model has its own elements
+    Block(BlockModel),
+    /// Drawn by code for the specific item rather than with a model, like chests and shields,
+    /// which isn't supported yet
+    Builtin,
+}
+
+impl ItemModel {
+    /// How the model is shown in a position like `gui` or `firstperson_righthand`
+    pub fn get_display(&self, position: &str) -> Option<&Display> {
+        match self {
+            ItemModel::Generated { display, .. } => display.get(position),
+            ItemModel::Block(model) => model.get_display(position),
+            ItemModel::Builtin => None,
+        }
+    }
+}
+
+pub fn load_items() -> Result<HashMap<u32, Item>, Box<dyn Error>> {
+    let mut items = HashMap::new();
+    // Block models parsed as the parents of items, so they're only parsed once
+    let mut cache = HashMap::new();
+
+    let json = assets::load_json("items.min.json")?;
+    for (name, val) in as_object(&json, "items.min.json")?.iter() {
+        let id = require_with!(
+            val.get("id").and_then(|i| i.as_u64()),
+            "Item {} has no id",
+            name
+        ) as u32;
+
+        let model_name = format!("minecraft:item/{}", name.trim_start_matches("minecraft:"));
+        let model = if name == "minecraft:air" {
+            None
+        } else {
+            match resolve_item_model(&model_name, read_item_model, &mut cache) {
+                Ok(model) => Some(model),
+                Err(e) => {
+                    log::debug!("Couldn't load item model {}: {}", model_name, e);
+                    None
+                }
+            }
+        };
+
+        items.insert(
+            id,
+            Item {
+                name: format_name(name),
+                id,
+                max_stack_size: val
+                    .get("max_stack_size")
+                    .and_then(|s| s.as_u64())
+                    .unwrap_or(64) as u32,
+                tint: Tint::for_block(name),
+                model,
+            },
+        );
+    }
+
+    Ok(items)
+}
+
+/// Reads an item model like `minecraft:item/apple` from the resource packs, or from the game's
+/// models if no pack has it
+fn read_item_model(name: &str) -> Option<Value> {
+    let path = name.trim_start_matches("minecraft:");
+    packs::read_json(&format!("models/{}.json", path)).or_else(|| {
+        BLOCK_MODELS_RAW
+            .load()
+            .get(&format!("minecraft:{}", path))
+            .cloned()
+    })
+}
+
+/// Follows an item model's parents until it reaches either a sprite made from its textures
+/// (`builtin/generated`) or a block model. Textures and display transforms from models further
+/// down the chain are used over the ones from their parents.
+pub fn resolve_item_model(
+    name: &str,
+    read: impl Fn(&str) -> Option<Value>,
+    cache: &mut HashMap<String, BlockModel>,
+) -> Result<ItemModel, Box<dyn Error>> {
+    let mut textures = Map::new();
+    let mut display = Map::new();
+    let mut elements = None;
+
+    let mut name = name.to_string();
+    for _ in 0..MAX_PARENTS {
+        let json = require_with!(read(&name), "Missing item model {}", name);
+        if let Some(Value::Object(model_textures)) = json.get("textures") {
+            for (key, texture) in model_textures {
+                textures
+                    .entry(key.clone())
+                    .or_insert_with(|| texture.clone());
+            }
+        }
+        if let Some(Value::Object(model_display)) = json.get("display") {
+            for (position, transform) in model_display {
+                display
+                    .entry(position.clone())
+                    .or_insert_with(|| transform.clone());
+            }
+        }
+        if elements.is_none() {
+            elements = json.get("elements").cloned();
+        }
+
+        let parent = json
+            .get("parent")
+            .and_then(|p| p.as_str())
+            .map(|p| p.trim_start_matches("minecraft:"));
+        match parent {
+            Some("builtin/generated") => return generated_model(&textures, &display),
+            Some("builtin/entity") => return Ok(ItemModel::Builtin),
+            Some(parent) if parent.starts_with("item/") => name = format!("minecraft:{}", parent),
+            None if elements.is_none() => bail!("Item model {} has no parent or elements", name),
+            parent => {
+                // Anything else is a block model, which the item's textures and display
+                // transforms are put on top of
+                let mut model = json!({ "textures": textures, "display": display });
+                if let Some(parent) = parent {
+                    model["parent"] = json!(parent);
+                }
+                if let Some(elements) = elements {
+                    model["elements"] = elements;
+                }
+                return Ok(ItemModel::Block(BlockModel::parse(&model, Some(cache))?));
+            }
+        }
+    }
+
+    bail!("Item model {} has too many parents", name)
+}
+
+/// Makes a sprite model from the `layer0`, `layer1`, ... textures
+fn generated_model(
+    textures: &Map<String, Value>,
+    display: &Map<String, Value>,
+) -> Result<ItemModel, Box<dyn Error>> {
+    let mut layers = Vec::new();
+    while let Some(texture) = textures.get(&format!("layer{}", layers.len())) {
+        let mut texture = require_with!(texture.as_str(), "Invalid texture: {:?}", texture);
+        // Layers can refer to other textures of the model
+        if let Some(key) = texture.strip_prefix('#') {
+            texture = require_with!(
+                textures.get(key).and_then(|t| t.as_str()),
+                "Unresolved texture #{}",
+                key
+            );
+        }
+        if texture.contains(':') {
+            layers.push(texture.to_string());
+        } else {
+            layers.push(format!("minecraft:{}", texture));
+        }
+    }
+    if layers.is_empty() {
+        bail!("Generated item model has no layers");
+    }
+
+    let mut transforms = HashMap::new();
+    for (position, transform) in display {
+        transforms.insert(position.clone(), Display::parse(transform)?);
+    }
+
+    Ok(ItemModel::Generated {
+        layers,
+        display: transforms,
+    })
+}
+
+/// The path of a texture like `minecraft:item/apple` within the resource packs
+pub fn texture_path(texture: &str) -> String {
+    format!("textures/{}.png", texture.trim_start_matches("minecraft:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn models() -> HashMap<String, Value> {
+        let mut models = HashMap::new();
+        models.insert(
+            String::from("minecraft:item/generated"),
+            json!({
+                "parent": "builtin/generated",
+                "display": {
+                    "ground": { "scale": [0.5, 0.5, 0.5] },
+                    "firstperson_righthand": { "rotation": [0, -90, 25] }
+                }
+            }),
+        );
+        models.insert(
+            String::from("minecraft:item/handheld"),
+            json!({
+                "parent": "item/generated",
+                "display": { "firstperson_righthand": { "rotation": [0, -90, 25], "scale": [0.68, 0.68, 0.68] } }
+            }),
+        );
+        models.insert(
+            String::from("minecraft:item/stick"),
+            json!({ "parent": "minecraft:item/handheld", "textures": { "layer0": "item/stick" } }),
+        );
+        models.insert(
+            String::from("minecraft:item/potion"),
+            json!({
+                "parent": "item/generated",
+                "textures": { "layer0": "item/potion_overlay", "layer1": "#bottle", "bottle": "item/potion" }
+            }),
+        );
+        models.insert(
+            String::from("minecraft:item/stone"),
+            json!({
+                "parent": "block/cube",
+                "textures": { "up": "block/stone", "down": "block/stone" },
+                "display": { "gui": { "rotation": [30, 225, 0], "scale": [0.625, 0.625, 0.625] } }
+            }),
+        );
+        models.insert(
+            String::from("minecraft:item/chest"),
+            json!({ "parent": "builtin/entity" }),
+        );
+        models.insert(
+            String::from("minecraft:item/loop"),
+            json!({ "parent": "item/loop" }),
+        );
+        models
+    }
+
+    fn resolve(name: &str) -> Result<ItemModel, Box<dyn Error>> {
+        let models = models();
+        resolve_item_model(name, |name| models.get(name).cloned(), &mut HashMap::new())
+    }
+
+    #[test]
+    fn generated_models_use_their_layers() {
+        match resolve("minecraft:item/stick").unwrap() {
+            ItemModel::Generated { layers, display } => {
+                assert_eq!(layers, vec!["minecraft:item/stick"]);
+                // The handheld transform is used over the generated one
+                assert_eq!(display["firstperson_righthand"].scale.x, 0.68);
+                assert_eq!(display["ground"].scale.x, 0.5);
+            }
+            model => panic!("Wrong model {:?}", model),
+        }
+
+        match resolve("minecraft:item/potion").unwrap() {
+            ItemModel::Generated { layers, .. } => assert_eq!(
+                layers,
+                vec!["minecraft:item/potion_overlay", "minecraft:item/potion"]
+            ),
+            model => panic!("Wrong model {:?}", model),
+        }
+    }
+
+    #[test]
+    fn block_items_use_block_models() {
+        let model = resolve("minecraft:item/stone").unwrap();
+        assert!(model.get_display("gui").is_some());
+        match model {
+            ItemModel::Block(model) => {
+                assert_eq!(model.get_face_textures().len(), 6);
+                assert_eq!(model.resolve_texture("#up"), Some("block/stone"));
+            }
+            model => panic!("Wrong model {:?}", model),
+        }
+    }
+
+    #[test]
+    fn unsupported_models() {
+        assert!(matches!(
+            resolve("minecraft:item/chest").unwrap(),
+            ItemModel::Builtin
+        ));
+        assert!(resolve("minecraft:item/missing").is_err());
+        assert!(resolve("minecraft:item/loop").is_err());
+    }
+}
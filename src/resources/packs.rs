@@ -0,0 +1,302 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use image::RgbaImage;
+use lazy_static::lazy_static;
+use serde_json::Value;
+use simple_error::bail;
+use zip::ZipArchive;
+
+use super::assets;
+
+/// Where resource packs picked in the options are kept
+pub const PACKS_DIR: &str = "resourcepacks";
+
+lazy_static! {
+    static ref PACKS: RwLock<PackStack> = RwLock::new(PackStack::new(&[]));
+}
+
+enum PackSource {
+    Folder(PathBuf),
+    Zip(Mutex<ZipArchive<File>>),
+}
+
+/// A folder or zip file of textures, models and blockstates which replace the game's ones
+pub struct ResourcePack {
+    name: String,
+    source: PackSource,
+    /// Where the `minecraft` namespace is within the pack
+    root: &'static str,
+}
+
+impl ResourcePack {
+    /// The game's assets, which every other pack is put on top of. They're laid out like the
+    /// inside of the `minecraft` namespace.
+    pub fn vanilla() -> ResourcePack {
+        ResourcePack {
+            name: String::from("Vanilla"),
+            source: PackSource::Folder(assets::get_dir()),
+            root: "",
+        }
+    }
+
+    /// Opens a resource pack, either a folder or a zip file, which has to have a `pack.mcmeta`
+    pub fn open(path: &Path) -> Result<ResourcePack, Box<dyn Error>> {
+        let source = if path.is_dir() {
+            PackSource::Folder(path.to_path_buf())
+        } else {
+            PackSource::Zip(Mutex::new(ZipArchive::new(File::open(path)?)?))
+        };
+        let pack = ResourcePack {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            source,
+            root: "",
+        };
+
+        if pack.read("pack.mcmeta").is_none() {
+            bail!(
+                "{} isn't a resource pack, it has no pack.mcmeta",
+                path.display()
+            );
+        }
+        Ok(ResourcePack {
+            root: "assets/minecraft/",
+            ..pack
+        })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Reads a file from the pack, with a path within the `minecraft` namespace like
+    /// `textures/block/stone.png`
+    pub fn read(&self, path: &str) -> Option<Vec<u8>> {
+        let path = format!("{}{}", self.root, path);
+        match &self.source {
+            PackSource::Folder(dir) => std::fs::read(dir.join(path)).ok(),
+            PackSource::Zip(zip) => {
+                let mut zip = zip.lock().unwrap();
+                let mut file = zip.by_name(&path).ok()?;
+                let mut data = Vec::new();
+                file.read_to_end(&mut data).ok()?;
+                Some(data)
+            }
+        }
+    }
+
+    /// Names of the files directly inside a folder of the pack
+    pub fn list(&self, dir: &str) -> Vec<String> {
+        let dir = format!("{}{}/", self.root, dir.trim_end_matches('/'));
+        match &self.source {
+            PackSource::Folder(root) => std::fs::read_dir(root.join(&dir))
+                .map(|files| {
+                    files
+                        .filter_map(|f| f.ok())
+                        .filter(|f| f.path().is_file())
+                        .map(|f| f.file_name().to_string_lossy().to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            PackSource::Zip(zip) => zip
+                .lock()
+                .unwrap()
+                .file_names()
+                .filter_map(|name| name.strip_prefix(&dir))
+                .filter(|name| !name.is_empty() && !name.contains('/'))
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+/// The game's assets with resource packs on top, where files in later packs are used over the
+/// same files in earlier ones
+pub struct PackStack {
+    packs: Vec<ResourcePack>,
+}
+
+impl PackStack {
+    /// Opens the given packs on top of the game's assets. Packs that can't be opened are skipped.
+    pub fn new(paths: &[PathBuf]) -> PackStack {
+        let mut packs = vec![ResourcePack::vanilla()];
+        for path in paths {
+            match ResourcePack::open(path) {
+                Ok(pack) => packs.push(pack),
+                Err(e) => log::error!("Couldn't open resource pack {}: {}", path.display(), e),
+            }
+        }
+        PackStack { packs }
+    }
+
+    pub fn read(&self, path: &str) -> Option<Vec<u8>> {
+        self.packs.iter().rev().find_map(|pack| pack.read(path))
+    }
+
+    /// Names of the files in a folder of any of the packs
+    pub fn list(&self, dir: &str) -> Vec<String> {
+        let mut files: Vec<_> = self.packs.iter().flat_map(|pack| pack.list(dir)).collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    pub fn get_names(&self) -> Vec<&str> {
+        self.packs.iter().map(|pack| pack.get_name()).collect()
+    }
+}
+
+/// Switches to a different set of resource packs. Resources have to be reloaded afterwards to
+/// use them.
+pub fn set_packs(paths: &[PathBuf]) {
+    let stack = PackStack::new(paths);
+    log::info!("Using resource packs: {}", stack.get_names().join(", "));
+    *PACKS.write().unwrap() = stack;
+}
+
+/// Reads a file from whichever resource pack has it, with a path within the `minecraft`
+/// namespace like `textures/block/stone.png`
+pub fn read(path: &str) -> Option<Vec<u8>> {
+    PACKS.read().unwrap().read(path)
+}
+
+/// Names of the files in a folder of any of the resource packs
+pub fn list(dir: &str) -> Vec<String> {
+    PACKS.read().unwrap().list(dir)
+}
+
+/// Reads a json file from the resource packs, logging it if it can't be parsed
+pub fn read_json(path: &str) -> Option<Value> {
+    let data = read(path)?;
+    match serde_json::from_slice(&data) {
+        Ok(json) => Some(json),
+        Err(e) => {
+            log::error!("Couldn't parse {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Loads a texture from the resource packs
+pub fn load_image(path: &str) -> Result<RgbaImage, Box<dyn Error>> {
+    match read(path) {
+        Some(data) => Ok(image::load_from_memory(&data)?.to_rgba8()),
+        None => bail!("{} isn't in any resource pack", path),
+    }
+}
+
+/// Names of the zip files and pack folders in the resource packs folder
+pub fn find_packs() -> Vec<String> {
+    let mut packs: Vec<_> = std::fs::read_dir(PACKS_DIR)
+        .map(|files| {
+            files
+                .filter_map(|f| f.ok())
+                .filter(|f| {
+                    let path = f.path();
+                    path.join("pack.mcmeta").is_file() || path.extension() == Some("zip".as_ref())
+                })
+                .map(|f| f.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    packs.sort();
+    packs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("minceraft-packs-{}", name));
+        std::fs::remove_dir_all(&path).ok();
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    fn folder_pack(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = temp_path(name);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn zip_pack(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = temp_path(name);
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, contents) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_folder_and_zip_packs() {
+        let files = [
+            ("pack.mcmeta", "{}"),
+            ("assets/minecraft/blockstates/stone.json", "stone"),
+            ("assets/minecraft/textures/block/a.png", "a"),
+            ("assets/minecraft/textures/block/b.png", "b"),
+            ("assets/minecraft/textures/block/nested/c.png", "c"),
+        ];
+        for path in [folder_pack("folder", &files), zip_pack("zip.zip", &files)] {
+            let pack = ResourcePack::open(&path).unwrap();
+            assert_eq!(pack.read("blockstates/stone.json").unwrap(), b"stone");
+            assert!(pack.read("blockstates/dirt.json").is_none());
+
+            let mut list = pack.list("textures/block");
+            list.sort();
+            assert_eq!(list, vec!["a.png", "b.png"]);
+        }
+    }
+
+    #[test]
+    fn needs_pack_mcmeta() {
+        let path = folder_pack("no-mcmeta", &[("assets/minecraft/a.json", "{}")]);
+        assert!(ResourcePack::open(&path).is_err());
+    }
+
+    #[test]
+    fn later_packs_are_used_first() {
+        let low = folder_pack(
+            "low",
+            &[
+                ("pack.mcmeta", "{}"),
+                ("assets/minecraft/textures/block/a.png", "low"),
+                ("assets/minecraft/textures/block/b.png", "low"),
+            ],
+        );
+        let high = zip_pack(
+            "high.zip",
+            &[
+                ("pack.mcmeta", "{}"),
+                ("assets/minecraft/textures/block/a.png", "high"),
+            ],
+        );
+        let stack = PackStack::new(&[low, high, temp_path("missing")]);
+
+        assert_eq!(stack.get_names().len(), 3);
+        assert_eq!(stack.read("textures/block/a.png").unwrap(), b"high");
+        assert_eq!(stack.read("textures/block/b.png").unwrap(), b"low");
+        assert!(stack
+            .list("textures/block")
+            .contains(&String::from("a.png")));
+    }
+}
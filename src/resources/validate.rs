@@ -0,0 +1,280 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    path::PathBuf,
+};
+
+use serde_json::Value;
+
+use super::{
+    assets, block_models::BlockModel, entity_size, get_load_errors, packs, BLOCKS,
+    BLOCK_MODELS_PARSED, BLOCK_MODELS_RAW, BLOCK_TEXTURES,
+};
+
+/// The command line argument that checks the assets instead of starting the game
+pub const VALIDATE_COMMAND: &str = "validate-assets";
+
+/// Problems with the assets that the game would otherwise only find (and log) while it's running
+#[derive(Debug, Default)]
+pub struct AssetReport {
+    /// Files that couldn't be loaded at all
+    pub load_errors: Vec<String>,
+    /// Models which couldn't be parsed, and why
+    pub unparsed_models: Vec<(String, String)>,
+    /// Models used by blocks that have faces with a `#texture` the model never defines
+    pub unresolved_textures: Vec<(String, String)>,
+    /// Textures used by blocks' models which aren't in any resource pack, and a model using each
+    pub missing_textures: Vec<(String, String)>,
+    /// Block states which have no models or use models that don't exist
+    pub blocks_without_models: Vec<(u32, String)>,
+    /// Entities which don't say how big they are
+    pub entities_without_size: Vec<(u32, String)>,
+}
+
+impl AssetReport {
+    /// Loads every asset from the current assets folder and resource packs and checks them
+    pub fn check() -> AssetReport {
+        let mut report = AssetReport {
+            load_errors: get_load_errors(),
+            ..Default::default()
+        };
+
+        report.check_models();
+        report.check_blocks();
+        if let Ok(json) = assets::load_json("entities.min.json") {
+            report.entities_without_size = entities_without_size(&json);
+        }
+
+        report
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.count() == 0
+    }
+
+    /// How many problems were found
+    pub fn count(&self) -> usize {
+        self.load_errors.len()
+            + self.unparsed_models.len()
+            + self.unresolved_textures.len()
+            + self.missing_textures.len()
+            + self.blocks_without_models.len()
+            + self.entities_without_size.len()
+    }
+
+    /// Parses every model again to find the ones that fail, which the game skips over
+    fn check_models(&mut self) {
+        let mut cache = HashMap::new();
+        for (name, json) in BLOCK_MODELS_RAW.load().iter() {
+            if let Err(e) = BlockModel::parse(json, Some(&mut cache)) {
+                self.unparsed_models.push((name.clone(), e.to_string()));
+            }
+        }
+        self.unparsed_models.sort();
+    }
+
+    fn check_blocks(&mut self) {
+        let (all_blocks, models, block_textures) = (
+            BLOCKS.load(),
+            BLOCK_MODELS_PARSED.load(),
+            BLOCK_TEXTURES.load(),
+        );
+        let mut used_models = BTreeSet::new();
+
+        let mut blocks: Vec<_> = all_blocks.values().collect();
+        blocks.sort_by_key(|b| b.id);
+        for block in blocks {
+            // Air isn't drawn and fluids are drawn without models
+            if block.is_air() || block.is_fluid() {
+                continue;
+            }
+
+            let parts = match &block.models {
+                Some(parts) => parts,
+                None => {
+                    self.blocks_without_models
+                        .push((block.id, format!("{} has no models", block.name)));
+                    continue;
+                }
+            };
+            for variant in parts.iter().flatten() {
+                if models.contains_key(&variant.model) {
+                    used_models.insert(variant.model.as_str());
+                } else {
+                    self.blocks_without_models.push((
+                        block.id,
+                        format!("{} uses missing model {}", block.name, variant.model),
+                    ));
+                }
+            }
+        }
+
+        // Only models blocks use are checked for textures, since the models they inherit from
+        // leave their textures for them to fill in
+        let mut missing = BTreeMap::new();
+        for name in used_models {
+            let model = &models[name];
+            let textures: BTreeSet<_> = model.get_face_textures().into_iter().collect();
+            for texture in textures {
+                match model.resolve_texture(texture) {
+                    Some(texture) => {
+                        let exists = block_textures.contains_key(texture)
+                            || block_textures.contains_key(&format!("minecraft:{}", texture));
+                        if !exists {
+                            missing
+                                .entry(texture.to_string())
+                                .or_insert_with(|| name.to_string());
+                        }
+                    }
+                    None => self
+                        .unresolved_textures
+                        .push((name.to_string(), texture.to_string())),
+                }
+            }
+        }
+        self.missing_textures = missing.into_iter().collect();
+    }
+}
+
+impl fmt::Display for AssetReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn section(f: &mut fmt::Formatter, title: &str, lines: Vec<String>) -> fmt::Result {
+            if lines.is_empty() {
+                return Ok(());
+            }
+            writeln!(f, "{} ({}):", title, lines.len())?;
+            for line in lines {
+                writeln!(f, "    {}", line)?;
+            }
+            Ok(())
+        }
+
+        section(f, "Couldn't load", self.load_errors.clone())?;
+        section(
+            f,
+            "Models that couldn't be parsed",
+            self.unparsed_models
+                .iter()
+                .map(|(model, e)| format!("{}: {}", model, e))
+                .collect(),
+        )?;
+        section(
+            f,
+            "Unresolved texture references",
+            self.unresolved_textures
+                .iter()
+                .map(|(model, texture)| format!("{} in {}", texture, model))
+                .collect(),
+        )?;
+        section(
+            f,
+            "Missing textures",
+            self.missing_textures
+                .iter()
+                .map(|(texture, model)| format!("{} (used by {})", texture, model))
+                .collect(),
+        )?;
+        section(
+            f,
+            "Block states without models",
+            self.blocks_without_models
+                .iter()
+                .map(|(id, problem)| format!("{}: {}", id, problem))
+                .collect(),
+        )?;
+        section(
+            f,
+            "Entities without a size",
+            self.entities_without_size
+                .iter()
+                .map(|(id, name)| format!("{}: {}", id, name))
+                .collect(),
+        )?;
+
+        if self.is_ok() {
+            writeln!(f, "No problems found")
+        } else {
+            writeln!(f, "{} problems found", self.count())
+        }
+    }
+}
+
+/// The ids and names of the entities in `entities.min.json` without a width or height
+fn entities_without_size(json: &Value) -> Vec<(u32, String)> {
+    let mut entities: Vec<_> = json
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(_, val)| entity_size(val).is_none())
+        .filter_map(|(name, val)| Some((val.get("id")?.as_u64()? as u32, name.clone())))
+        .collect();
+    entities.sort();
+    entities
+}
+
+/// Runs the asset check from the command line, with the arguments after the command being the
+/// assets folder followed by any resource packs to check on top of it. Gives the exit code, which
+/// is 1 if anything is wrong.
+pub fn run(args: &[String]) -> i32 {
+    if let Some(dir) = args.first() {
+        assets::set_dir(dir);
+    }
+    let packs: Vec<_> = args.iter().skip(1).map(PathBuf::from).collect();
+    packs::set_packs(&packs);
+
+    let report = AssetReport::check();
+    print!("{}", report);
+    if report.is_ok() {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn finds_entities_without_size() {
+        let json = json!({
+            "minecraft:pig": { "id": 3, "width": 0.9, "height": 0.9 },
+            "minecraft:ghost": { "id": 7, "width": 0.5 },
+            "minecraft:marker": { "id": 5 },
+            "minecraft:not_an_entity": {},
+        });
+
+        assert_eq!(
+            entities_without_size(&json),
+            vec![
+                (5, String::from("minecraft:marker")),
+                (7, String::from("minecraft:ghost"))
+            ]
+        );
+    }
+
+    #[test]
+    fn report_counts_every_problem() {
+        let report = AssetReport {
+            unparsed_models: vec![(String::from("minecraft:block/a"), String::from("bad"))],
+            missing_textures: vec![
+                (String::from("block/b"), String::from("minecraft:block/a")),
+                (String::from("block/c"), String::from("minecraft:block/a")),
+            ],
+            ..Default::default()
+        };
+
+        assert!(!report.is_ok());
+        assert_eq!(report.count(), 3);
+        let text = report.to_string();
+        assert!(text.contains("Missing textures (2):"), "{}", text);
+        assert!(
+            text.contains("block/c (used by minecraft:block/a)"),
+            "{}",
+            text
+        );
+        assert!(AssetReport::default().to_string().contains("No problems"));
+    }
+}
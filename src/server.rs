@@ -1,34 +1,54 @@
-use std::{collections::HashMap, f32::consts::PI, ops::AddAssign};
+use std::{collections::HashMap, convert::TryFrom, ops::AddAssign, path::PathBuf, sync::Arc};
 
 use egui_winit::winit::event::VirtualKeyCode;
-use glam::{IVec2, Vec3};
+use glam::{IVec2, IVec3, Vec3};
 use glium_app::context::Context;
+use image::RgbaImage;
 use lazy_static::__Deref;
 use log::{debug, error, info};
 use mcproto_rs::{
     types::{self, EntityLocation, VarInt},
     uuid::UUID4,
     v1_16_3::{
-        ClientStatusAction, Difficulty, GameMode, PlayClientChatMessageSpec,
-        PlayClientPlayerPositionAndRotationSpec, PlayClientSettingsSpec, PlayClientStatusSpec,
-        PlayTeleportConfirmSpec, PlayerInfoAction,
+        ClientStatusAction, Difficulty, EntityAnimationKind, GameMode, PlayClientChatMessageSpec,
+        PlayClientHeldItemChangeSpec, PlayClientPlayerPositionAndRotationSpec,
+        PlayClientSettingsSpec, PlayClientStatusSpec, PlayEffectSpec, PlayExplosionSpec,
+        PlayParticleSpec, PlayResourcePackStatusSpec, PlayTeleportConfirmSpec, PlayerInfoAction,
+        ResourcePackStatusKind,
     },
 };
 
 use crate::{
-    gui::{chat_windows, info_windows, pause_windows},
+    gui::{chat_windows, info_windows, pause_windows, resource_pack_prompt},
+    inventory::{Inventory, ItemStack, HOTBAR_SLOTS},
     network::{encode, NetworkChannel, NetworkCommand, PacketType},
+    particles::{ParticleKind, Particles},
+    renderer::camera::Camera,
     resources::PLAYER_INDEX,
     settings::Settings,
-    world::chunks::Chunk,
+    world::{chunks::Chunk, WorldCoords},
     WindowManager,
 };
 
-use self::remote_player::RemotePlayer;
+use self::{
+    remote_player::RemotePlayer,
+    resource_pack::{DownloadResult, PackOffer, ServerPack},
+    skins::{HttpFetcher, SkinLoader},
+};
 
 use super::{chat::Chat, entities::Entity, player::Player, world::World};
 
 pub mod remote_player;
+pub mod resource_pack;
+pub mod skins;
+
+/// Converts an angle sent by the server, in 256ths of a turn, to degrees between -180 and 180
+fn angle_to_degrees(angle: &types::Angle) -> f32 {
+    angle.value as i8 as f32 * 360.0 / 256.0
+}
+
+/// How far away the player can target blocks from
+pub const REACH_DISTANCE: f32 = 5.0;
 
 pub struct Server {
     network_destination: String,
@@ -38,14 +58,20 @@ pub struct Server {
 
     world_time: i64,
     day_time: i64,
+    /// How far through the current tick the client is, from 0 to 1
+    partial_tick: f32,
 
     player: Player,
+    inventory: Inventory,
     chat: Chat,
 
     world: World,
 
     entities: HashMap<i32, Entity>,
     players: HashMap<UUID4, RemotePlayer>,
+    skins: SkinLoader,
+    particles: Particles,
+    resource_pack: ServerPack,
 
     difficulty: Difficulty,
     difficulty_locked: bool,
@@ -82,14 +108,19 @@ impl Server {
 
             world_time: 0,
             day_time: 0,
+            partial_tick: 0.0,
 
             player: Player::new(),
+            inventory: Inventory::new(),
             chat: Chat::new(),
 
             world: World::new(),
 
             entities: HashMap::new(),
             players: HashMap::new(),
+            skins: SkinLoader::new(Arc::new(HttpFetcher)),
+            particles: Particles::new(),
+            resource_pack: ServerPack::None,
 
             difficulty: Difficulty::Easy,
             difficulty_locked: false,
@@ -116,10 +147,18 @@ impl Server {
         self.day_time
     }
 
+    pub fn get_partial_tick(&self) -> f32 {
+        self.partial_tick
+    }
+
     pub fn get_player(&self) -> &Player {
         &self.player
     }
 
+    pub fn get_inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
     pub fn get_chat(&self) -> &Chat {
         &self.chat
     }
@@ -132,10 +171,18 @@ impl Server {
         &self.world
     }
 
+    pub fn get_world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
     pub fn get_entities(&self) -> &HashMap<i32, Entity> {
         &self.entities
     }
 
+    pub fn get_particles(&self) -> &Particles {
+        &self.particles
+    }
+
     pub fn get_difficulty(&self) -> Difficulty {
         self.difficulty.clone()
     }
@@ -160,14 +207,44 @@ impl Server {
         &self.players
     }
 
-    /// Generates a sky colour based on a provided base colour and the current time of day on the
-    /// server
-    pub fn get_sky_colour(&self, col: &[f32; 3]) -> Vec3 {
-        const LIGHTEST: i64 = 9_000;
-        let lerp = (((self.day_time - LIGHTEST) as f32 / 24_000.0) * PI * 2.0).cos() / 2.0 + 0.5;
-        let dark = Vec3::new(0.001, 0.002, 0.005);
-        let light = Vec3::from(*col);
-        dark.lerp(light, lerp)
+    /// The resource pack the server sent, once it's been downloaded
+    pub fn get_resource_pack(&self) -> Option<&PathBuf> {
+        self.resource_pack.get_path()
+    }
+
+    /// Whether the server's resource pack has finished downloading and resources need reloading
+    /// to use it
+    pub fn is_resource_pack_downloaded(&self) -> bool {
+        matches!(self.resource_pack, ServerPack::Downloaded(_))
+    }
+
+    /// Lets the server know its resource pack is being used, once resources have been reloaded
+    pub fn resource_pack_loaded(&mut self) {
+        if let ServerPack::Downloaded(path) = &self.resource_pack {
+            self.resource_pack = ServerPack::Loaded(path.clone());
+            self.send_resource_pack_status(ResourcePackStatusKind::Loaded);
+        }
+    }
+
+    fn send_resource_pack_status(&self, status: ResourcePackStatusKind) {
+        self.send_packet(encode(PacketType::PlayResourcePackStatus(
+            PlayResourcePackStatusSpec { status },
+        )));
+    }
+
+    /// Player skins that have finished downloading since this was last called
+    pub fn take_loaded_skins(&self) -> Vec<(UUID4, RgbaImage)> {
+        self.skins.take_loaded()
+    }
+
+    /// Returns the block the player is looking at (if any is within reach) and the normal of the
+    /// face being looked at
+    pub fn get_targeted_block(&self) -> Option<(WorldCoords, IVec3)> {
+        self.world.raycast(
+            self.player.get_eye_position(),
+            self.player.get_orientation().get_look_vector(),
+            REACH_DISTANCE,
+        )
     }
 
     /// Attempts to send a packet over the provided (possible) network channel
@@ -193,6 +270,11 @@ impl Server {
     }
 
     pub fn should_grab_mouse(&self) -> bool {
+        // The mouse is needed to answer the resource pack prompt
+        if let ServerPack::Offered(_) = self.resource_pack {
+            return false;
+        }
+
         match self.input_state {
             InputState::Playing => true,
             InputState::Paused => false,
@@ -207,6 +289,20 @@ impl Server {
             chat_windows::render_inactive(self, gui_ctx);
         }
 
+        if let ServerPack::Offered(_) = self.resource_pack {
+            match resource_pack_prompt::render(gui_ctx) {
+                Some(true) => {
+                    self.send_resource_pack_status(ResourcePackStatusKind::Accepted);
+                    self.resource_pack.accept();
+                }
+                Some(false) => {
+                    self.send_resource_pack_status(ResourcePackStatusKind::Declined);
+                    self.resource_pack = ServerPack::None;
+                }
+                None => {}
+            }
+        }
+
         match self.input_state {
             InputState::Playing => {}
             InputState::Paused => match pause_windows::render(gui_ctx, windows) {
@@ -221,13 +317,31 @@ impl Server {
         }
     }
 
-    pub fn update(&mut self, ctx: &Context, delta: f32, settings: &mut Settings) {
-        self.world.generate_meshes(&ctx.dis, true);
+    pub fn update(&mut self, ctx: &Context, delta: f32, settings: &mut Settings, cam: &Camera) {
+        self.world.generate_meshes(&ctx.dis, cam, true);
+
+        // Time moves on between the updates the server sends, unless the daylight cycle is
+        // stopped which the server shows with a negative time of day
+        self.partial_tick += delta * 20.0;
+        while self.partial_tick >= 1.0 {
+            self.partial_tick -= 1.0;
+            self.world_time += 1;
+            if self.day_time >= 0 {
+                self.day_time += 1;
+            }
+        }
+
+        self.player.update_swing(delta);
 
         // Update entities
         for ent in self.entities.values_mut() {
             ent.update(delta);
         }
+        self.particles
+            .update(delta, &self.world, settings.max_particles);
+        if let Some(DownloadResult::Failed) = self.resource_pack.poll_download() {
+            self.send_resource_pack_status(ResourcePackStatusKind::FailedDownload);
+        }
 
         // Handle input
         match self.input_state {
@@ -267,12 +381,46 @@ impl Server {
             self.chat.set_current_message(String::from("/"));
         } else if ctx.keyboard.pressed_this_frame(&VirtualKeyCode::Tab) {
             self.input_state = InputState::ShowingInfo;
+        } else if ctx.keyboard.is_pressed(&VirtualKeyCode::F3)
+            && ctx.keyboard.pressed_this_frame(&VirtualKeyCode::B)
+        {
+            settings.show_hitboxes = !settings.show_hitboxes;
         }
 
+        // Digging and attacking aren't sent to the server yet, but the arm still swings
+        if ctx.mouse.is_pressed(0) {
+            self.player.swing_arm();
+        }
+
+        self.handle_hotbar_keys(ctx);
         self.handle_keyboard_movement(ctx, delta, settings);
         self.handle_mouse_movement(ctx, delta, settings);
     }
 
+    /// Selects a hotbar slot with the number keys
+    fn handle_hotbar_keys(&mut self, ctx: &Context) {
+        const KEYS: [VirtualKeyCode; HOTBAR_SLOTS] = [
+            VirtualKeyCode::Key1,
+            VirtualKeyCode::Key2,
+            VirtualKeyCode::Key3,
+            VirtualKeyCode::Key4,
+            VirtualKeyCode::Key5,
+            VirtualKeyCode::Key6,
+            VirtualKeyCode::Key7,
+            VirtualKeyCode::Key8,
+            VirtualKeyCode::Key9,
+        ];
+
+        for (slot, key) in KEYS.iter().enumerate() {
+            if ctx.keyboard.pressed_this_frame(key) && slot != self.inventory.get_selected() {
+                self.inventory.set_selected(slot);
+                self.send_packet(encode(PacketType::PlayClientHeldItemChange(
+                    PlayClientHeldItemChangeSpec { slot: slot as i16 },
+                )));
+            }
+        }
+    }
+
     fn handle_paused_state(&mut self, ctx: &Context, delta: f32, settings: &mut Settings) {
         if ctx.keyboard.pressed_this_frame(&VirtualKeyCode::Escape) {
             self.input_state = InputState::Playing;
@@ -403,6 +551,14 @@ impl Server {
                         info!("Changed difficulty: {}", pack.locked);
                     }
 
+                    PacketType::PlayResourcePackSend(pack) => {
+                        info!("Server sent resource pack: {}", pack.url);
+                        self.resource_pack = ServerPack::Offered(PackOffer {
+                            url: pack.url,
+                            hash: pack.hash,
+                        });
+                    }
+
                     PacketType::PlayTimeUpdate(pack) => {
                         self.world_time = pack.world_age;
                         self.day_time = pack.time_of_day;
@@ -450,69 +606,63 @@ impl Server {
                     }
 
                     PacketType::PlaySpawnPlayer(pack) => {
-                        self.entities.insert(
+                        if let Some(entity) = Entity::new_with_values(
                             pack.entity_id.0,
-                            Entity::new_with_values(
-                                pack.entity_id.0,
-                                pack.uuid,
-                                PLAYER_INDEX as u32,
-                                0,
-                                pack.location.position.x as f32,
-                                pack.location.position.y as f32,
-                                pack.location.position.z as f32,
-                                pack.location.rotation.yaw.value as f32 / 255.0,
-                                pack.location.rotation.pitch.value as f32 / 255.0,
-                                pack.location.rotation.pitch.value as f32 / 255.0,
-                                0.0,
-                                0.0,
-                                0.0,
-                            ),
-                        );
+                            pack.uuid,
+                            PLAYER_INDEX as u32,
+                            0,
+                            pack.location.position.x as f32,
+                            pack.location.position.y as f32,
+                            pack.location.position.z as f32,
+                            angle_to_degrees(&pack.location.rotation.yaw),
+                            angle_to_degrees(&pack.location.rotation.pitch),
+                            angle_to_degrees(&pack.location.rotation.pitch),
+                            0.0,
+                            0.0,
+                            0.0,
+                        ) {
+                            self.entities.insert(pack.entity_id.0, entity);
+                        }
                     }
 
                     PacketType::PlaySpawnLivingEntity(pack) => {
-                        match self.entities.insert(
+                        if let Some(entity) = Entity::new_with_values(
                             pack.entity_id.0,
-                            Entity::new_with_values(
-                                pack.entity_id.0,
-                                pack.entity_uuid,
-                                pack.entity_type.0 as u32,
-                                0,
-                                pack.location.position.x as f32,
-                                pack.location.position.y as f32,
-                                pack.location.position.z as f32,
-                                pack.location.rotation.yaw.value as f32 / 255.0,
-                                pack.location.rotation.pitch.value as f32 / 255.0,
-                                pack.head_pitch.value as f32 / 255.0,
-                                pack.velocity.x as f32 / 400.0,
-                                pack.velocity.y as f32 / 400.0,
-                                pack.velocity.z as f32 / 400.0,
-                            ),
+                            pack.entity_uuid,
+                            pack.entity_type.0 as u32,
+                            0,
+                            pack.location.position.x as f32,
+                            pack.location.position.y as f32,
+                            pack.location.position.z as f32,
+                            angle_to_degrees(&pack.location.rotation.yaw),
+                            angle_to_degrees(&pack.location.rotation.pitch),
+                            angle_to_degrees(&pack.head_pitch),
+                            pack.velocity.x as f32 / 400.0,
+                            pack.velocity.y as f32 / 400.0,
+                            pack.velocity.z as f32 / 400.0,
                         ) {
-                            Some(_) => {}
-                            None => {}
+                            self.entities.insert(pack.entity_id.0, entity);
                         }
                     }
 
                     PacketType::PlaySpawnEntity(pack) => {
-                        self.entities.insert(
+                        if let Some(entity) = Entity::new_with_values(
                             pack.entity_id.0,
-                            Entity::new_with_values(
-                                pack.entity_id.0,
-                                pack.object_uuid,
-                                pack.entity_type.0 as u32,
-                                pack.data,
-                                pack.position.x as f32,
-                                pack.position.y as f32,
-                                pack.position.z as f32,
-                                pack.yaw.value as f32 / 255.0,
-                                pack.pitch.value as f32 / 255.0,
-                                0.0,
-                                pack.velocity.x as f32 / 400.0,
-                                pack.velocity.y as f32 / 400.0,
-                                pack.velocity.z as f32 / 400.0,
-                            ),
-                        );
+                            pack.object_uuid,
+                            pack.entity_type.0 as u32,
+                            pack.data,
+                            pack.position.x as f32,
+                            pack.position.y as f32,
+                            pack.position.z as f32,
+                            angle_to_degrees(&pack.yaw),
+                            angle_to_degrees(&pack.pitch),
+                            0.0,
+                            pack.velocity.x as f32 / 400.0,
+                            pack.velocity.y as f32 / 400.0,
+                            pack.velocity.z as f32 / 400.0,
+                        ) {
+                            self.entities.insert(pack.entity_id.0, entity);
+                        }
                     }
 
                     PacketType::PlayDestroyEntities(pack) => {
@@ -524,14 +674,14 @@ impl Server {
                     PacketType::PlayEntityPosition(pack) => {
                         match self.entities.get_mut(&pack.entity_id.0) {
                             Some(ent) => {
-                                let new_pos = ent.last_pos
-                                    + Vec3::new(
-                                        (pack.delta.x as f32) / 4096.0,
-                                        (pack.delta.y as f32) / 4096.0,
-                                        (pack.delta.z as f32) / 4096.0,
-                                    );
-                                ent.pos = new_pos;
-                                ent.last_pos = new_pos;
+                                ent.move_to(
+                                    ent.get_server_pos()
+                                        + Vec3::new(
+                                            (pack.delta.x as f32) / 4096.0,
+                                            (pack.delta.y as f32) / 4096.0,
+                                            (pack.delta.z as f32) / 4096.0,
+                                        ),
+                                );
                             }
                             None => {}
                         }
@@ -540,17 +690,17 @@ impl Server {
                     PacketType::PlayEntityPositionAndRotation(pack) => {
                         match self.entities.get_mut(&pack.entity_id.0) {
                             Some(ent) => {
-                                let new_pos = ent.last_pos
-                                    + Vec3::new(
-                                        (pack.delta.position.x as f32) / 4096.0,
-                                        (pack.delta.position.y as f32) / 4096.0,
-                                        (pack.delta.position.z as f32) / 4096.0,
-                                    );
-                                ent.pos = new_pos;
-                                ent.last_pos = new_pos;
-                                ent.ori.set(
-                                    pack.delta.rotation.yaw.value as f32 / 256.0,
-                                    pack.delta.rotation.pitch.value as f32 / 256.0,
+                                ent.move_to(
+                                    ent.get_server_pos()
+                                        + Vec3::new(
+                                            (pack.delta.position.x as f32) / 4096.0,
+                                            (pack.delta.position.y as f32) / 4096.0,
+                                            (pack.delta.position.z as f32) / 4096.0,
+                                        ),
+                                );
+                                ent.rotate_to(
+                                    angle_to_degrees(&pack.delta.rotation.yaw),
+                                    angle_to_degrees(&pack.delta.rotation.pitch),
                                 );
                                 ent.on_ground = pack.on_ground;
                             }
@@ -561,9 +711,9 @@ impl Server {
                     PacketType::PlayEntityRotation(pack) => {
                         match self.entities.get_mut(&pack.entity_id.0) {
                             Some(ent) => {
-                                ent.ori.set(
-                                    pack.rotation.yaw.value as f32 / 256.0,
-                                    pack.rotation.pitch.value as f32 / 256.0,
+                                ent.rotate_to(
+                                    angle_to_degrees(&pack.rotation.yaw),
+                                    angle_to_degrees(&pack.rotation.pitch),
                                 );
                                 ent.on_ground = pack.on_ground;
                             }
@@ -574,10 +724,7 @@ impl Server {
                     PacketType::PlayEntityHeadLook(pack) => {
                         match self.entities.get_mut(&pack.entity_id.0) {
                             Some(ent) => {
-                                ent.ori_head.set(
-                                    pack.head_yaw.value as f32 / 256.0,
-                                    ent.ori_head.get_pitch(),
-                                );
+                                ent.turn_head_to(angle_to_degrees(&pack.head_yaw));
                             }
                             None => {}
                         }
@@ -599,14 +746,14 @@ impl Server {
                     PacketType::PlayEntityTeleport(pack) => {
                         match self.entities.get_mut(&pack.entity_id.0) {
                             Some(ent) => {
-                                ent.pos = Vec3::new(
+                                ent.move_to(Vec3::new(
                                     pack.location.position.x as f32,
                                     pack.location.position.y as f32,
                                     pack.location.position.z as f32,
-                                );
-                                ent.ori.set(
-                                    pack.location.rotation.yaw.value as f32 / 256.0,
-                                    pack.location.rotation.pitch.value as f32 / 256.0,
+                                ));
+                                ent.rotate_to(
+                                    angle_to_degrees(&pack.location.rotation.yaw),
+                                    angle_to_degrees(&pack.location.rotation.pitch),
                                 );
                                 ent.on_ground = pack.on_ground;
                             }
@@ -657,12 +804,16 @@ impl Server {
 
                     PacketType::PlayChunkData(cd) => {
                         self.world.insert_chunk(Chunk::new(&cd.data));
+                        self.world.insert_block_entities(&cd.data);
                     }
 
                     PacketType::PlayUnloadChunk(pack) => {
                         self.world
-                            .get_chunks_mut()
-                            .remove(&IVec2::new(pack.position.x, pack.position.z));
+                            .unload_chunk(&IVec2::new(pack.position.x, pack.position.z));
+                    }
+
+                    PacketType::PlayBlockEntityData(pack) => {
+                        self.world.handle_block_entity_data(pack);
                     }
 
                     PacketType::PlayBlockChange(pack) => {
@@ -678,6 +829,21 @@ impl Server {
                         match pack.actions {
                             PlayerInfoActionList::Add(players) => {
                                 for player in players.iter() {
+                                    let textures = player
+                                        .action
+                                        .properties
+                                        .iter()
+                                        .find(|p| p.name == "textures");
+                                    if let Some(textures) = textures {
+                                        match skins::parse_skin_url(&textures.value) {
+                                            Ok(url) => self.skins.request(player.uuid, url),
+                                            Err(e) => error!(
+                                                "Couldn't read skin of {}: {:?}",
+                                                player.action.name, e
+                                            ),
+                                        }
+                                    }
+
                                     self.players.insert(
                                         player.uuid,
                                         RemotePlayer {
@@ -730,11 +896,52 @@ impl Server {
                         }
                     }
 
+                    PacketType::PlayParticle(pack) => self.handle_particle(pack),
+
+                    PacketType::PlayEffect(pack) => self.handle_effect(pack),
+
+                    PacketType::PlayExplosion(pack) => self.handle_explosion(pack),
+
+                    PacketType::PlayEntityAnimation(pack) => {
+                        let kind = match pack.animation {
+                            EntityAnimationKind::CriticalEffect => Some(ParticleKind::Crit),
+                            EntityAnimationKind::MagicCriticalEffect => {
+                                Some(ParticleKind::EnchantedHit)
+                            }
+                            _ => None,
+                        };
+                        let ent = self.entities.get(&pack.entity_id.0);
+                        if let (Some(kind), Some(ent)) = (kind, ent) {
+                            let e = ent.get_type();
+                            self.particles.spawn_crit(kind, ent.pos, e.width, e.height);
+                        }
+                    }
+
+                    PacketType::PlayWindowItems(pack) => {
+                        // Window 0 is always the player's inventory
+                        if pack.window_id == 0 {
+                            self.inventory
+                                .set_slots(pack.slots.iter().map(ItemStack::from_slot).collect());
+                        }
+                    }
+
+                    PacketType::PlaySetSlot(pack) => {
+                        if let (0, Ok(slot)) = (pack.window_id, usize::try_from(pack.slot)) {
+                            self.inventory
+                                .set_slot(slot, ItemStack::from_slot(&pack.slot_data));
+                        }
+                    }
+
+                    PacketType::PlayServerHeldItemChange(pack) => {
+                        if let Ok(slot) = usize::try_from(pack.slot) {
+                            self.inventory.set_selected(slot);
+                        }
+                    }
+
                     // Currently ignoring these packets
                     PacketType::PlayEntityMetadata(_)
                     | PacketType::PlayEntityProperties(_)
-                    | PacketType::PlayEntityStatus(_)
-                    | PacketType::PlayEntityAnimation(_) => {}
+                    | PacketType::PlayEntityStatus(_) => {}
 
                     // Packets that have been forwarded but not handled properly
                     _ => {
@@ -749,4 +956,63 @@ impl Server {
             }
         }
     }
+
+    fn handle_particle(&mut self, pack: PlayParticleSpec) {
+        match ParticleKind::from_id(pack.particle_id, &pack.data.data) {
+            Some(kind) => self.particles.spawn_from_server(
+                kind,
+                Vec3::new(
+                    pack.position.x as f32,
+                    pack.position.y as f32,
+                    pack.position.z as f32,
+                ),
+                Vec3::new(pack.offset.x, pack.offset.y, pack.offset.z),
+                pack.particle_data,
+                pack.particle_count,
+            ),
+            None => debug!("Unhandled particle: {}", pack.particle_id),
+        }
+    }
+
+    fn handle_effect(&mut self, pack: PlayEffectSpec) {
+        let coords = IVec3::new(pack.location.x, pack.location.y.into(), pack.location.z);
+        let centre = coords.as_vec3() + Vec3::splat(0.5);
+
+        match pack.effect_id {
+            // Smoke coming out of one side of a block, like from a dispenser
+            2000 => {
+                let dir = Vec3::new(
+                    (pack.data % 3 - 1) as f32,
+                    0.0,
+                    (pack.data / 3 % 3 - 1) as f32,
+                );
+                for _ in 0..10 {
+                    self.particles
+                        .spawn(ParticleKind::Smoke, centre + dir * 0.6, dir * 0.4);
+                }
+            }
+            // A block being broken, with the block state that was broken
+            2001 => self.particles.spawn_block_break(&coords, pack.data as u32),
+            // A mob spawner spawning something
+            2004 => {
+                let spread = Vec3::splat(0.3);
+                self.particles
+                    .spawn_from_server(ParticleKind::Smoke, centre, spread, 0.0, 20);
+                self.particles
+                    .spawn_from_server(ParticleKind::Flame, centre, spread, 0.0, 20);
+            }
+            _ => debug!("Unhandled effect: {}", pack.effect_id),
+        }
+    }
+
+    fn handle_explosion(&mut self, pack: PlayExplosionSpec) {
+        // Big explosions are shown with a cluster of smaller ones, like in the game
+        let kind = if pack.strength >= 2.0 {
+            ParticleKind::ExplosionEmitter
+        } else {
+            ParticleKind::Explosion
+        };
+        self.particles
+            .spawn(kind, Vec3::new(pack.x, pack.y, pack.z), Vec3::ZERO);
+    }
 }
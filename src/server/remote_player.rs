@@ -7,3 +7,20 @@ pub struct RemotePlayer {
     pub ping: i32,
     pub display_name: Option<String>,
 }
+
+impl RemotePlayer {
+    /// The name to show for the player, without any formatting codes
+    pub fn get_shown_name(&self) -> String {
+        let name = self.display_name.as_ref().unwrap_or(&self.name);
+        let mut shown = String::with_capacity(name.len());
+        let mut chars = name.chars();
+        while let Some(c) = chars.next() {
+            if c == '§' {
+                chars.next();
+            } else {
+                shown.push(c);
+            }
+        }
+        shown
+    }
+}
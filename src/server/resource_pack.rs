@@ -0,0 +1,183 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    error::Error,
+    hash::{Hash, Hasher},
+    io::Read,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, TryRecvError},
+};
+
+use simple_error::bail;
+
+/// Where resource packs sent by servers are saved, so they only have to be downloaded once
+pub const SERVER_PACKS_DIR: &str = "resourcepacks/server";
+
+/// Packs bigger than this many bytes aren't downloaded
+const MAX_PACK_SIZE: u64 = 100 * 1024 * 1024;
+
+/// A resource pack a server has asked the player to use
+#[derive(Debug, Clone)]
+pub struct PackOffer {
+    pub url: String,
+    pub hash: String,
+}
+
+/// How far along a server's resource pack is
+pub enum ServerPack {
+    None,
+    /// Waiting for the player to say whether they want to use it
+    Offered(PackOffer),
+    Downloading(Receiver<Result<PathBuf, String>>),
+    /// Downloaded, but resources haven't been reloaded with it yet
+    Downloaded(PathBuf),
+    Loaded(PathBuf),
+}
+
+/// What happened to a pack that was being downloaded
+pub enum DownloadResult {
+    Finished,
+    Failed,
+}
+
+impl ServerPack {
+    /// Starts downloading the offered pack, unless it's already been downloaded before
+    pub fn accept(&mut self) {
+        let offer = match self {
+            ServerPack::Offered(offer) => offer.clone(),
+            _ => return,
+        };
+
+        let (send, recv) = channel();
+        std::thread::spawn(move || {
+            let result = download_pack(&offer).map_err(|e| e.to_string());
+            send.send(result).ok();
+        });
+        *self = ServerPack::Downloading(recv);
+    }
+
+    /// Checks if the download has finished, returning what happened if it has
+    pub fn poll_download(&mut self) -> Option<DownloadResult> {
+        let result = match self {
+            ServerPack::Downloading(recv) => match recv.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => Err(String::from("Download thread stopped")),
+            },
+            _ => return None,
+        };
+
+        match result {
+            Ok(path) => {
+                *self = ServerPack::Downloaded(path);
+                Some(DownloadResult::Finished)
+            }
+            Err(e) => {
+                log::error!("Couldn't download server resource pack: {}", e);
+                *self = ServerPack::None;
+                Some(DownloadResult::Failed)
+            }
+        }
+    }
+
+    /// The pack to use on top of the player's own ones, once it's been downloaded
+    pub fn get_path(&self) -> Option<&PathBuf> {
+        match self {
+            ServerPack::Downloaded(path) | ServerPack::Loaded(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// The SHA-1 hash the server sent for the pack in lowercase, if it sent a valid one
+fn get_hash(offer: &PackOffer) -> Option<String> {
+    if offer.hash.len() == 40 && offer.hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(offer.hash.to_lowercase())
+    } else {
+        None
+    }
+}
+
+fn sha1(bytes: &[u8]) -> String {
+    sha1_smol::Sha1::from(bytes).digest().to_string()
+}
+
+/// The file a pack is saved to. The hash the server sends is used if there is one, otherwise the
+/// url is hashed instead.
+fn cache_path(offer: &PackOffer) -> PathBuf {
+    let name = get_hash(offer).unwrap_or_else(|| {
+        let mut hasher = DefaultHasher::new();
+        offer.url.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    });
+    PathBuf::from(SERVER_PACKS_DIR).join(format!("{}.zip", name))
+}
+
+fn download_pack(offer: &PackOffer) -> Result<PathBuf, Box<dyn Error>> {
+    let hash = get_hash(offer);
+    let path = cache_path(offer);
+    // Saved packs are only used if they can be checked against the hash, since packs without one
+    // could have changed on the server since
+    if let (true, Some(hash)) = (path.exists(), &hash) {
+        if sha1(&std::fs::read(&path)?) == *hash {
+            log::info!("Using saved server resource pack {}", path.display());
+            return Ok(path);
+        }
+        log::warn!(
+            "Saved server resource pack {} doesn't match its hash, downloading it again",
+            path.display()
+        );
+    }
+
+    log::info!("Downloading server resource pack {}", offer.url);
+    let mut bytes = Vec::new();
+    ureq::get(&offer.url)
+        .call()?
+        .into_reader()
+        .take(MAX_PACK_SIZE + 1)
+        .read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_PACK_SIZE {
+        bail!("Resource pack is bigger than {} bytes", MAX_PACK_SIZE);
+    }
+    if let Some(hash) = hash {
+        let actual = sha1(&bytes);
+        if actual != hash {
+            bail!(
+                "Resource pack has the hash {} but the server said it would be {}",
+                actual,
+                hash
+            );
+        }
+    }
+
+    // Written somewhere else first so a half finished download is never mistaken for a pack
+    std::fs::create_dir_all(SERVER_PACKS_DIR)?;
+    let partial = path.with_extension("part");
+    std::fs::write(&partial, &bytes)?;
+    std::fs::rename(&partial, &path)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(hash: &str) -> PackOffer {
+        PackOffer {
+            url: String::from("http://example.com/pack.zip"),
+            hash: String::from(hash),
+        }
+    }
+
+    #[test]
+    fn only_valid_hashes_are_checked() {
+        assert_eq!(sha1(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+
+        let hash = "A9993E364706816ABA3E25717850C26C9CD0D89D";
+        assert_eq!(get_hash(&offer(hash)), Some(sha1(b"abc")));
+        assert!(cache_path(&offer(hash)).ends_with(format!("{}.zip", sha1(b"abc"))));
+
+        assert_eq!(get_hash(&offer("")), None);
+        assert_eq!(get_hash(&offer(&"z".repeat(40))), None);
+    }
+}
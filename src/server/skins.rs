@@ -0,0 +1,213 @@
+use std::{
+    error::Error,
+    io::Read,
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+
+use image::RgbaImage;
+use mcproto_rs::uuid::UUID4;
+use serde_json::Value;
+use simple_error::bail;
+
+/// Somewhere player skins can be downloaded from, so tests can load them from files instead
+pub trait SkinFetcher: Send + Sync {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error>>;
+}
+
+/// Downloads skins from the skin server
+pub struct HttpFetcher;
+
+impl SkinFetcher for HttpFetcher {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        ureq::get(url)
+            .call()?
+            .into_reader()
+            .take(MAX_SKIN_SIZE)
+            .read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Loads skins from a folder, using the last part of the url plus `.png` as the file name
+pub struct FileFetcher {
+    dir: PathBuf,
+}
+
+impl FileFetcher {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> FileFetcher {
+        FileFetcher { dir: dir.into() }
+    }
+}
+
+impl SkinFetcher for FileFetcher {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let name = url.rsplit('/').next().unwrap_or(url);
+        Ok(std::fs::read(self.dir.join(format!("{}.png", name)))?)
+    }
+}
+
+/// Skins bigger than this many bytes aren't downloaded
+const MAX_SKIN_SIZE: u64 = 1024 * 1024;
+
+/// Gets the skin url out of the `textures` property sent with player info, which is base64
+/// encoded json
+pub fn parse_skin_url(textures: &str) -> Result<String, Box<dyn Error>> {
+    let json: Value = serde_json::from_slice(&base64::decode(textures)?)?;
+    match json
+        .get("textures")
+        .and_then(|t| t.get("SKIN"))
+        .and_then(|s| s.get("url"))
+        .and_then(|u| u.as_str())
+    {
+        Some(url) => Ok(url.to_string()),
+        None => bail!("No skin url in textures property"),
+    }
+}
+
+/// Fetches and decodes a skin, turning old 64x32 skins into the 64x64 layout
+pub fn load_skin(fetcher: &dyn SkinFetcher, url: &str) -> Result<RgbaImage, Box<dyn Error>> {
+    let img = image::load_from_memory(&fetcher.fetch(url)?)?.to_rgba8();
+    match img.dimensions() {
+        (64, 64) => Ok(img),
+        (64, 32) => Ok(convert_legacy_skin(&img)),
+        (w, h) => bail!("Skin is {}x{}, not 64x64 or 64x32", w, h),
+    }
+}
+
+/// Copies the right arm and leg of an old skin onto the left ones, mirrored, the same way the game
+/// does
+fn convert_legacy_skin(img: &RgbaImage) -> RgbaImage {
+    let mut out = RgbaImage::new(64, 64);
+    image::imageops::replace(&mut out, img, 0, 0);
+
+    // Source x and y, then how far to move it and the size of the area
+    let copies: [(u32, u32, i32, i32, u32, u32); 12] = [
+        (4, 16, 16, 32, 4, 4),
+        (8, 16, 16, 32, 4, 4),
+        (0, 20, 24, 32, 4, 12),
+        (4, 20, 16, 32, 4, 12),
+        (8, 20, 8, 32, 4, 12),
+        (12, 20, 16, 32, 4, 12),
+        (44, 16, -8, 32, 4, 4),
+        (48, 16, -8, 32, 4, 4),
+        (40, 20, 0, 32, 4, 12),
+        (44, 20, -8, 32, 4, 12),
+        (48, 20, -16, 32, 4, 12),
+        (52, 20, -8, 32, 4, 12),
+    ];
+    for (x, y, dx, dy, w, h) in copies {
+        for j in 0..h {
+            for i in 0..w {
+                let to_x = (x as i32 + dx) as u32 + (w - 1 - i);
+                let to_y = (y as i32 + dy) as u32 + j;
+                out.put_pixel(to_x, to_y, *img.get_pixel(x + i, y + j));
+            }
+        }
+    }
+
+    out
+}
+
+/// Loads player skins in the background
+pub struct SkinLoader {
+    fetcher: Arc<dyn SkinFetcher>,
+    send: Sender<(UUID4, RgbaImage)>,
+    recv: Receiver<(UUID4, RgbaImage)>,
+}
+
+impl SkinLoader {
+    pub fn new(fetcher: Arc<dyn SkinFetcher>) -> SkinLoader {
+        let (send, recv) = channel();
+        SkinLoader {
+            fetcher,
+            send,
+            recv,
+        }
+    }
+
+    /// Starts loading a player's skin
+    pub fn request(&self, uuid: UUID4, url: String) {
+        let fetcher = self.fetcher.clone();
+        let send = self.send.clone();
+        std::thread::spawn(move || match load_skin(fetcher.as_ref(), &url) {
+            Ok(skin) => {
+                send.send((uuid, skin)).ok();
+            }
+            Err(e) => log::error!("Couldn't load skin {}: {:?}", url, e),
+        });
+    }
+
+    /// Skins that have finished loading since this was last called
+    pub fn take_loaded(&self) -> Vec<(UUID4, RgbaImage)> {
+        self.recv.try_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Writes a skin into a fresh folder for a `FileFetcher` to load
+    fn skin_dir(test: &str, name: &str, skin: &RgbaImage) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("minceraft-skins-{}", test));
+        std::fs::create_dir_all(&dir).unwrap();
+        skin.save(dir.join(format!("{}.png", name))).unwrap();
+        dir
+    }
+
+    #[test]
+    fn skin_url_from_textures_property() {
+        let json = r#"{"profileName":"Harry","textures":{"SKIN":{"url":"http://textures.minecraft.net/texture/abc123"}}}"#;
+        let url = parse_skin_url(&base64::encode(json)).unwrap();
+        assert_eq!(url, "http://textures.minecraft.net/texture/abc123");
+
+        assert!(parse_skin_url(&base64::encode(r#"{"textures":{}}"#)).is_err());
+        assert!(parse_skin_url("not base64!").is_err());
+    }
+
+    #[test]
+    fn legacy_skins_are_converted() {
+        let mut skin = RgbaImage::new(64, 32);
+        // Front of the right leg
+        skin.put_pixel(4, 20, image::Rgba([255, 0, 0, 255]));
+        let dir = skin_dir("legacy", "old", &skin);
+
+        let loaded = load_skin(&FileFetcher::new(dir), "http://skins/old").unwrap();
+        assert_eq!(loaded.dimensions(), (64, 64));
+        // Mirrored onto the front of the left leg
+        assert_eq!(loaded.get_pixel(23, 52), &image::Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn loader_uses_fetcher() {
+        let skin = RgbaImage::from_pixel(64, 64, image::Rgba([1, 2, 3, 255]));
+        let dir = skin_dir("loader", "abc123", &skin);
+        let loader = SkinLoader::new(Arc::new(FileFetcher::new(dir)));
+
+        let uuid = UUID4::random();
+        loader.request(
+            uuid,
+            "http://textures.minecraft.net/texture/abc123".to_string(),
+        );
+
+        let start = Instant::now();
+        let loaded = loop {
+            let loaded = loader.take_loaded();
+            if !loaded.is_empty() || start.elapsed() > Duration::from_secs(5) {
+                break loaded;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].0, uuid);
+        assert_eq!(loaded[0].1, skin);
+    }
+}
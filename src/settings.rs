@@ -3,7 +3,12 @@ use std::path::Path;
 use glam::Vec3;
 use serde::{Deserialize, Serialize};
 
-use crate::gui::main_menu::SavedServer;
+use crate::{
+    gui::main_menu::SavedServer,
+    particles::DEFAULT_MAX_PARTICLES,
+    renderer::{clouds::CloudMode, mipmaps::DEFAULT_MIPMAP_LEVELS},
+    resources::assets::DEFAULT_ASSETS_DIR,
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Settings {
@@ -19,6 +24,45 @@ pub struct Settings {
     pub day_colour: [f32; 3],
     pub fog_near: f32,
     pub fog_far: f32,
+    #[serde(default)]
+    pub clouds: CloudMode,
+    /// The oldest particles are removed to make room for new ones past this many
+    #[serde(default = "default_max_particles")]
+    pub max_particles: usize,
+    /// How many smaller versions of block textures are made for drawing blocks further away,
+    /// 0 turns mipmapping off
+    #[serde(default = "default_mipmap_levels")]
+    pub mipmap_levels: u32,
+    /// How many samples are taken from block textures seen at an angle, 1 turns it off
+    #[serde(default = "default_anisotropy")]
+    pub anisotropy: u16,
+    /// Names of the resource packs to use from the resource packs folder, with later ones used
+    /// over earlier ones
+    #[serde(default)]
+    pub resource_packs: Vec<String>,
+    /// The folder the game's assets were downloaded to
+    #[serde(default = "default_assets_dir")]
+    pub assets_dir: String,
+
+    /// Draws the hitbox of every entity over its model
+    #[serde(default)]
+    pub show_hitboxes: bool,
+}
+
+fn default_max_particles() -> usize {
+    DEFAULT_MAX_PARTICLES
+}
+
+fn default_mipmap_levels() -> u32 {
+    DEFAULT_MIPMAP_LEVELS
+}
+
+fn default_anisotropy() -> u16 {
+    1
+}
+
+fn default_assets_dir() -> String {
+    String::from(DEFAULT_ASSETS_DIR)
 }
 
 impl Settings {
@@ -36,6 +80,14 @@ impl Settings {
             day_colour: [0.2, 0.5, 0.9],
             fog_near: 5.0,
             fog_far: 320.0,
+            clouds: CloudMode::Fancy,
+            max_particles: DEFAULT_MAX_PARTICLES,
+            mipmap_levels: DEFAULT_MIPMAP_LEVELS,
+            anisotropy: 1,
+            resource_packs: Vec::new(),
+            assets_dir: default_assets_dir(),
+
+            show_hitboxes: false,
         }
     }
 
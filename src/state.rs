@@ -1,11 +1,20 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use egui_extras::RetainedImage;
-use glium::Display;
+use glium::{backend::Facade, Display};
 
 use mcproto_rs::status;
 
-use crate::{renderer::Renderer, server::Server, settings::Settings};
+use crate::{
+    gui::item_icons::ItemIcons,
+    renderer::Renderer,
+    resources::{self, packs::PACKS_DIR},
+    server::Server,
+    settings::Settings,
+};
 
 pub struct State {
     pub rend: Renderer,
@@ -16,6 +25,10 @@ pub struct State {
     pub outstanding_server_pings: HashMap<String, Server>,
     pub server_pings: HashMap<String, status::StatusSpec>,
     pub icon_handles: HashMap<String, RetainedImage>,
+    pub item_icons: ItemIcons,
+
+    /// Set when the resource packs being used have changed so resources get reloaded
+    pub resources_changed: bool,
 }
 
 impl State {
@@ -35,6 +48,39 @@ impl State {
             outstanding_server_pings: HashMap::new(),
             server_pings: HashMap::new(),
             icon_handles: HashMap::new(),
+            item_icons: ItemIcons::new(),
+
+            resources_changed: false,
+        }
+    }
+
+    /// The resource packs picked in the settings followed by the server's pack if it's sent one,
+    /// so the server's is used over the others
+    pub fn get_resource_packs(&self) -> Vec<PathBuf> {
+        let mut packs: Vec<_> = self
+            .settings
+            .resource_packs
+            .iter()
+            .map(|name| Path::new(PACKS_DIR).join(name))
+            .collect();
+        if let Some(server) = &self.server {
+            packs.extend(server.get_resource_pack().cloned());
+        }
+        packs
+    }
+
+    /// Loads every resource again from the current resource packs, then uploads the new textures
+    /// and rebuilds the world with them
+    pub fn reload_resources(&mut self, dis: &impl Facade) {
+        self.resources_changed = false;
+        resources::assets::set_dir(&self.settings.assets_dir);
+        resources::reload(&self.get_resource_packs());
+        self.rend.load_textures(dis);
+        self.item_icons.clear();
+
+        if let Some(server) = &mut self.server {
+            server.get_world_mut().remesh_all();
+            server.resource_pack_loaded();
         }
     }
 }
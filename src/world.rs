@@ -1,21 +1,37 @@
-use std::{collections::HashMap, convert::TryInto, sync::mpsc::TryRecvError};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    sync::{mpsc::TryRecvError, Arc},
+};
 
 use glam::{IVec2, IVec3, Vec3, Vec3Swizzles};
-use glium::Display;
-use mcproto_rs::v1_16_3::{PlayBlockChangeSpec, PlayMultiBlockChangeSpec};
+use glium::backend::Facade;
+use mcproto_rs::{
+    nbt,
+    v1_16_3::{ChunkData, PlayBlockChangeSpec, PlayBlockEntityDataSpec, PlayMultiBlockChangeSpec},
+};
 
-use crate::resources::{BlockState, BLOCKS};
+use crate::{
+    renderer::camera::Camera,
+    resources::{Biome, BlockState, BLOCKS},
+};
 
 use self::{
-    chunk_builder::ChunkBuilder,
+    block_entities::BlockEntity,
+    chunk_builder::{ChunkBuilder, MeshJob},
     chunks::{
         block_pos_to_index, BlockIndex, Chunk, ChunkSection, WrappedChunkSection, MAX_SECTION,
-        MIN_SECTION,
+        MIN_SECTION, SECTIONS_PER_CHUNK,
     },
+    mesh_queue::MeshQueue,
+    visibility::SectionVisibility,
 };
 
+pub mod block_entities;
 pub mod chunk_builder;
 pub mod chunks;
+pub mod mesh_queue;
+pub mod visibility;
 
 pub type WorldCoords = IVec3;
 pub type ChunkCoords = IVec3;
@@ -23,6 +39,10 @@ pub type SectionCoords = IVec3;
 pub type ChunkLocation = IVec2;
 pub type SectionLocation = IVec3;
 
+/// Uploading too many meshes to the GPU in one go makes frames stutter, so only this many
+/// chunk sections are loaded each frame
+const MAX_UPLOADS_PER_FRAME: usize = 16;
+
 trait Directional {
     fn north() -> Self;
     fn east() -> Self;
@@ -32,8 +52,10 @@ trait Directional {
 
 pub struct World {
     chunks: HashMap<IVec2, Chunk>,
-    chunks_to_generate: Vec<ChunkLocation>,
-    sections_to_generate: Vec<SectionLocation>,
+    block_entities: HashMap<WorldCoords, BlockEntity>,
+    /// Chunks that will be meshed once the chunks around them are loaded
+    chunks_to_generate: HashSet<ChunkLocation>,
+    mesh_queue: MeshQueue,
     builder: ChunkBuilder,
 }
 
@@ -41,30 +63,148 @@ impl World {
     pub fn new() -> World {
         World {
             chunks: HashMap::new(),
-            chunks_to_generate: Vec::new(),
-            sections_to_generate: Vec::new(),
+            block_entities: HashMap::new(),
+            chunks_to_generate: HashSet::new(),
+            mesh_queue: MeshQueue::new(),
             builder: ChunkBuilder::new(),
         }
     }
 
-    pub fn insert_chunk(&mut self, chunk: Chunk) {
+    pub fn insert_chunk(&mut self, mut chunk: Chunk) {
         let chunk_coords = *chunk.get_coords();
+
+        // Biomes are only sent with full chunks, so keep the old ones for partial updates
+        if chunk.get_biomes().is_none() {
+            if let Some(old) = self.chunks.get(&chunk_coords) {
+                chunk.set_biomes(old.get_biomes());
+            }
+        }
+
         self.chunks.insert(chunk_coords, chunk);
         self.queue_chunk_mesh(chunk_coords);
+        self.queue_neighbour_meshes(&chunk_coords);
+    }
+
+    /// Removes a chunk along with it's meshes and any that are still being built. The chunks next
+    /// to it are rebuilt since their sides facing it were hidden by it's blocks.
+    pub fn unload_chunk(&mut self, location: &ChunkLocation) {
+        self.chunks.remove(location);
+        self.chunks_to_generate.remove(location);
+        self.mesh_queue.cancel_chunk(location);
+        self.block_entities
+            .retain(|pos, _| Chunk::chunk_containing(pos) != *location);
+
+        self.queue_neighbour_meshes(location);
+    }
+
+    /// Rebuilds the sections that already have meshes in the chunks around a chunk, so the faces
+    /// along their sides match what's next to them now
+    fn queue_neighbour_meshes(&mut self, location: &ChunkLocation) {
+        for offset in [IVec2::north(), IVec2::east(), IVec2::south(), IVec2::west()] {
+            let loc = *location + offset;
+            let sections: Vec<_> = match self.get_chunk(&loc) {
+                Some(chunk) => (MIN_SECTION..=MAX_SECTION)
+                    .filter(|y| chunk.get_section_buffers(*y).is_some())
+                    .collect(),
+                None => continue,
+            };
+
+            for y in sections {
+                self.mesh_queue.push(SectionLocation::new(loc.x, y, loc.y));
+            }
+        }
+    }
+
+    /// Which sides of a chunk section can be seen from each other. Sections that haven't been
+    /// built yet or are empty can be seen straight through
+    pub fn get_section_visibility(&self, loc: &SectionLocation) -> SectionVisibility {
+        self.get_chunk(&loc.xz())
+            .and_then(|c| c.get_section_buffers(loc.y))
+            .map(|buffers| buffers.visibility)
+            .unwrap_or_default()
+    }
+
+    /// Size of all the chunk section meshes on the GPU in bytes
+    pub fn get_mesh_memory(&self) -> usize {
+        self.chunks.values().map(|c| c.get_mesh_size()).sum()
+    }
+
+    /// Adds the block entities sent along with a chunk, replacing any in the sections that were
+    /// sent. Full chunks replace the whole chunk, but partial updates only send the sections in
+    /// their bit mask.
+    pub fn insert_block_entities(&mut self, data: &ChunkData) {
+        let location = IVec2::new(data.position.x, data.position.z);
+        // Biomes are only sent with full chunks
+        let full_chunk = data.biomes.is_some();
+        let replaced = |pos: &WorldCoords| {
+            let section = ChunkSection::section_containing_height(pos.y);
+            full_chunk
+                || (0..SECTIONS_PER_CHUNK as i32).contains(&section)
+                    && data.primary_bit_mask.0 & 1 << section != 0
+        };
+        self.block_entities
+            .retain(|pos, _| Chunk::chunk_containing(pos) != location || !replaced(pos));
+
+        for tag in &data.block_entities {
+            if let Some(be) = BlockEntity::from_network(tag) {
+                self.block_entities.insert(be.pos, be);
+            }
+        }
+    }
+
+    pub fn get_block_entities(&self) -> &HashMap<WorldCoords, BlockEntity> {
+        &self.block_entities
     }
 
+    pub fn get_block_entity(&self, coords: &WorldCoords) -> Option<&BlockEntity> {
+        self.block_entities.get(coords)
+    }
+
+    /// Queues a chunk section to have it's mesh rebuilt. Sections in chunks that are still waiting
+    /// for their neighbours are built along with the rest of the chunk instead
     pub fn queue_chunk_section_mesh(&mut self, location: SectionLocation) {
-        if self.sections_to_generate.contains(&location) {
+        let chunk = location.xz();
+        if !self.is_chunk_loaded(&chunk) || self.chunks_to_generate.contains(&chunk) {
             return;
         }
-        self.sections_to_generate.push(location);
+        self.mesh_queue.push(location);
     }
 
+    /// Queues every section of a chunk to be built once the chunks around it are loaded
     pub fn queue_chunk_mesh(&mut self, location: ChunkLocation) {
-        if self.chunks_to_generate.contains(&location) {
-            return;
+        self.chunks_to_generate.insert(location);
+        self.queue_ready_chunks(location);
+    }
+
+    /// Moves a chunk and the ones next to it into the mesh queue if they have all their
+    /// neighbours now
+    fn queue_ready_chunks(&mut self, location: ChunkLocation) {
+        for loc in [
+            location,
+            location + IVec2::north(),
+            location + IVec2::east(),
+            location + IVec2::south(),
+            location + IVec2::west(),
+        ] {
+            if self.chunks_to_generate.contains(&loc) && self.are_chunk_neighbours_loaded(&loc) {
+                self.chunks_to_generate.remove(&loc);
+                for y in MIN_SECTION..=MAX_SECTION {
+                    self.mesh_queue.push(SectionLocation::new(loc.x, y, loc.y));
+                }
+            }
+        }
+    }
+
+    /// Rebuilds the meshes of every chunk, for when the textures or models they use change
+    pub fn remesh_all(&mut self) {
+        let chunks: Vec<_> = self.chunks.keys().copied().collect();
+        for location in chunks {
+            self.queue_chunk_mesh(location);
         }
-        self.chunks_to_generate.push(location);
+    }
+
+    pub fn get_mesh_queue(&self) -> &MeshQueue {
+        &self.mesh_queue
     }
 
     fn are_chunk_neighbours_loaded(&self, loc: &ChunkLocation) -> bool {
@@ -76,134 +216,79 @@ impl World {
         chunk.is_some() && north.is_some() && east.is_some() && south.is_some() && west.is_some()
     }
 
-    fn generate_section_mesh(&mut self, loc: &SectionLocation, dis: &Display, threaded: bool) {
-        let chunk = self.get_section(loc);
-        // Discard chunk sections that are empty
-        if chunk.is_none() {
-            return;
-        }
-        let chunk = chunk.unwrap();
-
-        // Can unwrap because we checked above that they are all valid
-        let north = self.get_section(&SectionLocation::new(loc.x, loc.y, loc.z - 1));
-        let east = self.get_section(&SectionLocation::new(loc.x + 1, loc.y, loc.z));
-        let south = self.get_section(&SectionLocation::new(loc.x, loc.y, loc.z + 1));
-        let west = self.get_section(&SectionLocation::new(loc.x - 1, loc.y, loc.z));
-        let above = if loc.y >= MAX_SECTION {
-            None
-        } else {
-            self.get_section(&SectionLocation::new(loc.x, loc.y + 1, loc.z))
+    /// Sends a chunk section off to have it's mesh built, returns false if there's no section
+    /// there to build
+    fn generate_section_mesh(&self, loc: &SectionLocation, job: MeshJob, threaded: bool) -> bool {
+        let sect = match self.get_section(loc) {
+            Some(sect) => sect,
+            None => return false,
         };
-        let below = if loc.y <= MIN_SECTION {
+
+        let above = if loc.y < MAX_SECTION {
+            self.get_section(&(*loc + IVec3::Y))
+        } else {
             None
+        };
+        let below = if loc.y > MIN_SECTION {
+            self.get_section(&(*loc - IVec3::Y))
         } else {
-            self.get_section(&SectionLocation::new(loc.x, loc.y - 1, loc.z))
+            None
         };
+        let north = self.get_section(&(*loc + IVec3::north()));
+        let east = self.get_section(&(*loc + IVec3::east()));
+        let south = self.get_section(&(*loc + IVec3::south()));
+        let west = self.get_section(&(*loc + IVec3::west()));
+        let biomes = self
+            .get_chunk(&loc.xz())
+            .map(|c| c.get_biomes())
+            .unwrap_or(None);
 
         self.builder.generate_chunk_section(
-            chunk,
-            loc.clone(),
-            above,
-            below,
-            north,
-            east,
-            south,
-            west,
-            threaded,
+            sect, biomes, *loc, above, below, north, east, south, west, job, threaded,
         );
+        true
     }
 
-    pub fn generate_meshes(&mut self, dis: &Display, threaded: bool) {
-        // Chunks
-        let mut temp = Vec::new();
-        std::mem::swap(&mut self.chunks_to_generate, &mut temp);
-        let ready_chunks: Vec<_> = temp
-            .drain_filter(|loc| self.are_chunk_neighbours_loaded(loc))
-            .collect();
-        std::mem::swap(&mut self.chunks_to_generate, &mut temp);
-
-        for loc in ready_chunks {
-            for y in MIN_SECTION..=MAX_SECTION {
-                self.generate_section_mesh(&SectionLocation::new(loc.x, y, loc.y), dis, threaded);
-                self.builder.generate_chunk(
-                    self.get_chunk(&loc).unwrap(),
-                    self.get_chunk(&(loc + IVec2::north())).unwrap(),
-                    self.get_chunk(&(loc + IVec2::east())).unwrap(),
-                    self.get_chunk(&(loc + IVec2::south())).unwrap(),
-                    self.get_chunk(&(loc + IVec2::west())).unwrap(),
-                    threaded,
-                )
+    /// Starts building the most important queued meshes and uploads some of the ones that are
+    /// finished
+    pub fn generate_meshes(&mut self, dis: &impl Facade, cam: &Camera, threaded: bool) {
+        self.mesh_queue.update_view(cam);
+
+        // Only keep a few jobs waiting for each thread, so the queue can still reorder the rest
+        // as the camera moves
+        let max_in_flight = self.builder.get_thread_count() * 2;
+        while let Some((loc, job)) = self.mesh_queue.pop(max_in_flight) {
+            let id = job.id;
+            if !self.generate_section_mesh(&loc, job, threaded) {
+                self.mesh_queue.finish(&loc, id);
             }
         }
 
-        // Chunk sections
-        let mut temp = Vec::new();
-        std::mem::swap(&mut temp, &mut self.sections_to_generate);
-        temp.retain(|loc| {
-            // Retain chunks that don't have all their neighbouring chunks
-            if !self.are_chunk_neighbours_loaded(&ChunkLocation::new(loc.x, loc.z)) {
-                return true;
-            }
-
-            // Discard chunk sections that are empty
-            if self.get_section(loc).is_none() {
-                return false;
-            }
-
-            let sect = self.get_section(loc).unwrap();
-            let above = if loc.y < MAX_SECTION {
-                self.get_section(&(*loc + IVec3::Y))
-            } else {
-                None
-            };
-            let below = if loc.y > MIN_SECTION {
-                self.get_section(&(*loc - IVec3::Y))
-            } else {
-                None
-            };
-            let north = self.get_section(&(*loc + IVec3::north()));
-            let east = self.get_section(&(*loc + IVec3::east()));
-            let south = self.get_section(&(*loc + IVec3::south()));
-            let west = self.get_section(&(*loc + IVec3::west()));
-
-            self.builder.generate_chunk_section(
-                sect,
-                loc.clone(),
-                above,
-                below,
-                north,
-                east,
-                south,
-                west,
-                threaded,
-            );
-
-            false
-        });
-        std::mem::swap(&mut temp, &mut self.sections_to_generate);
-
         // Load ready meshes
-        let incoming = self.builder.get_incoming_meshes();
-        let mut new_meshes = Vec::new();
-        let mut i = 0;
-        loop {
-            match incoming.try_recv() {
-                Ok(a) => new_meshes.push(a),
+        let mut uploaded = 0;
+        while uploaded < MAX_UPLOADS_PER_FRAME {
+            let (loc, id, mesh) = match self.builder.get_incoming_meshes().try_recv() {
+                Ok(built) => built,
                 Err(TryRecvError::Empty) => break,
                 Err(e) => panic!("Lost chunk builder thread: {}", e),
-            }
+            };
 
-            // Limit to 16 chunk sections per frame otherwise it can still tank frames from
-            // uploading entire new chunks to the GPU at once
-            if i >= 16 {
-                break;
+            // Meshes for sections that changed again or were unloaded are out of date
+            if !self.mesh_queue.finish(&loc, id) {
+                continue;
+            }
+            if let Some(c) = self.get_chunk_mut(&loc.xz()) {
+                c.load_mesh(dis, mesh, loc.y);
+                uploaded += 1;
             }
-            i += 1;
         }
+    }
 
-        for (loc, verts) in new_meshes {
-            self.get_chunk_mut(&loc.xz())
-                .map(|c| c.load_mesh(dis, verts, loc.y));
+    /// Builds every queued mesh straight away on this thread, for when there aren't any frames to
+    /// spread the work over
+    pub fn generate_all_meshes(&mut self, dis: &impl Facade, cam: &Camera) {
+        while !self.mesh_queue.is_empty() || self.mesh_queue.in_flight() > 0 {
+            self.generate_meshes(dis, cam, false);
         }
     }
 
@@ -261,20 +346,133 @@ impl World {
         self.chunks.get(location).is_some()
     }
 
-    pub fn block_at(&self, coords: &WorldCoords) -> Option<&BlockState> {
+    pub fn biome_at(&self, coords: &WorldCoords) -> Option<Biome> {
+        self.chunks
+            .get(&Chunk::chunk_containing(coords))
+            .map(|c| c.biome_at(&Chunk::map_from_world_coords(coords)))
+            .unwrap_or(None)
+    }
+
+    pub fn block_at(&self, coords: &WorldCoords) -> Option<Arc<BlockState>> {
         self.chunks
             .get(&Chunk::chunk_containing(coords))
             .map(|c| c.block_at(&Chunk::map_from_world_coords(coords)))
             .unwrap_or(None)
     }
 
+    /// Casts a ray through the world and returns the coordinates of the first solid block it hits
+    /// within `max_distance`, along with the normal of the face that was hit
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_distance: f32,
+    ) -> Option<(WorldCoords, IVec3)> {
+        let dir = dir.normalize_or_zero();
+        if dir == Vec3::ZERO {
+            return None;
+        }
+
+        let mut block = block_coords(&origin);
+        let step = IVec3::new(
+            dir.x.signum() as i32,
+            dir.y.signum() as i32,
+            dir.z.signum() as i32,
+        );
+
+        // Distance along the ray to cross a whole block on each axis
+        let delta = Vec3::new(
+            (1.0 / dir.x).abs(),
+            (1.0 / dir.y).abs(),
+            (1.0 / dir.z).abs(),
+        );
+
+        // Distance along the ray to the next block boundary on each axis
+        let next_boundary = |o: f32, b: i32, d: f32| {
+            if d > 0.0 {
+                (b as f32 + 1.0 - o) / d
+            } else if d < 0.0 {
+                (o - b as f32) / -d
+            } else {
+                f32::INFINITY
+            }
+        };
+        let mut t_max = Vec3::new(
+            next_boundary(origin.x, block.x, dir.x),
+            next_boundary(origin.y, block.y, dir.y),
+            next_boundary(origin.z, block.z, dir.z),
+        );
+
+        let mut normal = IVec3::ZERO;
+        let mut travelled = 0.0;
+        while travelled <= max_distance {
+            if let Some(b) = self.block_at(&block) {
+                if !b.is_air() && !b.is_fluid() {
+                    return Some((block, normal));
+                }
+            }
+
+            if t_max.x < t_max.y && t_max.x < t_max.z {
+                block.x += step.x;
+                travelled = t_max.x;
+                t_max.x += delta.x;
+                normal = IVec3::new(-step.x, 0, 0);
+            } else if t_max.y < t_max.z {
+                block.y += step.y;
+                travelled = t_max.y;
+                t_max.y += delta.y;
+                normal = IVec3::new(0, -step.y, 0);
+            } else {
+                block.z += step.z;
+                travelled = t_max.z;
+                t_max.z += delta.z;
+                normal = IVec3::new(0, 0, -step.z);
+            }
+        }
+
+        None
+    }
+
+    pub fn handle_block_entity_data(&mut self, pack: PlayBlockEntityDataSpec) {
+        let coords = IVec3::new(pack.location.x, pack.location.y.into(), pack.location.z);
+
+        // An empty tag means the block entity was removed
+        if let nbt::Tag::End = pack.nbt_data.root.payload {
+            self.block_entities.remove(&coords);
+            return;
+        }
+
+        match BlockEntity::from_network(&pack.nbt_data.root) {
+            Some(mut be) => {
+                be.pos = coords;
+                self.block_entities.insert(coords, be);
+            }
+            None => log::warn!("Got invalid block entity data at {:?}", coords),
+        }
+    }
+
+    /// Removes the block entity at a position if the block there is changing to a different block
+    fn check_block_entity_replaced(&mut self, coords: &WorldCoords, new_block: u32) {
+        if !self.block_entities.contains_key(coords) {
+            return;
+        }
+
+        let (old, blocks) = (self.block_at(coords), BLOCKS.load());
+        let old_name = old.as_ref().map(|b| b.name.as_str());
+        let new_name = blocks.get(&new_block).map(|b| b.name.as_str());
+        if old_name != new_name {
+            self.block_entities.remove(coords);
+        }
+    }
+
     pub fn handle_block_change(&mut self, pack: PlayBlockChangeSpec) {
-        if pack.block_id.0 < 0 || pack.block_id.0 >= BLOCKS.len() as i32 {
+        if pack.block_id.0 < 0 || pack.block_id.0 >= BLOCKS.load().len() as i32 {
             log::error!("Got block change with invalid block ID");
             return;
         }
 
         let coords = IVec3::new(pack.location.x, pack.location.y.into(), pack.location.z);
+        self.check_block_entity_replaced(&coords, pack.block_id.0 as u32);
         let section_loc = ChunkSection::section_containing(&coords);
         let mut sections_to_regenerate = Vec::new();
 
@@ -367,7 +565,7 @@ impl World {
 
         self.queue_chunk_section_mesh(loc);
         for change in pack.blocks.iter() {
-            if change.block_id >= BLOCKS.len().try_into().unwrap() {
+            if change.block_id >= BLOCKS.load().len().try_into().unwrap() {
                 log::error!("Got block change with invalid block ID");
                 continue;
             }
@@ -378,6 +576,7 @@ impl World {
                 change.rel_position.z.into(),
                 change.rel_position.y.into(),
             );
+            self.check_block_entity_replaced(&(loc * 16 + local_pos), change.block_id as u32);
 
             self.get_section(&loc).unwrap().write().unwrap().blocks
                 [block_pos_to_index(&local_pos)] = change.block_id.try_into().unwrap();
@@ -0,0 +1,103 @@
+use std::io::Cursor;
+
+use glam::IVec3;
+use mcproto_rs::nbt;
+use quartz_nbt::{
+    io::{read_nbt, Flavor, NbtIoError},
+    NbtCompound,
+};
+
+use super::WorldCoords;
+
+/// Extra data attached to a block such as a sign, chest, banner or skull
+#[derive(Debug, Clone)]
+pub struct BlockEntity {
+    pub pos: WorldCoords,
+    pub id: String,
+    pub nbt: NbtCompound,
+}
+
+impl BlockEntity {
+    /// Creates a block entity from its NBT, which should contain it's position and type
+    pub fn from_nbt(nbt: NbtCompound) -> Option<BlockEntity> {
+        let x: i32 = nbt.get("x").ok()?;
+        let y: i32 = nbt.get("y").ok()?;
+        let z: i32 = nbt.get("z").ok()?;
+        let id = nbt
+            .get::<_, &str>("id")
+            .map(|id| id.to_string())
+            .unwrap_or_default();
+
+        Some(BlockEntity {
+            pos: IVec3::new(x, y, z),
+            id,
+            nbt,
+        })
+    }
+
+    /// Creates a block entity from NBT received over the network
+    pub fn from_network(tag: &nbt::NamedTag) -> Option<BlockEntity> {
+        match decode_nbt(tag) {
+            Ok(nbt) => Self::from_nbt(nbt),
+            Err(e) => {
+                log::error!("Couldn't decode block entity NBT: {:?}", e);
+                None
+            }
+        }
+    }
+
+    pub fn is_sign(&self) -> bool {
+        self.id == "minecraft:sign"
+    }
+
+    /// Returns the 4 lines of text on a sign as plain text, or None if this isn't a sign
+    pub fn get_sign_text(&self) -> Option<[String; 4]> {
+        if !self.is_sign() {
+            return None;
+        }
+
+        let line = |key: &str| {
+            self.nbt
+                .get::<_, &str>(key)
+                .map(|json| chat_json_to_string(json))
+                .unwrap_or_default()
+        };
+
+        Some([line("Text1"), line("Text2"), line("Text3"), line("Text4")])
+    }
+}
+
+/// Decodes a network NBT tag with quartz_nbt so it's a bit nicer to work with
+pub fn decode_nbt(tag: &nbt::NamedTag) -> Result<NbtCompound, NbtIoError> {
+    let bytes = tag.bytes();
+    let (compound, _) = read_nbt(&mut Cursor::new(bytes), Flavor::Uncompressed)?;
+    Ok(compound)
+}
+
+/// Flattens a json chat component (as stored on signs) into plain text
+pub fn chat_json_to_string(json: &str) -> String {
+    fn flatten(val: &serde_json::Value, out: &mut String) {
+        match val {
+            serde_json::Value::String(s) => out.push_str(s),
+            serde_json::Value::Array(vals) => vals.iter().for_each(|v| flatten(v, out)),
+            serde_json::Value::Object(obj) => {
+                if let Some(serde_json::Value::String(text)) = obj.get("text") {
+                    out.push_str(text);
+                }
+                if let Some(extra) = obj.get("extra") {
+                    flatten(extra, out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match serde_json::from_str::<serde_json::Value>(json) {
+        Ok(val) => {
+            let mut out = String::new();
+            flatten(&val, &mut out);
+            out
+        }
+        Err(_) => json.to_string(),
+    }
+}
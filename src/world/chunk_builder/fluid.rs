@@ -0,0 +1,255 @@
+use std::{collections::HashMap, sync::Arc};
+
+use glam::{IVec3, Vec2, Vec3};
+
+use crate::{
+    renderer::{ChunkMesh, FaceVertex},
+    resources::{
+        block_models::{is_opaque_block, Direction},
+        BlockState, BlockTexture, Fluid, FluidState, MeshResources, TextureRef, MISSING_TEXTURE,
+    },
+};
+
+/// Generates the surface of a fluid block, in coordinates relative to the block. `block_at` looks
+/// up blocks relative to the section the fluid is in, like the rest of the chunk builder.
+pub fn generate_mesh(
+    res: &MeshResources,
+    state: FluidState,
+    pos: IVec3,
+    block_at: &impl Fn(IVec3) -> u16,
+    tint: [f32; 3],
+) -> ChunkMesh {
+    let mut mesh = ChunkMesh::new();
+
+    let (still, flowing) = textures(state.fluid, &res.textures);
+    let same_fluid = |offset: IVec3| fluid_at(block_at(pos + offset), state.fluid, &res.blocks);
+    let hidden_by = |offset: IVec3| {
+        let id = block_at(pos + offset);
+        fluid_at(id, state.fluid, &res.blocks).is_some() || is_opaque_block(id, res)
+    };
+
+    // Height of each corner of the top, in the same order as the corners of an up face
+    let covered = same_fluid(IVec3::Y).is_some();
+    let heights = if covered {
+        [1.0; 4]
+    } else {
+        [
+            corner_height(res, state.fluid, pos, -1, -1, block_at),
+            corner_height(res, state.fluid, pos, -1, 1, block_at),
+            corner_height(res, state.fluid, pos, 1, 1, block_at),
+            corner_height(res, state.fluid, pos, 1, -1, block_at),
+        ]
+    };
+    let height_at = |x: f32, z: f32| match (x > 0.5, z > 0.5) {
+        (false, false) => heights[0],
+        (false, true) => heights[1],
+        (true, true) => heights[2],
+        (true, false) => heights[3],
+    };
+
+    // Top
+    if !covered {
+        let mut corners = Direction::Up.face_corners(Vec3::ZERO, Vec3::ONE);
+        for (corner, height) in corners.iter_mut().zip(heights) {
+            corner.y = height;
+        }
+
+        let flow = flow_direction(res, state, pos, block_at);
+        let (texture, uvs) = if flow == Vec2::ZERO {
+            (still, corners.map(|c| Direction::Up.project_uv(c)))
+        } else {
+            // Turn the flowing texture to point the way the fluid is going
+            let angle = flow.y.atan2(flow.x) - std::f32::consts::FRAC_PI_2;
+            let (s, c) = (angle.sin() * 0.25, angle.cos() * 0.25);
+            (
+                flowing,
+                [
+                    Vec2::new(0.5 - c - s, 0.5 - c + s),
+                    Vec2::new(0.5 - c + s, 0.5 + c + s),
+                    Vec2::new(0.5 + c + s, 0.5 + c - s),
+                    Vec2::new(0.5 + c - s, 0.5 - c - s),
+                ],
+            )
+        };
+
+        let verts = quad(corners, uvs, texture, tint, Direction::Up.shade());
+        // Drawn from both sides so the surface can be seen from underneath
+        mesh.layer_mut(texture.layer)
+            .push(verts, &[0, 1, 2, 0, 2, 3, 0, 2, 1, 0, 3, 2]);
+    }
+
+    // Bottom
+    if !hidden_by(-IVec3::Y) {
+        let corners = Direction::Down.face_corners(Vec3::ZERO, Vec3::ONE);
+        let uvs = corners.map(|c| Direction::Down.project_uv(c));
+        mesh.layer_mut(still.layer).push_quad(quad(
+            corners,
+            uvs,
+            still,
+            tint,
+            Direction::Down.shade(),
+        ));
+    }
+
+    // Sides
+    for dir in [
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ] {
+        if hidden_by(dir.normal()) {
+            continue;
+        }
+
+        // The top two corners come down to the height of the surface at that corner
+        let mut corners = dir.face_corners(Vec3::ZERO, Vec3::ONE);
+        for i in [0, 3] {
+            corners[i].y = height_at(corners[i].x, corners[i].z);
+        }
+        // Sides only show half of the flowing texture
+        let uvs = corners.map(|c| dir.project_uv(c) * 0.5);
+
+        mesh.layer_mut(flowing.layer)
+            .push_quad(quad(corners, uvs, flowing, tint, dir.shade()));
+    }
+
+    mesh
+}
+
+/// Returns the fluid in a block if it's the same type of fluid
+fn fluid_at(id: u16, fluid: Fluid, blocks: &HashMap<u32, Arc<BlockState>>) -> Option<FluidState> {
+    blocks
+        .get(&id.into())
+        .and_then(|block| block.fluid)
+        .filter(|state| state.fluid == fluid)
+}
+
+/// Height of the fluid at a corner of the block, averaged over the 4 blocks that share that
+/// corner. `dx` and `dz` are -1 or 1 for which corner it is.
+fn corner_height(
+    res: &MeshResources,
+    fluid: Fluid,
+    pos: IVec3,
+    dx: i32,
+    dz: i32,
+    block_at: &impl Fn(IVec3) -> u16,
+) -> f32 {
+    let mut total = 0.0;
+    let mut weight = 0.0;
+
+    for offset in [
+        IVec3::ZERO,
+        IVec3::new(dx, 0, 0),
+        IVec3::new(0, 0, dz),
+        IVec3::new(dx, 0, dz),
+    ] {
+        let id = block_at(pos + offset);
+
+        // Fluid that continues upwards fills the corner to the top
+        if fluid_at(block_at(pos + offset + IVec3::Y), fluid, &res.blocks).is_some() {
+            return 1.0;
+        }
+
+        match fluid_at(id, fluid, &res.blocks) {
+            Some(state) => {
+                // Nearly full blocks pull the corner up to their height much more strongly
+                let height = state.height();
+                if height >= 0.8 {
+                    total += height * 10.0;
+                    weight += 10.0;
+                } else {
+                    total += height;
+                    weight += 1.0;
+                }
+            }
+            None => {
+                if !is_opaque_block(id, res) {
+                    weight += 1.0;
+                }
+            }
+        }
+    }
+
+    if weight == 0.0 {
+        return 0.0;
+    }
+    total / weight
+}
+
+/// Which way the fluid is flowing across the top of the block as (x, z), or zero if it's still
+fn flow_direction(
+    res: &MeshResources,
+    state: FluidState,
+    pos: IVec3,
+    block_at: &impl Fn(IVec3) -> u16,
+) -> Vec2 {
+    let height = state.height();
+    let mut flow = Vec2::ZERO;
+
+    for dir in [
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ] {
+        let normal = dir.normal();
+        let id = block_at(pos + normal);
+        let below = fluid_at(block_at(pos + normal - IVec3::Y), state.fluid, &res.blocks);
+        let difference = match (fluid_at(id, state.fluid, &res.blocks), below) {
+            (Some(other), _) => height - other.height(),
+            // Fluid pours over the edge if there's more of it below
+            (None, Some(below)) if !is_opaque_block(id, res) => {
+                height - (below.height() - 8.0 / 9.0)
+            }
+            _ => continue,
+        };
+
+        flow += Vec2::new(normal.x as f32, normal.z as f32) * difference;
+    }
+
+    if flow.length_squared() < 1e-6 {
+        Vec2::ZERO
+    } else {
+        flow.normalize()
+    }
+}
+
+fn quad(
+    corners: [Vec3; 4],
+    uvs: [Vec2; 4],
+    texture: TextureRef,
+    tint: [f32; 3],
+    shade: f32,
+) -> [FaceVertex; 4] {
+    let mut verts = [FaceVertex {
+        position: [0.0; 3],
+        tex_coords: [0.0; 3],
+        tint,
+        shade,
+    }; 4];
+    for (i, (corner, uv)) in corners.iter().zip(uvs).enumerate() {
+        verts[i].position = corner.to_array();
+        // Textures are stored upside down, so flip v
+        verts[i].tex_coords = [uv.x, 1.0 - uv.y, texture.index as f32];
+    }
+    verts
+}
+
+/// The still and flowing textures for a fluid
+fn textures(fluid: Fluid, textures: &HashMap<String, BlockTexture>) -> (TextureRef, TextureRef) {
+    let (still, flowing) = match fluid {
+        Fluid::Water => ("minecraft:block/water_still", "minecraft:block/water_flow"),
+        Fluid::Lava => ("minecraft:block/lava_still", "minecraft:block/lava_flow"),
+    };
+
+    let get = |name: &str| match textures.get(name) {
+        Some(texture) => texture.get_ref(),
+        None => {
+            log::error!("Missing texture: {}", name);
+            MISSING_TEXTURE.get_ref()
+        }
+    };
+
+    (get(still), get(flowing))
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3};
+
+use crate::{
+    renderer::{BlockVertex, ChunkMesh, FullFace},
+    resources::block_models::Direction,
+};
+
+/// The full faces in one slice through a section, indexed by their position along the second
+/// and then the first tangent of the direction they face
+type Slice = [[Option<FullFace>; 16]; 16];
+
+/// Turns the full faces in a mesh into quads, merging faces that look the same into rectangles
+/// that cover as many of them as possible
+pub fn merge_faces(mesh: &mut ChunkMesh) {
+    let mut slices: HashMap<(Direction, i32), Slice> = HashMap::new();
+
+    for face in std::mem::take(&mut mesh.faces) {
+        let (ta, tb) = face.dir.tangents();
+        let (a, b) = (face.pos[ta], face.pos[tb]);
+        if !(0..16).contains(&a) || !(0..16).contains(&b) {
+            push_face(mesh, face, 1, 1);
+            continue;
+        }
+
+        let depth = face.pos[3 - ta - tb];
+        let slice = slices.entry((face.dir, depth)).or_insert([[None; 16]; 16]);
+        // Some blocks like grass have more than one face on the same side, only one of them can
+        // be merged
+        match &mut slice[b as usize][a as usize] {
+            Some(_) => push_face(mesh, face, 1, 1),
+            cell => *cell = Some(face),
+        }
+    }
+
+    for slice in slices.values_mut() {
+        for b in 0..16 {
+            for a in 0..16 {
+                let face = match slice[b][a] {
+                    Some(face) => face,
+                    None => continue,
+                };
+                let matches =
+                    |cell: &Option<FullFace>| matches!(cell, Some(other) if other.matches(&face));
+
+                // Grow along the first tangent as far as possible, then along the second for as
+                // long as the whole row matches
+                let mut width = 1;
+                while a + width < 16 && matches(&slice[b][a + width]) {
+                    width += 1;
+                }
+                let mut height = 1;
+                while b + height < 16 && slice[b + height][a..a + width].iter().all(matches) {
+                    height += 1;
+                }
+
+                for row in &mut slice[b..b + height] {
+                    row[a..a + width].fill(None);
+                }
+                push_face(mesh, face, width, height);
+            }
+        }
+    }
+}
+
+/// Turns the full faces in a mesh into a quad each, without merging any of them
+pub fn split_faces(mesh: &mut ChunkMesh) {
+    for face in std::mem::take(&mut mesh.faces) {
+        push_face(mesh, face, 1, 1);
+    }
+}
+
+/// Adds a quad covering `width` by `height` faces along the tangents, starting from `face`
+fn push_face(mesh: &mut ChunkMesh, face: FullFace, width: usize, height: usize) {
+    let (ta, tb) = face.dir.tangents();
+    let mut size = Vec3::ONE;
+    size[ta] = width as f32;
+    size[tb] = height as f32;
+
+    let from = face.pos.as_vec3();
+    let verts = face.dir.face_corners(Vec3::ZERO, size).map(|corner| {
+        // Texture coordinates carry on past 1 so the texture repeats once per block
+        let uv = face.dir.project_uv(corner);
+        BlockVertex::new(
+            from + corner,
+            // Textures are stored upside down, so flip v
+            Vec2::new(uv.x, 1.0 - uv.y),
+            face.texture,
+            face.colour,
+        )
+    });
+    mesh.layer_mut(face.layer).push_quad(verts);
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::IVec3;
+
+    use super::*;
+    use crate::resources::RenderLayer;
+
+    fn face(pos: IVec3, dir: Direction, texture: u16) -> FullFace {
+        FullFace {
+            pos,
+            dir,
+            layer: RenderLayer::Opaque,
+            texture,
+            colour: [255; 4],
+        }
+    }
+
+    fn merged(faces: Vec<FullFace>) -> ChunkMesh {
+        let mut mesh = ChunkMesh::new();
+        mesh.faces = faces;
+        merge_faces(&mut mesh);
+        mesh
+    }
+
+    #[test]
+    fn flat_layer_becomes_one_quad() {
+        let faces = (0..256)
+            .map(|i| face(IVec3::new(i % 16, 3, i / 16), Direction::Up, 1))
+            .collect();
+        let mesh = merged(faces);
+
+        assert!(mesh.faces.is_empty());
+        assert_eq!(mesh.opaque.vertices.len(), 4);
+        assert_eq!(mesh.opaque.indices.len(), 6);
+        for vert in &mesh.opaque.vertices {
+            let pos = vert.get_position();
+            assert_eq!(pos.y, 4.0);
+            assert!(pos.x == 0.0 || pos.x == 16.0);
+            assert!(pos.z == 0.0 || pos.z == 16.0);
+        }
+    }
+
+    #[test]
+    fn texture_repeats_across_merged_faces() {
+        let faces = (0..4)
+            .map(|x| face(IVec3::new(x, 0, 0), Direction::South, 1))
+            .collect();
+        let mesh = merged(faces);
+
+        assert_eq!(mesh.opaque.vertices.len(), 4);
+        for vert in &mesh.opaque.vertices {
+            let pos = vert.get_position();
+            let u = vert.tex_coords[0] as f32 / crate::renderer::VERTEX_SCALE;
+            assert_eq!(u, pos.x);
+        }
+    }
+
+    #[test]
+    fn different_textures_stay_apart() {
+        let faces = (0..256)
+            .map(|i| {
+                let (x, z) = (i % 16, i / 16);
+                face(IVec3::new(x, 0, z), Direction::Down, ((x + z) % 2) as u16)
+            })
+            .collect();
+        let mesh = merged(faces);
+
+        assert_eq!(mesh.opaque.vertices.len(), 256 * 4);
+    }
+
+    #[test]
+    fn l_shape_needs_two_quads() {
+        let faces = [(0, 0), (1, 0), (2, 0), (0, 1), (0, 2)]
+            .iter()
+            .map(|&(x, y)| face(IVec3::new(x, y, 5), Direction::North, 1))
+            .collect();
+        let mesh = merged(faces);
+
+        assert_eq!(mesh.opaque.vertices.len(), 2 * 4);
+    }
+
+    #[test]
+    fn overlapping_faces_are_kept() {
+        let pos = IVec3::new(2, 2, 2);
+        let mesh = merged(vec![
+            face(pos, Direction::East, 1),
+            face(pos, Direction::East, 2),
+        ]);
+
+        assert_eq!(mesh.opaque.vertices.len(), 2 * 4);
+    }
+}
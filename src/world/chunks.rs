@@ -5,17 +5,20 @@ use std::{
 };
 
 use glam::{IVec2, IVec3};
-use glium::{Display, VertexBuffer};
+use glium::{backend::Facade, index::PrimitiveType, IndexBuffer, VertexBuffer};
 use log::debug;
 use mcproto_rs::{nbt, v1_16_3::ChunkData};
 
 use crate::{
     network::read_varint,
-    renderer::BlockVertex,
-    resources::{BlockState, BLOCKS},
+    renderer::{BlockVertex, ChunkMesh, MeshLayer},
+    resources::{Biome, BlockState, BIOMES, BLOCKS},
 };
 
-use super::{ChunkCoords, ChunkLocation, SectionCoords, SectionLocation, WorldCoords};
+use super::{
+    visibility::SectionVisibility, ChunkCoords, ChunkLocation, SectionCoords, SectionLocation,
+    WorldCoords,
+};
 
 // Base 2 Log of number of state ids in the game
 const MAX_BITS_PER_BLOCK: u32 = 15;
@@ -24,6 +27,9 @@ pub const MAX_SECTION: i32 = 15;
 pub const MIN_SECTION: i32 = 0;
 pub type BlockIndex = u16;
 pub type ChunkArray = [BlockIndex; 4096];
+pub const BIOMES_PER_CHUNK: usize = 1024;
+pub type BiomeArray = [i32; BIOMES_PER_CHUNK];
+pub type WrappedBiomes = Arc<BiomeArray>;
 
 #[derive(Debug)]
 pub struct ChunkSection {
@@ -51,8 +57,11 @@ impl ChunkSection {
     }
 
     /// Get the block at the provided SectionCoords within this chunk section
-    pub fn block_at(&self, coords: &SectionCoords) -> Option<&'static BlockState> {
-        BLOCKS.get(&self.blocks[block_pos_to_index(coords)].into())
+    pub fn block_at(&self, coords: &SectionCoords) -> Option<Arc<BlockState>> {
+        BLOCKS
+            .load()
+            .get(&self.blocks[block_pos_to_index(coords)].into())
+            .cloned()
     }
 
     /// Get the chunk section index of the section containing the provided y level
@@ -71,10 +80,68 @@ impl ChunkSection {
 
 pub type WrappedChunkSection = Arc<RwLock<ChunkSection>>;
 pub type VBO = VertexBuffer<BlockVertex>;
+pub type IBO = IndexBuffer<u32>;
+
+/// The buffers for one render pass of a chunk section
+pub struct LayerBuffers {
+    pub vertices: VBO,
+    pub indices: IBO,
+}
+
+impl LayerBuffers {
+    /// Size of the buffers on the GPU in bytes
+    pub fn get_size(&self) -> usize {
+        self.vertices.get_size() + self.indices.get_size()
+    }
+}
+
+/// The vertex buffers for each render pass of a chunk section, which are None if there's nothing
+/// to draw in that pass
+pub struct SectionBuffers {
+    pub opaque: Option<LayerBuffers>,
+    pub cutout: Option<LayerBuffers>,
+    pub translucent: Option<LayerBuffers>,
+    pub visibility: SectionVisibility,
+}
+
+impl SectionBuffers {
+    pub fn new(dis: &impl Facade, mesh: &ChunkMesh) -> SectionBuffers {
+        let upload = |layer: &MeshLayer| {
+            if layer.is_empty() {
+                None
+            } else {
+                Some(LayerBuffers {
+                    vertices: VertexBuffer::new(dis, &layer.vertices).unwrap(),
+                    indices: IndexBuffer::new(dis, PrimitiveType::TrianglesList, &layer.indices)
+                        .unwrap(),
+                })
+            }
+        };
+
+        SectionBuffers {
+            opaque: upload(&mesh.opaque),
+            cutout: upload(&mesh.cutout),
+            translucent: upload(&mesh.translucent),
+            visibility: mesh.visibility,
+        }
+    }
+
+    /// Size of all the buffers on the GPU in bytes
+    pub fn get_size(&self) -> usize {
+        self.opaque
+            .iter()
+            .chain(&self.cutout)
+            .chain(&self.translucent)
+            .map(|layer| layer.get_size())
+            .sum()
+    }
+}
+
 pub struct Chunk {
     pos: ChunkLocation,
     heightmap: [u16; 256],
-    sections: [Option<(WrappedChunkSection, Option<VBO>)>; SECTIONS_PER_CHUNK],
+    biomes: Option<WrappedBiomes>,
+    sections: [Option<(WrappedChunkSection, Option<SectionBuffers>)>; SECTIONS_PER_CHUNK],
 }
 
 impl Chunk {
@@ -85,10 +152,40 @@ impl Chunk {
             pos: IVec2::new(data.position.x, data.position.z),
 
             heightmap: process_heightmap(data),
+            biomes: process_biomes(data),
             sections: process_sections(data),
         }
     }
 
+    /// A chunk with no blocks in it, for sections to be put into
+    pub fn empty(pos: ChunkLocation) -> Chunk {
+        Chunk {
+            pos,
+            heightmap: [0; 256],
+            biomes: None,
+            sections: Default::default(),
+        }
+    }
+
+    /// Returns the biome ids of this chunk, these are only sent with full chunks so may not be present
+    pub fn get_biomes(&self) -> Option<WrappedBiomes> {
+        self.biomes.clone()
+    }
+
+    pub fn set_biomes(&mut self, biomes: Option<WrappedBiomes>) {
+        self.biomes = biomes;
+    }
+
+    /// Returns the biome at the provided ChunkCoords
+    pub fn biome_at(&self, coords: &ChunkCoords) -> Option<Biome> {
+        self.biomes.as_ref().and_then(|b| {
+            BIOMES
+                .load()
+                .get(&(b[biome_pos_to_index(coords)] as u32))
+                .cloned()
+        })
+    }
+
     /// Returns true if there are any non-air blocks within a section of the chunk
     pub fn is_section_present(&self, y: i32) -> bool {
         self.sections.get(section_to_index(y)).is_some()
@@ -121,15 +218,25 @@ impl Chunk {
             .map(|(s, _)| s.clone())
     }
 
-    pub fn get_section_vbo(&self, y: i32) -> Option<&VertexBuffer<BlockVertex>> {
+    pub fn get_section_buffers(&self, y: i32) -> Option<&SectionBuffers> {
         self.sections
             .get(section_to_index(y))
             .unwrap_or(&None)
             .as_ref()
-            .map(|(_, vbo)| vbo.as_ref())
+            .map(|(_, buffers)| buffers.as_ref())
             .unwrap_or(None)
     }
 
+    /// Size of the meshes for every section of this chunk on the GPU in bytes
+    pub fn get_mesh_size(&self) -> usize {
+        self.sections
+            .iter()
+            .flatten()
+            .filter_map(|(_, buffers)| buffers.as_ref())
+            .map(|buffers| buffers.get_size())
+            .sum()
+    }
+
     pub fn get_section_containing(&self, y: i32) -> Option<WrappedChunkSection> {
         self.get_section(ChunkSection::section_containing_height(y))
     }
@@ -158,14 +265,14 @@ impl Chunk {
         IVec2::new(coords.x.div_floor(16), coords.z.div_floor(16))
     }
 
-    pub fn load_mesh(&mut self, dis: &Display, verts: Vec<BlockVertex>, section: i32) {
+    pub fn load_mesh(&mut self, dis: &impl Facade, mesh: ChunkMesh, section: i32) {
         self.sections.get_mut(section_to_index(section)).map(|cs| {
             cs.as_mut()
-                .map(|cs| cs.1 = Some(VertexBuffer::new(dis, &verts).unwrap()))
+                .map(|cs| cs.1 = Some(SectionBuffers::new(dis, &mesh)))
         });
     }
 
-    pub fn block_at(&self, coords: &ChunkCoords) -> Option<&'static BlockState> {
+    pub fn block_at(&self, coords: &ChunkCoords) -> Option<Arc<BlockState>> {
         self.get_section(ChunkSection::section_containing_height(coords.y))
             .map(|s| {
                 s.read()
@@ -221,10 +328,30 @@ fn process_heightmap(data: &ChunkData) -> [u16; 256] {
     map
 }
 
+/// Extracts the biome array from chunk data
+fn process_biomes(data: &ChunkData) -> Option<WrappedBiomes> {
+    let biomes = data.biomes.as_ref()?;
+    if biomes.len() != BIOMES_PER_CHUNK {
+        log::error!(
+            "Got unexpected number of biomes, expected {} got {}",
+            BIOMES_PER_CHUNK,
+            biomes.len()
+        );
+        return None;
+    }
+
+    let mut out = [0i32; BIOMES_PER_CHUNK];
+    for (i, biome) in biomes.iter().enumerate() {
+        out[i] = biome.0;
+    }
+
+    Some(Arc::new(out))
+}
+
 /// Builds a list of chunk sections from chunk data
 fn process_sections(
     data: &ChunkData,
-) -> [Option<(Arc<RwLock<ChunkSection>>, Option<VertexBuffer<BlockVertex>>)>; 16] {
+) -> [Option<(Arc<RwLock<ChunkSection>>, Option<SectionBuffers>)>; 16] {
     // Check bit mask for which chunk sections are present
     let mut chunk_sections_present = [false; SECTIONS_PER_CHUNK];
     for i in 0..SECTIONS_PER_CHUNK {
@@ -233,7 +360,7 @@ fn process_sections(
         }
     }
 
-    const INIT: Option<(Arc<RwLock<ChunkSection>>, Option<VertexBuffer<BlockVertex>>)> = None;
+    const INIT: Option<(Arc<RwLock<ChunkSection>>, Option<SectionBuffers>)> = None;
     let mut sections = [INIT; SECTIONS_PER_CHUNK];
 
     // Decode data array
@@ -333,6 +460,12 @@ pub fn block_index_to_pos(i: usize) -> IVec3 {
     IVec3::new(x as i32, y as i32, z as i32)
 }
 
+/// Converts a block position within a chunk to an index within the chunk's biome array.
+/// Biomes are stored in 4x4x4 cells
+pub fn biome_pos_to_index(pos: &ChunkCoords) -> usize {
+    ((((pos.y >> 2) & 63) << 4) | (((pos.z >> 2) & 3) << 2) | ((pos.x >> 2) & 3)) as usize
+}
+
 fn section_to_index(loc: i32) -> usize {
     loc.try_into().unwrap()
 }
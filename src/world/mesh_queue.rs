@@ -0,0 +1,161 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use glam::{IVec3, Vec3, Vec3Swizzles};
+
+use crate::renderer::camera::{Camera, ViewFrustum};
+
+use super::{chunk_builder::MeshJob, ChunkLocation, SectionLocation};
+
+/// How much further away sections outside the view are treated as being, so the ones in front of
+/// the camera get built first
+const HIDDEN_PENALTY: f32 = 4.0;
+
+/// Chunk sections waiting to have their meshes built, ordered so the closest visible sections are
+/// built first
+pub struct MeshQueue {
+    queued: HashSet<SectionLocation>,
+    /// Queued sections ordered by priority. Sections can be in here more than once, or after
+    /// they've been cancelled, so only ones still in `queued` are built
+    heap: BinaryHeap<Reverse<(u64, [i32; 3])>>,
+    in_flight: HashMap<SectionLocation, MeshJob>,
+    next_id: u64,
+
+    /// The view the queue was last sorted for
+    cam_section: IVec3,
+    cam_look: Vec3,
+    cam_pos: Vec3,
+    frustum: Option<ViewFrustum>,
+}
+
+impl MeshQueue {
+    pub fn new() -> MeshQueue {
+        MeshQueue {
+            queued: HashSet::new(),
+            heap: BinaryHeap::new(),
+            in_flight: HashMap::new(),
+            next_id: 0,
+
+            cam_section: IVec3::ZERO,
+            cam_look: Vec3::ZERO,
+            cam_pos: Vec3::ZERO,
+            frustum: None,
+        }
+    }
+
+    /// Queues a section to be built, cancelling any mesh already being built for it since that
+    /// will be out of date
+    pub fn push(&mut self, loc: SectionLocation) {
+        if let Some(job) = self.in_flight.remove(&loc) {
+            job.cancel();
+        }
+
+        if self.queued.insert(loc) {
+            self.heap
+                .push(Reverse((self.priority(&loc), loc.to_array())));
+        }
+    }
+
+    /// Stops every section in a chunk from being built
+    pub fn cancel_chunk(&mut self, loc: &ChunkLocation) {
+        self.queued.retain(|s| s.xz() != *loc);
+        self.in_flight.retain(|s, job| {
+            if s.xz() == *loc {
+                job.cancel();
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Re-sorts the queue if the camera has moved into another section or turned far enough
+    pub fn update_view(&mut self, cam: &Camera) {
+        let section = (*cam.get_pos() / 16.0).floor().as_ivec3();
+        let look = cam.get_look_vector();
+        if self.frustum.is_some() && section == self.cam_section && look.dot(self.cam_look) > 0.9 {
+            return;
+        }
+
+        self.cam_section = section;
+        self.cam_look = look;
+        self.cam_pos = *cam.get_pos();
+        self.frustum = Some(cam.generate_view_frustum());
+
+        self.heap = self
+            .queued
+            .iter()
+            .map(|loc| Reverse((self.priority(loc), loc.to_array())))
+            .collect();
+    }
+
+    /// Takes the next section to build, unless `max_in_flight` meshes are already being built
+    pub fn pop(&mut self, max_in_flight: usize) -> Option<(SectionLocation, MeshJob)> {
+        while self.in_flight.len() < max_in_flight {
+            let Reverse((_, loc)) = self.heap.pop()?;
+            let loc = IVec3::from(loc);
+            if !self.queued.remove(&loc) {
+                continue;
+            }
+
+            let job = MeshJob::new(self.next_id);
+            self.next_id += 1;
+            self.in_flight.insert(loc, job.clone());
+            return Some((loc, job));
+        }
+
+        None
+    }
+
+    /// Marks a mesh as finished, returning false if it's been cancelled and should be thrown away
+    pub fn finish(&mut self, loc: &SectionLocation, id: u64) -> bool {
+        match self.in_flight.get(loc) {
+            Some(job) if job.id == id => {
+                self.in_flight.remove(loc);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of sections waiting to be built
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    /// Number of sections being built right now
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Distance from the camera to the middle of a section squared, bigger for sections that
+    /// can't be seen
+    fn priority(&self, loc: &SectionLocation) -> u64 {
+        let min = (*loc * 16).as_vec3();
+        let distance = (min + Vec3::splat(8.0)).distance_squared(self.cam_pos);
+
+        let visible = self
+            .frustum
+            .as_ref()
+            .map(|f| f.accept_box(min, min + Vec3::splat(16.0)))
+            .unwrap_or(true);
+
+        if visible {
+            distance as u64
+        } else {
+            (distance * HIDDEN_PENALTY) as u64
+        }
+    }
+}
+
+impl Default for MeshQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{HashSet, VecDeque};
+
+use glam::{Vec3, Vec3Swizzles};
+
+use crate::{renderer::camera::ViewFrustum, resources::block_models::Direction};
+
+use super::{
+    chunks::{block_index_to_pos, block_pos_to_index, MAX_SECTION, MIN_SECTION},
+    SectionLocation, World,
+};
+
+/// Which sides of a chunk section can be seen from which other sides, by looking through the
+/// blocks in it that aren't opaque
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionVisibility(u64);
+
+impl SectionVisibility {
+    /// Every side can be seen from every other side, like in a section with no solid blocks
+    pub const ALL: SectionVisibility = SectionVisibility((1 << 36) - 1);
+    /// No side can be seen from any other side
+    pub const NONE: SectionVisibility = SectionVisibility(0);
+
+    /// Flood fills through the blocks that aren't opaque to find which sides of the section are
+    /// joined together. `is_opaque` is given the index of each block in the section.
+    pub fn from_blocks(is_opaque: impl Fn(usize) -> bool) -> SectionVisibility {
+        let mut visited = [false; 4096];
+        let mut any_opaque = false;
+        for (i, visited) in visited.iter_mut().enumerate() {
+            if is_opaque(i) {
+                *visited = true;
+                any_opaque = true;
+            }
+        }
+        if !any_opaque {
+            return Self::ALL;
+        }
+
+        let mut visibility = Self::NONE;
+        let mut stack = Vec::new();
+        for start in 0..4096 {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            stack.push(start);
+
+            // Sides of the section this group of blocks touches
+            let mut sides = Vec::new();
+            while let Some(i) = stack.pop() {
+                let pos = block_index_to_pos(i);
+                for dir in Direction::ALL {
+                    let next = pos + dir.normal();
+                    if next.min_element() < 0 || next.max_element() > 15 {
+                        if !sides.contains(&dir) {
+                            sides.push(dir);
+                        }
+                        continue;
+                    }
+
+                    let next = block_pos_to_index(&next);
+                    if !visited[next] {
+                        visited[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+
+            for a in &sides {
+                for b in &sides {
+                    visibility.connect(*a, *b);
+                }
+            }
+        }
+
+        visibility
+    }
+
+    /// Whether the side `to` can be seen when looking in through the side `from`
+    pub fn connects(&self, from: Direction, to: Direction) -> bool {
+        self.0 & Self::bit(from, to) != 0
+    }
+
+    fn connect(&mut self, a: Direction, b: Direction) {
+        self.0 |= Self::bit(a, b) | Self::bit(b, a);
+    }
+
+    fn bit(a: Direction, b: Direction) -> u64 {
+        1 << (a as u64 * 6 + b as u64)
+    }
+}
+
+impl Default for SectionVisibility {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Finds the chunk sections that might be seen from the camera, by searching outwards from the
+/// section it's in through sides that can be seen through and that are in view. The search
+/// never turns back towards the camera, so it can't go round corners to reach hidden caves.
+pub fn find_visible_sections(
+    world: &World,
+    cam_pos: Vec3,
+    frustum: &ViewFrustum,
+) -> HashSet<SectionLocation> {
+    let mut start = (cam_pos / 16.0).floor().as_ivec3();
+    start.y = start.y.clamp(MIN_SECTION, MAX_SECTION);
+
+    let mut visible = HashSet::new();
+    visible.insert(start);
+
+    // Each section is searched along with the side it was entered through and every direction
+    // travelled to get there
+    let mut queue: VecDeque<(SectionLocation, Option<Direction>, u8)> = VecDeque::new();
+    queue.push_back((start, None, 0));
+
+    while let Some((loc, entered, travelled)) = queue.pop_front() {
+        let visibility = world.get_section_visibility(&loc);
+        for dir in Direction::ALL {
+            let opposite = dir.opposite();
+            if travelled & (1 << opposite as u8) != 0 {
+                continue;
+            }
+            if let Some(entered) = entered {
+                if !visibility.connects(entered, dir) {
+                    continue;
+                }
+            }
+
+            let next = loc + dir.normal();
+            if next.y < MIN_SECTION || next.y > MAX_SECTION || visible.contains(&next) {
+                continue;
+            }
+            if !world.is_chunk_loaded(&next.xz()) {
+                continue;
+            }
+            let min = (next * 16).as_vec3();
+            if !frustum.accept_box(min, min + Vec3::splat(16.0)) {
+                continue;
+            }
+
+            visible.insert(next);
+            queue.push_back((next, Some(opposite), travelled | (1 << dir as u8)));
+        }
+    }
+
+    visible
+}